@@ -0,0 +1,120 @@
+use crate::diff::{self, myers, Edit};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Lines that show up more often than this on the old side are too common to
+/// anchor a match on, like in git.
+const MAX_CHAIN_LENGTH: usize = 64;
+
+/// Diffs with the histogram algorithm, an extension of patience that anchors
+/// on the common region whose lines are the least frequent on the old side
+/// rather than requiring them to be unique, falling back to Myers when the
+/// sides have nothing in common.
+pub fn diff<T: Eq + Hash>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let mut edits = vec![];
+
+    diff_range(old, new, (0, 0), &mut edits);
+
+    edits
+}
+
+fn diff_range<T: Eq + Hash>(old: &[T], new: &[T], offsets: (usize, usize), edits: &mut Vec<Edit>) {
+    diff::around_common_affixes(old, new, offsets, edits, |old, new, offsets, edits| {
+        let (i, j, length) = match rarest_common_region(old, new) {
+            Some(region) => region,
+            None => {
+                diff::extend_shifted(edits, myers::diff(old, new, false), offsets);
+                return;
+            }
+        };
+
+        diff_range(&old[..i], &new[..j], offsets, edits);
+
+        for k in 0..length {
+            edits.push(Edit::Equal(offsets.0 + i + k, offsets.1 + j + k));
+        }
+
+        diff_range(
+            &old[i + length..],
+            &new[j + length..],
+            (offsets.0 + i + length, offsets.1 + j + length),
+            edits,
+        );
+    });
+}
+
+/// Finds the run of lines common to both sides with the lowest number of
+/// occurrences on the old side, preferring longer runs on ties, as the start
+/// on both sides and its length.
+fn rarest_common_region<T: Eq + Hash>(old: &[T], new: &[T]) -> Option<(usize, usize, usize)> {
+    let mut occurrences: HashMap<&T, Vec<usize>> = HashMap::new();
+
+    for (i, line) in old.iter().enumerate() {
+        occurrences.entry(line).or_default().push(i);
+    }
+
+    // start on the old side, start on the new side, length and occurrences
+    let mut best: Option<(usize, usize, usize, usize)> = None;
+    let mut j = 0;
+
+    while j < new.len() {
+        let mut next_j = j + 1;
+
+        let positions = match occurrences.get(&new[j]) {
+            Some(positions) if positions.len() <= MAX_CHAIN_LENGTH => positions,
+            _ => {
+                j = next_j;
+                continue;
+            }
+        };
+
+        for &i in positions {
+            let (mut start_i, mut start_j) = (i, j);
+
+            while start_i > 0 && start_j > 0 && old[start_i - 1] == new[start_j - 1] {
+                start_i -= 1;
+                start_j -= 1;
+            }
+
+            let (mut end_i, mut end_j) = (i + 1, j + 1);
+
+            while end_i < old.len() && end_j < new.len() && old[end_i] == new[end_j] {
+                end_i += 1;
+                end_j += 1;
+            }
+
+            let count = old[start_i..end_i]
+                .iter()
+                .map(|line| occurrences[line].len())
+                .min()
+                .unwrap();
+            let length = end_i - start_i;
+
+            let better = match best {
+                Some((_, _, best_length, best_count)) => {
+                    count < best_count || (count == best_count && length > best_length)
+                }
+                None => true,
+            };
+
+            if better {
+                best = Some((start_i, start_j, length, count));
+            }
+
+            next_j = next_j.max(end_j);
+        }
+
+        j = next_j;
+    }
+
+    best.map(|(i, j, length, _)| (i, j, length))
+}
+
+#[test]
+fn test_rarest_common_region() {
+    let old = ["}", "a", "b", "}", "c"];
+    let new = ["c", "}", "a", "b", "}"];
+
+    assert_eq!(rarest_common_region(&old, &new), Some((0, 1, 4)));
+    assert_eq!(rarest_common_region(&["a"], &["b"]), None);
+}
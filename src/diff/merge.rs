@@ -0,0 +1,215 @@
+use crate::diff::{self, Algorithm, Edit};
+use std::hash::Hash;
+
+/// A run of lines of a three-way merge, either taken from whichever side
+/// changed it or changed differently on both sides.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Chunk<'a, T> {
+    Resolved(&'a [T]),
+    Conflict {
+        base: &'a [T],
+        ours: &'a [T],
+        theirs: &'a [T],
+    },
+}
+
+/// The position each line of `base` kept in `side`, if it wasn't changed.
+fn matching_lines<T: Eq + Hash>(base: &[T], side: &[T]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; base.len()];
+
+    for edit in diff::diff(base, side, Algorithm::Myers) {
+        if let Edit::Equal(i, j) = edit {
+            matches[i] = Some(j);
+        }
+    }
+
+    matches
+}
+
+/// Merges the changes `ours` and `theirs` made to `base`, as diff3 does:
+/// the lines no side changed split the files into chunks, each taken from
+/// the side that changed it, and a conflict when both did differently.
+pub fn merge<'a, T: Eq + Hash>(base: &'a [T], ours: &'a [T], theirs: &'a [T]) -> Vec<Chunk<'a, T>> {
+    let our_matches = matching_lines(base, ours);
+    let their_matches = matching_lines(base, theirs);
+
+    let mut chunks = vec![];
+    let (mut o, mut a, mut b) = (0, 0, 0);
+
+    while o < base.len() || a < ours.len() || b < theirs.len() {
+        let mut stable = 0;
+
+        while o + stable < base.len()
+            && our_matches[o + stable] == Some(a + stable)
+            && their_matches[o + stable] == Some(b + stable)
+        {
+            stable += 1;
+        }
+
+        if stable > 0 {
+            chunks.push(Chunk::Resolved(&base[o..o + stable]));
+            (o, a, b) = (o + stable, a + stable, b + stable);
+            continue;
+        }
+
+        // the changed chunk runs up to the next line both sides kept
+        let (o_end, a_end, b_end) = (o..base.len())
+            .find_map(|i| Some((i, our_matches[i]?, their_matches[i]?)))
+            .unwrap_or((base.len(), ours.len(), theirs.len()));
+
+        let (base_lines, our_lines, their_lines) =
+            (&base[o..o_end], &ours[a..a_end], &theirs[b..b_end]);
+
+        chunks.push(if our_lines == base_lines {
+            Chunk::Resolved(their_lines)
+        } else if their_lines == base_lines || our_lines == their_lines {
+            Chunk::Resolved(our_lines)
+        } else {
+            Chunk::Conflict {
+                base: base_lines,
+                ours: our_lines,
+                theirs: their_lines,
+            }
+        });
+
+        (o, a, b) = (o_end, a_end, b_end);
+    }
+
+    chunks
+}
+
+fn push_lines(output: &mut Vec<u8>, lines: &[&[u8]]) {
+    for line in lines {
+        output.extend(*line);
+    }
+}
+
+/// Merges the contents of a file changed on both sides, putting conflicts
+/// between markers named after each side. The lines a conflict starts or
+/// ends with on both sides are left out of it, as git does. Returns the
+/// merged contents and whether there was a conflict.
+pub fn merge_files(
+    base: &[u8],
+    ours: &[u8],
+    theirs: &[u8],
+    (our_name, their_name): (&str, &str),
+) -> (Vec<u8>, bool) {
+    let (base, ours, theirs) = (
+        diff::split_lines(base),
+        diff::split_lines(ours),
+        diff::split_lines(theirs),
+    );
+
+    let mut output = vec![];
+    let mut conflicted = false;
+
+    for chunk in merge(&base, &ours, &theirs) {
+        let (ours, theirs) = match chunk {
+            Chunk::Resolved(lines) => {
+                push_lines(&mut output, lines);
+                continue;
+            }
+            Chunk::Conflict { ours, theirs, .. } => (ours, theirs),
+        };
+
+        let prefix = ours.iter().zip(theirs).take_while(|(a, b)| a == b).count();
+        let suffix = ours[prefix..]
+            .iter()
+            .rev()
+            .zip(theirs[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        push_lines(&mut output, &ours[..prefix]);
+
+        for (marker, lines) in [
+            (
+                format!("<<<<<<< {}\n", our_name),
+                &ours[prefix..ours.len() - suffix],
+            ),
+            (
+                "=======\n".to_string(),
+                &theirs[prefix..theirs.len() - suffix],
+            ),
+        ] {
+            output.extend(marker.as_bytes());
+            push_lines(&mut output, lines);
+
+            // a last line without a newline would run into the marker
+            if !output.ends_with(b"\n") {
+                output.push(b'\n');
+            }
+        }
+
+        output.extend(format!(">>>>>>> {}\n", their_name).as_bytes());
+        push_lines(&mut output, &ours[ours.len() - suffix..]);
+
+        conflicted = true;
+    }
+
+    (output, conflicted)
+}
+
+#[test]
+fn test_merge() {
+    let base = ["a", "b", "c", "d", "e"];
+    let ours = ["a", "B", "c", "d", "e", "f"];
+    let theirs = ["a", "b", "c", "D", "e"];
+
+    assert_eq!(
+        merge(&base, &ours, &theirs),
+        vec![
+            Chunk::Resolved(&["a"][..]),
+            Chunk::Resolved(&["B"][..]),
+            Chunk::Resolved(&["c"][..]),
+            Chunk::Resolved(&["D"][..]),
+            Chunk::Resolved(&["e"][..]),
+            Chunk::Resolved(&["f"][..]),
+        ]
+    );
+
+    assert_eq!(
+        merge(
+            &base,
+            &["a", "x", "c", "d", "e"],
+            &["a", "y", "c", "d", "e"]
+        ),
+        vec![
+            Chunk::Resolved(&["a"][..]),
+            Chunk::Conflict {
+                base: &["b"][..],
+                ours: &["x"][..],
+                theirs: &["y"][..],
+            },
+            Chunk::Resolved(&["c", "d", "e"][..]),
+        ]
+    );
+}
+
+#[test]
+fn test_merge_files() {
+    let base = b"one\ntwo\nthree\nfour\n";
+
+    assert_eq!(
+        merge_files(
+            base,
+            b"one\n2\nthree\nfour\n",
+            b"one\ntwo\nthree\n4\n",
+            ("HEAD", "topic")
+        ),
+        (b"one\n2\nthree\n4\n".to_vec(), false)
+    );
+
+    assert_eq!(
+        merge_files(
+            b"one\ntwo\n",
+            b"one\nsame\nours",
+            b"one\nsame\ntheirs",
+            ("HEAD", "topic")
+        ),
+        (
+            b"one\nsame\n<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> topic\n".to_vec(),
+            true
+        )
+    );
+}
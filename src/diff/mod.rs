@@ -0,0 +1,525 @@
+use std::hash::Hash;
+use std::str::FromStr;
+
+pub mod histogram;
+pub mod merge;
+pub mod myers;
+pub mod patience;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    #[default]
+    Myers,
+    /// Myers without giving up on the smallest diff when finding it takes
+    /// too many edits, which `myers` does to stay fast on large inputs.
+    Minimal,
+    Patience,
+    Histogram,
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "myers" | "default" => Ok(Self::Myers),
+            "minimal" => Ok(Self::Minimal),
+            "patience" => Ok(Self::Patience),
+            "histogram" => Ok(Self::Histogram),
+            _ => Err(
+                "error: option diff-algorithm accepts \"myers\", \"minimal\", \"patience\" and \"histogram\""
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+/// One step of an edit script, holding the positions of the lines it
+/// refers to on the old and new side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+impl Edit {
+    fn shift(self, (old_offset, new_offset): (usize, usize)) -> Self {
+        match self {
+            Self::Equal(i, j) => Self::Equal(i + old_offset, j + new_offset),
+            Self::Delete(i) => Self::Delete(i + old_offset),
+            Self::Insert(j) => Self::Insert(j + new_offset),
+        }
+    }
+}
+
+/// Computes the edit script turning `old` into `new`, which are usually the
+/// lines of two files.
+pub fn diff<T: Eq + Hash>(old: &[T], new: &[T], algorithm: Algorithm) -> Vec<Edit> {
+    let edits = match algorithm {
+        Algorithm::Myers => myers::diff(old, new, false),
+        Algorithm::Minimal => myers::diff(old, new, true),
+        Algorithm::Patience => patience::diff(old, new),
+        Algorithm::Histogram => histogram::diff(old, new),
+    };
+
+    let mut old_changed = vec![false; old.len()];
+    let mut new_changed = vec![false; new.len()];
+
+    for edit in edits {
+        match edit {
+            Edit::Delete(i) => old_changed[i] = true,
+            Edit::Insert(j) => new_changed[j] = true,
+            Edit::Equal(_, _) => {}
+        }
+    }
+
+    compact(old, &mut old_changed, &new_changed);
+    compact(new, &mut new_changed, &old_changed);
+
+    let (mut i, mut j) = (0, 0);
+    let mut edits = vec![];
+
+    while i < old.len() || j < new.len() {
+        if i < old.len() && old_changed[i] {
+            edits.push(Edit::Delete(i));
+            i += 1;
+        } else if j < new.len() && new_changed[j] {
+            edits.push(Edit::Insert(j));
+            j += 1;
+        } else {
+            edits.push(Edit::Equal(i, j));
+            i += 1;
+            j += 1;
+        }
+    }
+
+    edits
+}
+
+/// A run of changed lines, which can be empty, of one side of a diff.
+/// Between every two unchanged lines there is a group on each side, so the
+/// groups of both sides correspond to each other.
+#[derive(Clone, Copy)]
+struct Group {
+    start: usize,
+    end: usize,
+}
+
+impl Group {
+    fn first(changed: &[bool]) -> Self {
+        let end = changed.iter().take_while(|changed| **changed).count();
+
+        Self { start: 0, end }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    fn next(&mut self, changed: &[bool]) -> bool {
+        if self.end == changed.len() {
+            return false;
+        }
+
+        self.start = self.end + 1;
+        self.end = self.start;
+
+        while self.end < changed.len() && changed[self.end] {
+            self.end += 1;
+        }
+
+        true
+    }
+
+    fn previous(&mut self, changed: &[bool]) -> bool {
+        if self.start == 0 {
+            return false;
+        }
+
+        self.end = self.start - 1;
+        self.start = self.end;
+
+        while self.start > 0 && changed[self.start - 1] {
+            self.start -= 1;
+        }
+
+        true
+    }
+
+    /// Moves the group one line up when the line before it is the same as
+    /// its last line, merging it with the group before if they then touch.
+    fn slide_up<T: PartialEq>(&mut self, lines: &[T], changed: &mut [bool]) -> bool {
+        if self.start == 0 || lines[self.start - 1] != lines[self.end - 1] {
+            return false;
+        }
+
+        self.start -= 1;
+        self.end -= 1;
+        changed[self.start] = true;
+        changed[self.end] = false;
+
+        while self.start > 0 && changed[self.start - 1] {
+            self.start -= 1;
+        }
+
+        true
+    }
+
+    fn slide_down<T: PartialEq>(&mut self, lines: &[T], changed: &mut [bool]) -> bool {
+        if self.end == lines.len() || lines[self.start] != lines[self.end] {
+            return false;
+        }
+
+        changed[self.start] = false;
+        changed[self.end] = true;
+        self.start += 1;
+        self.end += 1;
+
+        while self.end < lines.len() && changed[self.end] {
+            self.end += 1;
+        }
+
+        true
+    }
+}
+
+/// Slides groups of changed lines whose position is ambiguous, like git
+/// does after diffing: a group is merged with any other it can reach, then
+/// lined up with a change on the other side if possible, and otherwise moved
+/// as far down as it goes. Git's indent heuristic isn't implemented.
+fn compact<T: PartialEq>(lines: &[T], changed: &mut [bool], other_changed: &[bool]) {
+    let mut group = Group::first(changed);
+    let mut other = Group::first(other_changed);
+
+    loop {
+        if !group.is_empty() {
+            let mut end_matching_other;
+            let mut earliest_end;
+
+            loop {
+                let size = group.end - group.start;
+
+                while group.slide_up(lines, changed) {
+                    other.previous(other_changed);
+                }
+
+                earliest_end = group.end;
+                end_matching_other = if other.is_empty() {
+                    None
+                } else {
+                    Some(group.end)
+                };
+
+                while group.slide_down(lines, changed) {
+                    other.next(other_changed);
+
+                    if !other.is_empty() {
+                        end_matching_other = Some(group.end);
+                    }
+                }
+
+                if size == group.end - group.start {
+                    break;
+                }
+            }
+
+            if let Some(end) = end_matching_other {
+                if group.end != earliest_end {
+                    while other.is_empty() && group.end > end {
+                        group.slide_up(lines, changed);
+                        other.previous(other_changed);
+                    }
+                }
+            }
+        }
+
+        if !group.next(changed) {
+            break;
+        }
+
+        other.next(other_changed);
+    }
+}
+
+/// Splits contents into lines, each keeping its line feed so that a missing
+/// one at the end of a file shows up as a change.
+pub fn split_lines(contents: &[u8]) -> Vec<&[u8]> {
+    contents.split_inclusive(|byte| *byte == b'\n').collect()
+}
+
+fn extend_shifted(edits: &mut Vec<Edit>, new_edits: Vec<Edit>, offsets: (usize, usize)) {
+    edits.extend(new_edits.into_iter().map(|edit| edit.shift(offsets)));
+}
+
+/// Matches the lines both sides start and end with, leaving what is between
+/// them to `diff_middle`, which is given the offsets of its slices. When one
+/// of the sides has nothing left, the other one is all insertions or all
+/// deletions.
+fn around_common_affixes<T: PartialEq>(
+    old: &[T],
+    new: &[T],
+    offsets: (usize, usize),
+    edits: &mut Vec<Edit>,
+    diff_middle: impl FnOnce(&[T], &[T], (usize, usize), &mut Vec<Edit>),
+) {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    for k in 0..prefix {
+        edits.push(Edit::Equal(offsets.0 + k, offsets.1 + k));
+    }
+
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+    let middle_offsets = (offsets.0 + prefix, offsets.1 + prefix);
+
+    if old_middle.is_empty() || new_middle.is_empty() {
+        for i in 0..old_middle.len() {
+            edits.push(Edit::Delete(middle_offsets.0 + i));
+        }
+        for j in 0..new_middle.len() {
+            edits.push(Edit::Insert(middle_offsets.1 + j));
+        }
+    } else {
+        diff_middle(old_middle, new_middle, middle_offsets, edits);
+    }
+
+    for k in 0..suffix {
+        edits.push(Edit::Equal(
+            offsets.0 + old.len() - suffix + k,
+            offsets.1 + new.len() - suffix + k,
+        ));
+    }
+}
+
+fn hunk_range(start: usize, count: usize) -> String {
+    // an empty range points at the line before it
+    let start = if count == 0 { start } else { start + 1 };
+
+    if count == 1 {
+        format!("{}", start)
+    } else {
+        format!("{},{}", start, count)
+    }
+}
+
+/// Finds the line shown after a hunk header, which like in git is the
+/// closest line before the hunk starting with a letter, `_` or `$`.
+fn function_context(lines: &[&[u8]], before: usize) -> Option<String> {
+    lines[..before]
+        .iter()
+        .rev()
+        .find(|line| matches!(line.first(), Some(c) if c.is_ascii_alphabetic() || *c == b'_' || *c == b'$'))
+        .map(|line| {
+            String::from_utf8_lossy(line)
+                .chars()
+                .take(80)
+                .collect::<String>()
+                .trim_end()
+                .to_string()
+        })
+}
+
+fn format_line(prefix: char, line: &[u8], output: &mut Vec<String>) {
+    let text = String::from_utf8_lossy(line);
+
+    output.push(format!("{}{}", prefix, text.trim_end_matches('\n')));
+
+    if !line.ends_with(b"\n") {
+        output.push("\\ No newline at end of file".to_string());
+    }
+}
+
+/// Formats the differences between two files as unified diff hunks with
+/// `context` unchanged lines around every change.
+pub fn unified_diff(
+    old_contents: &[u8],
+    new_contents: &[u8],
+    context: usize,
+    algorithm: Algorithm,
+) -> Vec<String> {
+    let old = split_lines(old_contents);
+    let new = split_lines(new_contents);
+    let edits = diff(&old, &new, algorithm);
+
+    // line positions in both files before each edit
+    let mut positions = vec![];
+    let (mut old_line, mut new_line) = (0, 0);
+
+    for edit in &edits {
+        positions.push((old_line, new_line));
+
+        match edit {
+            Edit::Equal(_, _) => {
+                old_line += 1;
+                new_line += 1;
+            }
+            Edit::Delete(_) => old_line += 1,
+            Edit::Insert(_) => new_line += 1,
+        }
+    }
+
+    let changes = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Equal(_, _)))
+        .map(|(i, _)| i);
+
+    // changes closer than twice the context end up in the same hunk
+    let mut hunks: Vec<(usize, usize)> = vec![];
+
+    for change in changes {
+        let start = change.saturating_sub(context);
+        let end = (change + 1 + context).min(edits.len());
+
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.1 => hunk.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut output = vec![];
+
+    for (start, end) in hunks {
+        let hunk = &edits[start..end];
+
+        let old_count = hunk
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Insert(_)))
+            .count();
+        let new_count = hunk
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Delete(_)))
+            .count();
+        let (old_start, new_start) = positions[start];
+
+        let header = format!(
+            "@@ -{} +{} @@",
+            hunk_range(old_start, old_count),
+            hunk_range(new_start, new_count)
+        );
+
+        match function_context(&old, old_start) {
+            Some(function) => output.push(format!("{} {}", header, function)),
+            None => output.push(header),
+        }
+
+        for edit in hunk {
+            match *edit {
+                Edit::Equal(i, _) => format_line(' ', old[i], &mut output),
+                Edit::Delete(i) => format_line('-', old[i], &mut output),
+                Edit::Insert(j) => format_line('+', new[j], &mut output),
+            }
+        }
+    }
+
+    output
+}
+
+#[test]
+fn test_unified_diff() {
+    let old = b"one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\n";
+    let new = b"one\n2\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\neleven";
+
+    assert_eq!(
+        unified_diff(old, new, 3, Algorithm::Myers).join("\n"),
+        "@@ -1,5 +1,5 @@\n \
+         one\n\
+         -two\n\
+         +2\n \
+         three\n \
+         four\n \
+         five\n\
+         @@ -8,3 +8,4 @@ seven\n \
+         eight\n \
+         nine\n \
+         ten\n\
+         +eleven\n\
+         \\ No newline at end of file"
+    );
+
+    assert_eq!(
+        unified_diff(old, new, 4, Algorithm::Myers)
+            .join("\n")
+            .lines()
+            .next()
+            .unwrap(),
+        "@@ -1,10 +1,11 @@"
+    );
+    assert_eq!(
+        unified_diff(b"", b"new\n", 3, Algorithm::Myers).join("\n"),
+        "@@ -0,0 +1 @@\n+new"
+    );
+}
+
+#[test]
+fn test_unified_diff_reordered_functions() {
+    let old = b"int add(int a, int b)\n{\n    return a + b;\n}\n\n\
+                int sub(int a, int b)\n{\n    return a - b;\n}\n";
+    let new = b"int sub(int a, int b)\n{\n    return a - b;\n}\n\n\
+                int add(int a, int b)\n{\n    return a + b;\n}\n";
+
+    // patience and histogram keep `sub` whole and move `add` below it
+    let expected = "@@ -1,9 +1,9 @@\n\
+                    -int add(int a, int b)\n\
+                    -{\n\
+                    -    return a + b;\n\
+                    -}\n\
+                    -\n \
+                    int sub(int a, int b)\n \
+                    {\n     \
+                    return a - b;\n \
+                    }\n\
+                    +\n\
+                    +int add(int a, int b)\n\
+                    +{\n\
+                    +    return a + b;\n\
+                    +}";
+
+    assert_eq!(
+        unified_diff(old, new, 3, Algorithm::Patience).join("\n"),
+        expected
+    );
+    assert_eq!(
+        unified_diff(old, new, 3, Algorithm::Histogram).join("\n"),
+        expected
+    );
+
+    // Myers only looks for the fewest changed lines
+    assert_eq!(
+        unified_diff(old, new, 3, Algorithm::Myers).join("\n"),
+        "@@ -1,9 +1,9 @@\n\
+         -int add(int a, int b)\n\
+         +int sub(int a, int b)\n \
+         {\n\
+         -    return a + b;\n\
+         +    return a - b;\n \
+         }\n \
+         \n\
+         -int sub(int a, int b)\n\
+         +int add(int a, int b)\n \
+         {\n\
+         -    return a - b;\n\
+         +    return a + b;\n \
+         }"
+    );
+}
+
+#[test]
+fn test_algorithm_from_str() {
+    assert_eq!("histogram".parse(), Ok(Algorithm::Histogram));
+    assert_eq!(
+        "fast".parse::<Algorithm>().unwrap_err(),
+        "error: option diff-algorithm accepts \"myers\", \"minimal\", \"patience\" and \"histogram\""
+    );
+}
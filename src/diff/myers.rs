@@ -0,0 +1,172 @@
+use crate::diff::{self, Edit};
+
+/// Git's limit on the edits searched for the middle of a path, which grows
+/// with the square root of the input size.
+const MIN_MAX_COST: isize = 256;
+
+/// Finds an edit script turning `old` into `new` with the Myers algorithm,
+/// in linear space: the middle of an optimal path is found by searching from
+/// both ends at once, and each half is diffed the same way. Unless `minimal`
+/// is set, a search that takes too many edits settles for the point it got
+/// furthest to, as git does, so the script may not be the shortest one.
+pub fn diff<T: PartialEq>(old: &[T], new: &[T], minimal: bool) -> Vec<Edit> {
+    let mut edits = vec![];
+
+    diff_range(old, new, (0, 0), minimal, &mut edits);
+
+    edits
+}
+
+fn diff_range<T: PartialEq>(
+    old: &[T],
+    new: &[T],
+    offsets: (usize, usize),
+    minimal: bool,
+    edits: &mut Vec<Edit>,
+) {
+    diff::around_common_affixes(old, new, offsets, edits, |old, new, offsets, edits| {
+        match middle_point(old, new, minimal) {
+            Some((x, y)) => {
+                diff_range(&old[..x], &new[..y], offsets, minimal, edits);
+                diff_range(
+                    &old[x..],
+                    &new[y..],
+                    (offsets.0 + x, offsets.1 + y),
+                    minimal,
+                    edits,
+                );
+            }
+            None => {
+                edits.extend((0..old.len()).map(|i| Edit::Delete(offsets.0 + i)));
+                edits.extend((0..new.len()).map(|j| Edit::Insert(offsets.1 + j)));
+            }
+        }
+    });
+}
+
+/// Walks the furthest reaching paths from the start and from the end, one
+/// edit at a time each, until they overlap on a diagonal, and returns a
+/// point of the optimal path where they meet. Paths running off the edit
+/// graph are dropped by narrowing the diagonals searched. Both sides are
+/// non-empty and differ at their first and last line, so the point is never
+/// one of the ends. When the paths don't meet, no line is common to both.
+fn middle_point<T: PartialEq>(old: &[T], new: &[T], minimal: bool) -> Option<(usize, usize)> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = (n + m + 1) / 2;
+    let index = |k: isize| (k + max) as usize;
+
+    let max_cost = MIN_MAX_COST.max(((n + m + 3) as f64).sqrt() as isize);
+    // the point each direction got furthest to, as x + y and the point
+    let mut forward_best = (0, (0, 0));
+    let mut backward_best = (0, (n, m));
+
+    // how far along `old` each diagonal got, from the start and the end
+    let mut forward = vec![-1; 2 * max as usize + 2];
+    let mut backward = vec![-1; 2 * max as usize + 2];
+    forward[index(1)] = 0;
+    backward[index(1)] = 0;
+
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let (mut forward_start, mut forward_end) = (0, 0);
+    let (mut backward_start, mut backward_end) = (0, 0);
+
+    for d in 0..max {
+        for k in (-d + forward_start..=d - forward_end).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[index(k - 1)] < forward[index(k + 1)]) {
+                forward[index(k + 1)]
+            } else {
+                forward[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            forward[index(k)] = x;
+
+            if x > n {
+                forward_end += 2;
+            } else if y > m {
+                forward_start += 2;
+            } else {
+                if odd && (-d..=d).contains(&(delta - k)) {
+                    let backward_x = backward[index(delta - k)];
+
+                    if backward_x != -1 && x >= n - backward_x {
+                        return Some((x as usize, y as usize));
+                    }
+                }
+
+                forward_best = forward_best.max((x + y, (x, y)));
+            }
+        }
+
+        for k in (-d + backward_start..=d - backward_end).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[index(k - 1)] < backward[index(k + 1)]) {
+                backward[index(k + 1)]
+            } else {
+                backward[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+
+            while x < n && y < m && old[(n - x - 1) as usize] == new[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            backward[index(k)] = x;
+
+            if x > n {
+                backward_end += 2;
+            } else if y > m {
+                backward_start += 2;
+            } else {
+                if !odd && (-d..=d).contains(&(delta - k)) {
+                    let forward_x = forward[index(delta - k)];
+
+                    if forward_x != -1 && forward_x >= n - x {
+                        return Some((forward_x as usize, (forward_x - delta + k) as usize));
+                    }
+                }
+
+                backward_best = backward_best.max((x + y, (n - x, m - y)));
+            }
+        }
+
+        // past the limit, split where the search got furthest from its end;
+        // neither side can be at its end, or the paths would have met
+        if !minimal && d + 1 >= max_cost {
+            let (x, y) = if forward_best.0 >= backward_best.0 {
+                forward_best.1
+            } else {
+                backward_best.1
+            };
+
+            return Some((x as usize, y as usize));
+        }
+    }
+
+    None
+}
+
+#[test]
+fn test_diff() {
+    let old = ['a', 'b', 'c', 'a', 'b', 'b', 'a'];
+    let new = ['c', 'b', 'a', 'b', 'a', 'c'];
+
+    let edits = diff(&old, &new, true);
+
+    // the classic example from the paper needs five edits
+    assert_eq!(
+        edits
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Equal(_, _)))
+            .count(),
+        5
+    );
+    assert_eq!(diff::<char>(&[], &[], false), vec![]);
+    assert_eq!(diff(&['a'], &[], false), vec![Edit::Delete(0)]);
+}
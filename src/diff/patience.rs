@@ -0,0 +1,118 @@
+use crate::diff::{self, myers, Edit};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Diffs with the patience algorithm: lines that appear exactly once on both
+/// sides are matched in order and used as anchors, and what is between them
+/// is diffed recursively, falling back to Myers when there is no such line.
+pub fn diff<T: Eq + Hash>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let mut edits = vec![];
+
+    diff_range(old, new, (0, 0), &mut edits);
+
+    edits
+}
+
+fn diff_range<T: Eq + Hash>(old: &[T], new: &[T], offsets: (usize, usize), edits: &mut Vec<Edit>) {
+    diff::around_common_affixes(old, new, offsets, edits, |old, new, offsets, edits| {
+        let anchors = unique_common_lines(old, new);
+
+        if anchors.is_empty() {
+            diff::extend_shifted(edits, myers::diff(old, new, false), offsets);
+            return;
+        }
+
+        let (mut i, mut j) = (0, 0);
+
+        for (anchor_i, anchor_j) in anchors {
+            diff_range(
+                &old[i..anchor_i],
+                &new[j..anchor_j],
+                (offsets.0 + i, offsets.1 + j),
+                edits,
+            );
+            edits.push(Edit::Equal(offsets.0 + anchor_i, offsets.1 + anchor_j));
+
+            i = anchor_i + 1;
+            j = anchor_j + 1;
+        }
+
+        diff_range(&old[i..], &new[j..], (offsets.0 + i, offsets.1 + j), edits);
+    });
+}
+
+/// Pairs up the lines that are unique on both sides and keeps the longest
+/// run of pairs that is in the same order on both.
+fn unique_common_lines<T: Eq + Hash>(old: &[T], new: &[T]) -> Vec<(usize, usize)> {
+    let mut counts: HashMap<&T, (usize, usize, usize)> = HashMap::new();
+
+    for (i, line) in old.iter().enumerate() {
+        let count = counts.entry(line).or_insert((0, 0, 0));
+        count.0 += 1;
+        count.2 = i;
+    }
+
+    let mut pairs = vec![];
+
+    for (j, line) in new.iter().enumerate() {
+        if let Some(count) = counts.get_mut(line) {
+            count.1 += 1;
+
+            if count.1 == 1 {
+                pairs.push((count.2, j, line));
+            }
+        }
+    }
+
+    let pairs = pairs
+        .into_iter()
+        .filter(|(_, _, line)| counts[line].0 == 1 && counts[line].1 == 1)
+        .map(|(i, j, _)| (i, j))
+        .collect::<Vec<(usize, usize)>>();
+
+    longest_increasing_run(pairs)
+}
+
+/// Patience sorting: `pairs` are ordered by their old line, and the longest
+/// subsequence whose new lines are increasing too is picked out of them.
+fn longest_increasing_run(mut pairs: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    pairs.sort_unstable();
+
+    // the top of every pile, and what was on top of the previous pile when
+    // each pair was placed
+    let mut piles: Vec<usize> = vec![];
+    let mut previous: Vec<Option<usize>> = vec![None; pairs.len()];
+
+    for (index, (_, j)) in pairs.iter().enumerate() {
+        let pile = piles.partition_point(|top| pairs[*top].1 < *j);
+
+        if pile > 0 {
+            previous[index] = Some(piles[pile - 1]);
+        }
+
+        if pile == piles.len() {
+            piles.push(index);
+        } else {
+            piles[pile] = index;
+        }
+    }
+
+    let mut run = vec![];
+    let mut current = piles.last().copied();
+
+    while let Some(index) = current {
+        run.push(pairs[index]);
+        current = previous[index];
+    }
+
+    run.reverse();
+    run
+}
+
+#[test]
+fn test_longest_increasing_run() {
+    assert_eq!(
+        longest_increasing_run(vec![(0, 9), (1, 4), (2, 6), (3, 12), (4, 8), (5, 7)]),
+        vec![(1, 4), (2, 6), (5, 7)]
+    );
+}
@@ -2,6 +2,7 @@ use crate::fs::Fs;
 use std::cmp::Eq;
 use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use std::path::{Path, PathBuf};
 use std::str::from_utf8;
@@ -9,7 +10,10 @@ use std::str::from_utf8;
 pub type FileSystem = FakeFs;
 pub type FileMetadata = FakeFileMetadata;
 
-pub struct FakeFileMetadata;
+pub struct FakeFileMetadata {
+    mode: u32,
+    mtime: i64,
+}
 
 impl MetadataExt for FakeFileMetadata {
     fn dev(&self) -> u64 {
@@ -43,7 +47,7 @@ impl MetadataExt for FakeFileMetadata {
         unimplemented!();
     }
     fn mtime(&self) -> i64 {
-        1591510158
+        self.mtime
     }
     fn mtime_nsec(&self) -> i64 {
         unimplemented!();
@@ -67,15 +71,15 @@ impl FakeFileMetadata {
         279
     }
     pub fn permissions(&self) -> FakePermissions {
-        FakePermissions
+        FakePermissions(self.mode)
     }
 }
 
-pub struct FakePermissions;
+pub struct FakePermissions(u32);
 
 impl PermissionsExt for FakePermissions {
     fn mode(&self) -> u32 {
-        33188
+        self.0
     }
     fn set_mode(&mut self, _mode: u32) {
         unimplemented!();
@@ -88,9 +92,18 @@ impl PermissionsExt for FakePermissions {
 pub struct FakeFs {
     files: HashMap<PathBuf, Vec<u8>>,
     directories: HashSet<PathBuf>,
+    modes: HashMap<PathBuf, u32>,
+    mtimes: HashMap<PathBuf, i64>,
     current_directory: String,
 }
 
+impl FakeFs {
+    /// Gives a file another modification time than the one every file has.
+    pub fn set_mtime<P: AsRef<Path>>(&mut self, path: &P, mtime: i64) {
+        self.mtimes.insert(PathBuf::from(path.as_ref()), mtime);
+    }
+}
+
 impl Fs for FakeFs {
     fn access() -> Self {
         let files = HashMap::new();
@@ -104,6 +117,8 @@ impl Fs for FakeFs {
         Self {
             files,
             directories,
+            modes: HashMap::new(),
+            mtimes: HashMap::new(),
             current_directory,
         }
     }
@@ -149,6 +164,9 @@ impl Fs for FakeFs {
     fn current_directory(&self) -> String {
         self.current_directory.clone()
     }
+    fn set_current_directory<P: AsRef<Path>>(&mut self, path: &P) {
+        self.current_directory = path.as_ref().to_str().unwrap().to_string();
+    }
     fn create_file<P: AsRef<Path> + Eq>(&mut self, path: &P) {
         let mut pathbuf = PathBuf::new();
         pathbuf.push(path);
@@ -160,7 +178,14 @@ impl Fs for FakeFs {
         self.files.insert(pathbuf, contents.to_vec());
     }
     fn get_file_contents_as_bytes(&self, file_name: &PathBuf) -> Result<Vec<u8>, String> {
-        match self.files.get(file_name) {
+        let pathbuf = if file_name.is_absolute() {
+            file_name.clone()
+        } else {
+            let mut pathbuf = PathBuf::from(self.current_directory());
+            pathbuf.push(file_name);
+            pathbuf
+        };
+        match self.files.get(&pathbuf) {
             Some(contents) => Ok(contents.to_vec()),
             None => Err(format!(
                 "fatal: Cannot open '{:?}': No such file or directory (os error 2)",
@@ -179,15 +204,47 @@ impl Fs for FakeFs {
         self.files
             .keys()
             .filter(|k| {
-                k.to_str()
-                    .unwrap()
-                    .starts_with(&full_file_path.to_str().unwrap())
+                k.as_os_str()
+                    .as_bytes()
+                    .starts_with(full_file_path.as_os_str().as_bytes())
             })
-            .map(|p| p.clone())
+            .cloned()
             .collect::<Vec<PathBuf>>()
     }
-    fn metadata<P: AsRef<Path>>(&self, _path: &P) -> Result<FileMetadata, String> {
-        // needs to check if path exists
-        Ok(FakeFileMetadata)
+    fn metadata<P: AsRef<Path>>(&self, path: &P) -> Result<FileMetadata, String> {
+        let pathbuf = PathBuf::from(path.as_ref());
+
+        if !self.path_exists(&pathbuf) {
+            return Err("No such file or directory (os error 2)".to_string());
+        }
+
+        let mode = self.modes.get(&pathbuf).copied().unwrap_or(33188);
+        let mtime = self.mtimes.get(&pathbuf).copied().unwrap_or(1591510158);
+
+        Ok(FakeFileMetadata { mode, mtime })
+    }
+    fn remove_file<P: AsRef<Path> + Eq>(&mut self, path: &P) {
+        let mut pathbuf = PathBuf::new();
+        pathbuf.push(path);
+        self.files.remove(&pathbuf);
+        self.modes.remove(&pathbuf);
+        self.mtimes.remove(&pathbuf);
+    }
+    fn set_permissions<P: AsRef<Path> + Eq>(&mut self, path: &P, mode: u32) {
+        let mut pathbuf = PathBuf::new();
+        pathbuf.push(path);
+        self.modes.insert(pathbuf, 0o100000 | mode);
+    }
+    fn get_directory_files_recursively(&self, directory: &PathBuf) -> Vec<PathBuf> {
+        let mut files = self
+            .files
+            .keys()
+            .filter(|k| k.starts_with(directory) && *k != directory)
+            .cloned()
+            .collect::<Vec<PathBuf>>();
+
+        files.sort();
+
+        files
     }
 }
@@ -14,6 +14,7 @@ mod fake;
 #[cfg(test)]
 pub use fake::{FileMetadata, FileSystem};
 
+#[allow(clippy::ptr_arg)]
 pub trait Fs {
     fn access() -> Self;
     fn get_file_contents(&self, file_name: &PathBuf) -> Result<String, String>;
@@ -21,6 +22,7 @@ pub trait Fs {
     fn remove_directory<P: AsRef<Path> + Eq>(&mut self, path: &P);
     fn path_exists<P: AsRef<OsStr> + ?Sized + Eq + AsRef<Path>>(&self, path: &P) -> bool;
     fn current_directory(&self) -> String;
+    fn set_current_directory<P: AsRef<Path>>(&mut self, path: &P);
     fn create_file<P: AsRef<Path> + Eq>(&mut self, path: &P);
     fn write_file<P: AsRef<Path> + Eq>(&mut self, path: &P, contents: &[u8]);
     fn get_file_contents_as_bytes(&self, file_name: &PathBuf) -> Result<Vec<u8>, String>;
@@ -30,4 +32,7 @@ pub trait Fs {
         file_name: &PathBuf,
     ) -> Vec<PathBuf>;
     fn metadata<P: AsRef<Path>>(&self, path: &P) -> Result<FileMetadata, String>;
+    fn remove_file<P: AsRef<Path> + Eq>(&mut self, path: &P);
+    fn set_permissions<P: AsRef<Path> + Eq>(&mut self, path: &P, mode: u32);
+    fn get_directory_files_recursively(&self, directory: &PathBuf) -> Vec<PathBuf>;
 }
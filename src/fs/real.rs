@@ -1,8 +1,8 @@
 use crate::fs::Fs;
 use std::cmp::Eq;
-use std::env::current_dir;
+use std::env::{current_dir, set_current_dir};
 use std::ffi::OsStr;
-use std::fs::{create_dir_all, remove_dir_all, OpenOptions};
+use std::fs::{create_dir_all, remove_dir_all, remove_file, set_permissions, OpenOptions};
 use std::fs::{read_dir, read_to_string, File};
 use std::fs::{Metadata, Permissions};
 use std::io::{Read, Write};
@@ -95,7 +95,7 @@ impl Fs for RealFs {
         Self
     }
     fn get_file_contents(&self, file_name: &PathBuf) -> Result<String, String> {
-        read_to_string(&file_name)
+        read_to_string(file_name)
             .map_err(|err| format!("fatal: Cannot open '{:?}': {}", file_name, err))
     }
     fn create_directory<P: AsRef<Path> + Eq>(&mut self, path: &P) {
@@ -112,11 +112,18 @@ impl Fs for RealFs {
         let current_directory = current_directory_pathbuf.to_str().unwrap();
         current_directory.to_string()
     }
+    fn set_current_directory<P: AsRef<Path>>(&mut self, path: &P) {
+        set_current_dir(path).unwrap();
+    }
     fn create_file<P: AsRef<Path> + Eq>(&mut self, path: &P) {
         File::create(path).unwrap();
     }
     fn write_file<P: AsRef<Path> + Eq>(&mut self, path: &P, contents: &[u8]) {
-        let mut file = OpenOptions::new().write(true).open(path).unwrap();
+        let mut file = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(path)
+            .unwrap();
         file.write_all(contents).unwrap();
     }
     fn get_file_contents_as_bytes(&self, file_name: &PathBuf) -> Result<Vec<u8>, String> {
@@ -153,4 +160,31 @@ impl Fs for RealFs {
 
         Ok(RealFileMetadata(m))
     }
+    fn remove_file<P: AsRef<Path> + Eq>(&mut self, path: &P) {
+        remove_file(path).unwrap();
+    }
+    fn set_permissions<P: AsRef<Path> + Eq>(&mut self, path: &P, mode: u32) {
+        set_permissions(path, Permissions::from_mode(mode)).unwrap();
+    }
+    fn get_directory_files_recursively(&self, directory: &PathBuf) -> Vec<PathBuf> {
+        if !directory.is_dir() {
+            return vec![];
+        }
+
+        let mut files = vec![];
+
+        for entry in read_dir(directory).unwrap().map(Result::unwrap) {
+            let path = entry.path();
+
+            if path.is_dir() {
+                files.append(&mut self.get_directory_files_recursively(&path));
+            } else {
+                files.push(path);
+            }
+        }
+
+        files.sort();
+
+        files
+    }
 }
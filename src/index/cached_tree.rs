@@ -0,0 +1,166 @@
+use crate::object;
+use std::cmp::Ordering;
+
+/// The `TREE` extension: the hashes of the trees last written from the
+/// index, for every directory whose entries haven't changed since, so that
+/// writing a tree doesn't need to hash them again.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CachedTree {
+    /// The directory name, empty for the root.
+    pub name: String,
+    /// How many index entries the tree covers, subdirectories included.
+    pub entry_count: usize,
+    /// `None` once the directory changed, until a tree is written again.
+    pub hash: Option<String>,
+    pub children: Vec<CachedTree>,
+}
+
+/// Subtrees are kept ordered by name length first, as git orders them.
+fn compare_names(a: &str, b: &str) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+impl CachedTree {
+    pub fn child(&self, name: &str) -> Option<&CachedTree> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// The subtree named `name`, created as an invalid one if missing.
+    pub fn child_mut(&mut self, name: &str) -> &mut CachedTree {
+        let position = match self
+            .children
+            .binary_search_by(|child| compare_names(&child.name, name))
+        {
+            Ok(position) => position,
+            Err(position) => {
+                self.children.insert(
+                    position,
+                    CachedTree {
+                        name: name.to_string(),
+                        ..Default::default()
+                    },
+                );
+                position
+            }
+        };
+
+        &mut self.children[position]
+    }
+
+    /// Marks the trees holding `path` as changed, from the root down.
+    pub fn invalidate(&mut self, path: &str) {
+        self.hash = None;
+
+        if let Some((directory, rest)) = path.split_once('/') {
+            if let Some(child) = self
+                .children
+                .iter_mut()
+                .find(|child| child.name == directory)
+            {
+                child.invalidate(rest);
+            }
+        }
+    }
+
+    /// Parses the tree and its subtrees, which follow it depth first, from
+    /// the start of `data`.
+    pub fn parse(data: &[u8], i: &mut usize) -> Result<Self, String> {
+        let corrupt = || "error: corrupt TREE extension\nfatal: index file corrupt".to_string();
+
+        let line_end = data[*i..]
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .ok_or_else(corrupt)?
+            + *i;
+        let line = std::str::from_utf8(&data[*i..line_end]).map_err(|_| corrupt())?;
+        *i = line_end + 1;
+
+        let (name, counts) = line.split_once('\x00').ok_or_else(corrupt)?;
+        let (entry_count, subtrees) = counts.split_once(' ').ok_or_else(corrupt)?;
+        let entry_count = entry_count.parse::<i64>().map_err(|_| corrupt())?;
+        let subtrees = subtrees.parse::<usize>().map_err(|_| corrupt())?;
+
+        // an invalid tree has a negative count and no hash
+        let hash = if entry_count >= 0 {
+            let hash = data.get(*i..*i + 20).ok_or_else(corrupt)?;
+            *i += 20;
+            Some(object::bytes_to_hash(hash))
+        } else {
+            None
+        };
+
+        let mut tree = CachedTree {
+            name: name.to_string(),
+            entry_count: entry_count.max(0) as usize,
+            hash,
+            children: vec![],
+        };
+
+        for _ in 0..subtrees {
+            tree.children.push(Self::parse(data, i)?);
+        }
+
+        tree.children
+            .sort_by(|a, b| compare_names(&a.name, &b.name));
+
+        Ok(tree)
+    }
+
+    pub fn parse_into(&self, output: &mut Vec<u8>) {
+        let entry_count = match self.hash {
+            Some(_) => self.entry_count as i64,
+            None => -1,
+        };
+
+        output.extend(self.name.as_bytes());
+        output.push(b'\x00');
+        output.extend(format!("{} {}\n", entry_count, self.children.len()).as_bytes());
+
+        if let Some(hash) = &self.hash {
+            output.extend(&object::hash_to_bytes(hash));
+        }
+
+        for child in &self.children {
+            child.parse_into(output);
+        }
+    }
+}
+
+#[test]
+fn test_cached_tree_round_trip() {
+    let mut tree = CachedTree {
+        entry_count: 3,
+        hash: Some("bee4a103cd500299b3277749ce3b29fd31f439a0".to_string()),
+        ..Default::default()
+    };
+
+    *tree.child_mut("src") = CachedTree {
+        name: "src".to_string(),
+        entry_count: 2,
+        hash: Some("7d11a85a54c02af57434e2bcd5ea7d7ea303e4ac".to_string()),
+        children: vec![],
+    };
+    tree.child_mut("docs");
+
+    assert_eq!(
+        tree.children
+            .iter()
+            .map(|child| &child.name[..])
+            .collect::<Vec<&str>>(),
+        vec!["src", "docs"]
+    );
+
+    let mut data = vec![];
+    tree.parse_into(&mut data);
+
+    assert!(data.starts_with(b"\x003 2\n"));
+
+    let mut i = 0;
+    assert_eq!(CachedTree::parse(&data, &mut i).unwrap(), tree);
+    assert_eq!(i, data.len());
+
+    tree.invalidate("src/lib.rs");
+
+    assert_eq!(tree.hash, None);
+    assert_eq!(tree.child("src").unwrap().hash, None);
+}
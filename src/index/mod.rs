@@ -1,22 +1,177 @@
+use crate::fs::{FileMetadata, FileSystem, Fs};
+use crate::object::ObjectId;
+use crate::repository;
+use cached_tree::CachedTree;
 use crypto::digest::Digest;
 use crypto::sha1::Sha1;
+use resolve_undo::ResolveUndo;
 use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::convert::TryInto;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::PathBuf;
 
-#[derive(Default, PartialEq, Eq)]
+pub mod cached_tree;
+pub mod resolve_undo;
+
+/// The on-disk format written for a new index: 3 adds extended flags to
+/// entries that need them and 4 also prefix-compresses paths.
+pub const DEFAULT_VERSION: u32 = 2;
+
+/// The index versions that can be read and written.
+pub const VERSIONS: std::ops::RangeInclusive<u32> = 2..=4;
+
+/// The flags of an entry, next to its stage and the length of its path.
+const ASSUME_VALID_FLAG: u16 = 0x8000;
+const EXTENDED_FLAG: u16 = 0x4000;
+
+/// The extended flags, which only exist from version 3.
+const SKIP_WORKTREE_FLAG: u16 = 0x4000;
+const INTENT_TO_ADD_FLAG: u16 = 0x2000;
+
+/// The path of the index file of the repository.
+pub fn path(fs: &FileSystem) -> PathBuf {
+    repository::path(fs).join("index")
+}
+
+/// Reads the index, an index that doesn't exist yet being an empty one.
+pub fn read(fs: &FileSystem) -> Result<Index, String> {
+    let index_path = path(fs);
+
+    if !fs.path_exists(&index_path) {
+        return Ok(Index::default());
+    }
+
+    let index_content = fs.get_file_contents_as_bytes(&index_path)?;
+
+    Index::parse_from_file(&index_content)
+}
+
+pub fn write(fs: &mut FileSystem, mut index: Index) {
+    let index_path = path(fs);
+
+    if !fs.path_exists(&index_path) {
+        fs.create_file(&index_path);
+    }
+
+    index.entries.sort();
+
+    fs.write_file(&index_path, &index.parse_into_file());
+}
+
+pub fn read_entries(fs: &FileSystem) -> Result<Vec<IndexEntry>, String> {
+    Ok(read(fs)?.entries)
+}
+
+/// Replaces the entries of the index, keeping its version and extensions.
+pub fn write_entries(fs: &mut FileSystem, entries: Vec<IndexEntry>) {
+    let mut index = read(fs).unwrap_or_default();
+
+    index.set_entries(entries);
+
+    write(fs, index);
+}
+
+/// Reads the number of bytes a v4 entry strips from the end of the previous
+/// path, encoded the same way as the offsets of `OFS_DELTA` pack entries.
+fn read_strip_length(data: &[u8], i: &mut usize) -> Option<usize> {
+    let mut byte = *data.get(*i)?;
+    let mut value = (byte & 0x7f) as usize;
+    *i += 1;
+
+    while byte & 0x80 != 0 {
+        byte = *data.get(*i)?;
+        value = ((value + 1) << 7) | (byte & 0x7f) as usize;
+        *i += 1;
+    }
+
+    Some(value)
+}
+
+fn write_strip_length(output: &mut Vec<u8>, mut value: usize) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+
+    while value >> 7 != 0 {
+        value = (value >> 7) - 1;
+        bytes.push(0x80 | (value & 0x7f) as u8);
+    }
+
+    output.extend(bytes.iter().rev());
+}
+
+/// Git only records whether a regular file is executable, so any other
+/// permission bits are dropped.
+pub fn normalize_mode(mode: u32) -> u32 {
+    match mode & 0o170000 {
+        0o040000 => 0o040000,
+        0o120000 => 0o120000,
+        0o160000 => 0o160000,
+        _ if mode & 0o111 != 0 => 0o100755,
+        _ => 0o100644,
+    }
+}
+
+/// What git records of the mode of a file in the index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum FileMode {
+    #[default]
+    Regular,
+    Executable,
+    Symlink,
+    /// A submodule, whose commit is recorded instead of a blob.
+    Gitlink,
+}
+
+impl From<u32> for FileMode {
+    fn from(mode: u32) -> Self {
+        match normalize_mode(mode) {
+            0o100755 => FileMode::Executable,
+            0o120000 => FileMode::Symlink,
+            0o160000 => FileMode::Gitlink,
+            _ => FileMode::Regular,
+        }
+    }
+}
+
+impl From<FileMode> for u32 {
+    fn from(mode: FileMode) -> Self {
+        match mode {
+            FileMode::Regular => 0o100644,
+            FileMode::Executable => 0o100755,
+            FileMode::Symlink => 0o120000,
+            FileMode::Gitlink => 0o160000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timestamp {
+    pub seconds: u32,
+    pub nanoseconds: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct IndexEntry {
-    pub ctime_s: [u8; 4],
-    pub ctime_n: [u8; 4],
-    pub mtime_s: [u8; 4],
-    pub mtime_n: [u8; 4],
-    pub dev: [u8; 4],
-    pub ino: [u8; 4],
-    pub mode: [u8; 4],
-    pub uid: [u8; 4],
-    pub gid: [u8; 4],
-    pub size: [u8; 4],
-    pub sha1: [u8; 20],
-    pub flags: [u8; 2],
+    pub ctime: Timestamp,
+    pub mtime: Timestamp,
+    pub dev: u32,
+    pub ino: u32,
+    pub mode: FileMode,
+    pub uid: u32,
+    pub gid: u32,
+    /// The size of the file, truncated to 32 bits like the rest of the stat
+    /// data.
+    pub size: u32,
+    pub hash: ObjectId,
+    /// 0 for a merged entry, otherwise 1 for the common ancestor, 2 for ours
+    /// and 3 for theirs.
+    pub stage: u8,
+    /// The file is assumed unchanged, without looking at it.
+    pub assume_valid: bool,
+    /// The file is left out of the worktree.
+    pub skip_worktree: bool,
+    /// The path was added with `add -N`, its contents not staged yet.
+    pub intent_to_add: bool,
     pub path: Vec<u8>,
 }
 
@@ -26,14 +181,118 @@ impl PartialOrd for IndexEntry {
     }
 }
 
+/// Entries are sorted by path, then by stage for a path in conflict.
 impl Ord for IndexEntry {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.path.cmp(&other.path)
+        self.path
+            .cmp(&other.path)
+            .then(self.stage.cmp(&other.stage))
     }
 }
 
 impl IndexEntry {
-    pub fn parse_from_file(index_content: &[u8]) -> Result<Vec<Self>, String> {
+    /// Records the stat data of the file, so that it can later be told
+    /// unchanged without hashing it.
+    pub fn set_stat(&mut self, metadata: &FileMetadata) {
+        self.ctime.seconds = metadata.ctime() as u32;
+        self.mtime.seconds = metadata.mtime() as u32;
+        self.dev = metadata.dev() as u32;
+        self.ino = metadata.ino() as u32;
+        self.mode = FileMode::from(metadata.permissions().mode());
+        self.uid = metadata.uid();
+        self.gid = metadata.gid();
+        self.size = metadata.len() as u32;
+    }
+
+    /// Whether the stat data recorded for the entry still matches the file,
+    /// in which case its contents can be assumed unchanged without hashing.
+    pub fn matches_stat(&self, metadata: &FileMetadata) -> bool {
+        self.mtime.seconds == metadata.mtime() as u32
+            && self.ctime.seconds == metadata.ctime() as u32
+            && self.dev == metadata.dev() as u32
+            && self.ino == metadata.ino() as u32
+            && self.size == metadata.len() as u32
+            && self.mode == FileMode::from(metadata.permissions().mode())
+    }
+
+    pub fn format_index_entries(entries: Vec<Self>, stage: bool) -> String {
+        let mut output = String::new();
+
+        for entry in &entries {
+            if stage {
+                output.push_str(&format!(
+                    "{:o} {} {}\t",
+                    u32::from(entry.mode),
+                    entry.hash,
+                    entry.stage
+                ));
+            }
+
+            output.push_str(&String::from_utf8_lossy(&entry.path));
+
+            if let Some(last) = entries.last() {
+                if last != entry {
+                    output.push('\n');
+                }
+            }
+        }
+
+        output
+    }
+}
+
+/// The index file: its entries, sorted by path, and the extensions that
+/// follow them.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Index {
+    pub version: u32,
+    pub entries: Vec<IndexEntry>,
+    pub cached_tree: Option<CachedTree>,
+    pub resolve_undo: Vec<ResolveUndo>,
+    /// Optional extensions that aren't understood, kept as they are.
+    pub extensions: Vec<([u8; 4], Vec<u8>)>,
+}
+
+impl Default for Index {
+    fn default() -> Self {
+        Index {
+            version: DEFAULT_VERSION,
+            entries: vec![],
+            cached_tree: None,
+            resolve_undo: vec![],
+            extensions: vec![],
+        }
+    }
+}
+
+impl Index {
+    /// Replaces the entries, invalidating the cached trees of the directories
+    /// whose entries changed.
+    pub fn set_entries(&mut self, entries: Vec<IndexEntry>) {
+        if let Some(cached_tree) = &mut self.cached_tree {
+            let key = |entry: &IndexEntry| {
+                let (path, mode, hash) = (entry.path.clone(), entry.mode, entry.hash);
+                (path, mode, hash, entry.stage, entry.intent_to_add)
+            };
+            let old = self.entries.iter().map(key).collect::<HashSet<_>>();
+            let new = entries.iter().map(key).collect::<HashSet<_>>();
+
+            for (path, ..) in old.symmetric_difference(&new) {
+                cached_tree.invalidate(&String::from_utf8_lossy(path));
+            }
+        }
+
+        self.entries = entries;
+    }
+
+    pub fn parse_from_file(index_content: &[u8]) -> Result<Self, String> {
+        let corrupt = |error: &str| format!("error: {}\nfatal: index file corrupt", error);
+
+        // a header and a checksum at the very least
+        if index_content.len() < 32 {
+            return Err(corrupt("index file smaller than expected"));
+        }
+
         let header = &index_content[..12];
 
         let signature = &header[..4];
@@ -42,9 +301,9 @@ impl IndexEntry {
             return Err("error: bad signature\nfatal: index file corrupt".to_string());
         }
 
-        let version = &header[4..8];
+        let version = u32::from_be_bytes(header[4..8].try_into().unwrap());
         // sanity check of version
-        if version != [0, 0, 0, 2] {
+        if !VERSIONS.contains(&version) {
             return Err("error: bad version\nfatal: index file corrupt".to_string());
         }
 
@@ -68,88 +327,148 @@ impl IndexEntry {
 
         let entry_data = &index_content[12..index_of_checksum];
         let quantity = u32::from_be_bytes(number_of_entries.try_into().unwrap());
-        let mut entries: Vec<Self> = vec![];
+        let mut entries: Vec<IndexEntry> = vec![];
         let mut i = 0;
         let mut count = 1;
+        let mut previous_path = vec![];
 
         while i + 62 < entry_data.len() && count <= quantity {
             count += 1;
-            let fields_end = i + 62;
+            let mut fields_end = i + 62;
             let fields = &entry_data[i..fields_end];
-            let path_end = entry_data
-                .iter()
-                .skip(fields_end)
-                .position(|a| *a == b'\x00')
-                .unwrap()
-                + fields_end;
-            let path = &entry_data[fields_end..path_end];
-
-            let mut entry = Self::default();
-
-            entry.ctime_s.copy_from_slice(&fields[..4]);
-            entry.ctime_n.copy_from_slice(&fields[4..8]);
-            entry.mtime_s.copy_from_slice(&fields[8..12]);
-            entry.mtime_n.copy_from_slice(&fields[12..16]);
-            entry.dev.copy_from_slice(&fields[16..20]);
-            entry.ino.copy_from_slice(&fields[20..24]);
-            entry.mode.copy_from_slice(&fields[24..28]);
-            entry.uid.copy_from_slice(&fields[28..32]);
-            entry.gid.copy_from_slice(&fields[32..36]);
-            entry.size.copy_from_slice(&fields[36..40]);
-
-            entry.sha1.copy_from_slice(&fields[40..60]);
-            entry.flags.copy_from_slice(&fields[60..62]);
-
-            for p in path {
-                entry.path.push(*p);
+
+            let field =
+                |index: usize| u32::from_be_bytes(fields[index * 4..][..4].try_into().unwrap());
+            let flags = u16::from_be_bytes(fields[60..62].try_into().unwrap());
+
+            let mut entry = IndexEntry {
+                ctime: Timestamp {
+                    seconds: field(0),
+                    nanoseconds: field(1),
+                },
+                mtime: Timestamp {
+                    seconds: field(2),
+                    nanoseconds: field(3),
+                },
+                dev: field(4),
+                ino: field(5),
+                mode: FileMode::from(field(6)),
+                uid: field(7),
+                gid: field(8),
+                size: field(9),
+                hash: ObjectId(fields[40..60].try_into().unwrap()),
+                stage: ((flags >> 12) & 3) as u8,
+                assume_valid: flags & ASSUME_VALID_FLAG != 0,
+                ..Default::default()
+            };
+
+            if version >= 3 && flags & EXTENDED_FLAG != 0 {
+                let extended_flags = entry_data
+                    .get(fields_end..fields_end + 2)
+                    .map(|bytes| u16::from_be_bytes(bytes.try_into().unwrap()))
+                    .ok_or_else(|| corrupt("truncated extended flags in index entry"))?;
+
+                if extended_flags & !(SKIP_WORKTREE_FLAG | INTENT_TO_ADD_FLAG) != 0 {
+                    return Err(format!(
+                        "fatal: unknown index entry format 0x{:08x}",
+                        (extended_flags as u32) << 16
+                    ));
+                }
+
+                entry.skip_worktree = extended_flags & SKIP_WORKTREE_FLAG != 0;
+                entry.intent_to_add = extended_flags & INTENT_TO_ADD_FLAG != 0;
+                fields_end += 2;
             }
 
-            entries.push(entry);
+            // version 4 paths only hold what differs from the previous path
+            let mut path_start = fields_end;
 
-            let entry_length = ((62 + path.len() + 8) / 8) * 8;
-            i += entry_length;
-        }
+            if version == 4 {
+                let strip = read_strip_length(entry_data, &mut path_start)
+                    .filter(|strip| *strip <= previous_path.len())
+                    .ok_or_else(|| corrupt("bad path compression in index entry"))?;
+                previous_path.truncate(previous_path.len() - strip);
+                entry.path = previous_path.clone();
+            }
 
-        Ok(entries)
-    }
+            let path_end = entry_data
+                .iter()
+                .skip(path_start)
+                .position(|a| *a == b'\x00')
+                .ok_or_else(|| corrupt("unterminated path in index entry"))?
+                + path_start;
 
-    pub fn format_index_entries(entries: Vec<Self>, stage: bool) -> String {
-        let mut output = String::new();
+            entry
+                .path
+                .extend_from_slice(&entry_data[path_start..path_end]);
 
-        for entry in &entries {
-            if stage {
-                let flags = u16::from_be_bytes(entry.flags.try_into().unwrap());
-                let stage = (flags >> 12) & 3;
+            i = if version == 4 {
+                previous_path = entry.path.clone();
+                path_end + 1
+            } else {
+                i + ((fields_end - i + entry.path.len() + 8) / 8) * 8
+            };
 
-                let mode = u32::from_be_bytes(entry.mode.try_into().unwrap());
-                output.push_str(&format!("{:o} ", mode));
+            entries.push(entry);
+        }
 
-                for s in &entry.sha1 {
-                    output.push_str(&format!("{:02x}", s));
-                }
+        let mut index = Index {
+            version,
+            entries,
+            ..Default::default()
+        };
 
-                output.push_str(&format!(" {:?}\t", stage));
-            }
+        let mut extensions = entry_data.get(i..).unwrap_or_default();
 
-            let path = std::str::from_utf8(&entry.path).unwrap();
-            output.push_str(&path);
+        while extensions.len() >= 8 {
+            let signature: [u8; 4] = extensions[..4].try_into().unwrap();
+            let size = u32::from_be_bytes(extensions[4..8].try_into().unwrap()) as usize;
+            let data = extensions
+                .get(8..8 + size)
+                .ok_or_else(|| corrupt("truncated index extension"))?;
 
-            if let Some(last) = entries.last() {
-                if last != entry {
-                    output.push_str("\n");
+            match &signature {
+                b"TREE" if !data.is_empty() => {
+                    index.cached_tree = Some(CachedTree::parse(data, &mut 0)?);
+                }
+                b"TREE" => {}
+                b"REUC" => index.resolve_undo = ResolveUndo::parse(data)?,
+                // offsets into the file, which no longer hold once it's rewritten
+                b"EOIE" | b"IEOT" => {}
+                // extensions starting with an uppercase letter are optional
+                [b'A'..=b'Z', ..] => index.extensions.push((signature, data.to_vec())),
+                _ => {
+                    return Err(corrupt(&format!(
+                        "index uses {} extension, which we do not understand",
+                        String::from_utf8_lossy(&signature)
+                    )))
                 }
             }
+
+            extensions = &extensions[8 + size..];
         }
 
-        output
+        Ok(index)
     }
 
-    pub fn parse_into_file(entries: Vec<Self>) -> Vec<u8> {
+    /// Serializes the index, versions 2 and 3 being picked between depending
+    /// on whether any entry has extended flags, as git does.
+    pub fn parse_into_file(&self) -> Vec<u8> {
         let mut index_file_bytes = vec![];
 
+        let entries = &self.entries;
+        let extended = entries
+            .iter()
+            .any(|entry| entry.skip_worktree || entry.intent_to_add);
+        let version = match self.version {
+            2 | 3 if extended => 3,
+            2 | 3 => 2,
+            version => version,
+        };
+
         let mut header = {
             let signature = b"DIRC";
-            let version = &[0, 0, 0, 2];
+            let version = &version.to_be_bytes();
             let number_of_entries = &entries.len().to_be_bytes();
 
             [
@@ -162,35 +481,93 @@ impl IndexEntry {
 
         index_file_bytes.append(&mut header);
 
+        let mut previous_path: &[u8] = &[];
+
         for entry in entries {
             let mut entry_bytes = [
-                entry.ctime_s,
-                entry.ctime_n,
-                entry.mtime_s,
-                entry.mtime_n,
+                entry.ctime.seconds,
+                entry.ctime.nanoseconds,
+                entry.mtime.seconds,
+                entry.mtime.nanoseconds,
                 entry.dev,
                 entry.ino,
-                entry.mode,
+                u32::from(entry.mode),
                 entry.uid,
                 entry.gid,
                 entry.size,
             ]
-            .concat()
-            .to_vec();
+            .iter()
+            .flat_map(|field| field.to_be_bytes())
+            .collect::<Vec<u8>>();
+
+            entry_bytes.extend_from_slice(&entry.hash.0);
 
-            entry_bytes.append(&mut entry.sha1.to_vec());
-            entry_bytes.append(&mut entry.flags.to_vec());
-            entry_bytes.append(&mut entry.path.to_vec());
+            let mut flags = ((entry.stage as u16 & 3) << 12) | entry.path.len().min(0xfff) as u16;
+            let mut extended_flags = 0;
 
-            let length = ((62 + entry.path.len() + 8) / 8) * 8;
+            if entry.assume_valid {
+                flags |= ASSUME_VALID_FLAG;
+            }
+
+            if entry.skip_worktree {
+                extended_flags |= SKIP_WORKTREE_FLAG;
+            }
+
+            if entry.intent_to_add {
+                extended_flags |= INTENT_TO_ADD_FLAG;
+            }
 
-            for _ in 0..(length - 62 - entry.path.len()) {
+            if extended_flags != 0 {
+                flags |= EXTENDED_FLAG;
+            }
+
+            entry_bytes.extend_from_slice(&flags.to_be_bytes());
+
+            if extended_flags != 0 {
+                entry_bytes.extend_from_slice(&extended_flags.to_be_bytes());
+            }
+
+            if version == 4 {
+                let common = previous_path
+                    .iter()
+                    .zip(&entry.path)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+
+                write_strip_length(&mut entry_bytes, previous_path.len() - common);
+                entry_bytes.extend_from_slice(&entry.path[common..]);
                 entry_bytes.push(b'\x00');
+                previous_path = &entry.path;
+            } else {
+                let length = ((entry_bytes.len() + entry.path.len() + 8) / 8) * 8;
+
+                entry_bytes.extend_from_slice(&entry.path);
+                entry_bytes.resize(length, b'\x00');
             }
 
             index_file_bytes.append(&mut entry_bytes);
         }
 
+        let mut extensions = vec![];
+
+        if let Some(cached_tree) = &self.cached_tree {
+            let mut data = vec![];
+            cached_tree.parse_into(&mut data);
+            extensions.push((*b"TREE", data));
+        }
+
+        if !self.resolve_undo.is_empty() {
+            let mut data = vec![];
+            ResolveUndo::parse_into(&self.resolve_undo, &mut data);
+            extensions.push((*b"REUC", data));
+        }
+
+        for (signature, data) in extensions.iter().chain(&self.extensions) {
+            index_file_bytes.extend(signature);
+            index_file_bytes.extend(&(data.len() as u32).to_be_bytes());
+            index_file_bytes.extend(data);
+        }
+
         let mut hasher = Sha1::new();
         hasher.input(&index_file_bytes[..]);
         let size = hasher.output_bytes();
@@ -202,3 +579,159 @@ impl IndexEntry {
         index_file_bytes
     }
 }
+
+#[test]
+fn test_normalize_mode() {
+    assert_eq!(normalize_mode(0o100664), 0o100644);
+    assert_eq!(normalize_mode(0o100744), 0o100755);
+    assert_eq!(normalize_mode(0o120777), 0o120000);
+}
+
+#[test]
+fn test_strip_length_round_trip() {
+    for value in &[0, 1, 127, 128, 255, 16511, 16512, 1 << 20] {
+        let mut encoded = vec![];
+        write_strip_length(&mut encoded, *value);
+
+        let mut i = 0;
+        assert_eq!(read_strip_length(&encoded, &mut i), Some(*value));
+        assert_eq!(i, encoded.len());
+    }
+
+    let mut encoded = vec![];
+    write_strip_length(&mut encoded, 128);
+    assert_eq!(encoded, [0x80, 0x00]);
+}
+
+#[test]
+fn test_parse_versions() {
+    let entries = || {
+        vec![
+            IndexEntry {
+                mode: FileMode::Executable,
+                path: b"src/deep/a.rs".to_vec(),
+                ..Default::default()
+            },
+            IndexEntry {
+                path: b"src/deep/b.rs".to_vec(),
+                intent_to_add: true,
+                ..Default::default()
+            },
+            IndexEntry {
+                mtime: Timestamp {
+                    seconds: 1591510158,
+                    nanoseconds: 1000,
+                },
+                size: 279,
+                stage: 2,
+                assume_valid: true,
+                skip_worktree: true,
+                path: b"src/lib.rs".to_vec(),
+                ..Default::default()
+            },
+        ]
+    };
+
+    for version in 2..=4 {
+        let file = Index {
+            version,
+            entries: entries(),
+            ..Default::default()
+        }
+        .parse_into_file();
+
+        // version 2 can't hold the extended flags
+        assert_eq!(&file[4..8], &version.max(3).to_be_bytes());
+
+        let parsed = Index::parse_from_file(&file).unwrap().entries;
+
+        assert_eq!(parsed, entries());
+    }
+
+    // the second path only stores "b.rs", stripping "a.rs" from the first
+    let file = Index {
+        version: 4,
+        entries: entries(),
+        ..Default::default()
+    }
+    .parse_into_file();
+    let second = 12 + 62 + 1 + "src/deep/a.rs".len() + 1;
+    assert_eq!(&file[second + 60..second + 64], &[0x40, 13, 0x20, 0x00]);
+    assert_eq!(&file[second + 64..second + 64 + 6], b"\x04b.rs\x00");
+
+    let third = second + 64 + 6;
+    assert_eq!(&file[third + 24..third + 28], &0o100644u32.to_be_bytes());
+    assert_eq!(&file[third + 60..third + 64], &[0xe0, 10, 0x40, 0x00]);
+
+    let mut without_extended = entries();
+    without_extended[1].intent_to_add = false;
+    without_extended[2].skip_worktree = false;
+
+    assert_eq!(
+        &Index {
+            version: 3,
+            entries: without_extended,
+            ..Default::default()
+        }
+        .parse_into_file()[4..8],
+        &[0, 0, 0, 2]
+    );
+}
+
+#[test]
+fn test_parse_extensions() {
+    let with_extensions = |extensions: &[(&[u8; 4], &[u8])]| {
+        let mut file = Index::default().parse_into_file();
+        file.truncate(file.len() - 20);
+
+        for (signature, data) in extensions {
+            file.extend(*signature);
+            file.extend(&(data.len() as u32).to_be_bytes());
+            file.extend(*data);
+        }
+
+        let mut hasher = Sha1::new();
+        hasher.input(&file);
+        let mut checksum = vec![0; hasher.output_bytes()];
+        hasher.result(&mut checksum);
+        file.extend(checksum);
+
+        file
+    };
+
+    let index = Index {
+        cached_tree: Some(CachedTree::default()),
+        resolve_undo: vec![ResolveUndo {
+            path: b"file.txt".to_vec(),
+            ..Default::default()
+        }],
+        extensions: vec![(*b"UNTR", b"untracked".to_vec())],
+        ..Default::default()
+    };
+    let file = index.parse_into_file();
+
+    assert_eq!(Index::parse_from_file(&file).unwrap(), index);
+
+    // offsets into the file are dropped, as rewriting it invalidates them
+    let file = with_extensions(&[(b"EOIE", b"offsets"), (b"UNTR", b"untracked")]);
+
+    assert_eq!(
+        Index::parse_from_file(&file).unwrap().extensions,
+        vec![(*b"UNTR", b"untracked".to_vec())]
+    );
+
+    let file = with_extensions(&[(b"link", b"split index")]);
+
+    assert_eq!(
+        Index::parse_from_file(&file).unwrap_err(),
+        "error: index uses link extension, which we do not understand\nfatal: index file corrupt"
+    );
+}
+
+#[test]
+fn test_file_mode() {
+    assert_eq!(FileMode::from(0o100664), FileMode::Regular);
+    assert_eq!(FileMode::from(0o100744), FileMode::Executable);
+    assert_eq!(FileMode::from(0o120777), FileMode::Symlink);
+    assert_eq!(u32::from(FileMode::Gitlink), 0o160000);
+}
@@ -0,0 +1,108 @@
+use crate::object;
+
+/// Reads a field terminated by a NUL, as the path and the three modes are.
+fn next_field<'a>(data: &'a [u8], i: &mut usize) -> Option<&'a [u8]> {
+    let end = data[*i..].iter().position(|byte| *byte == b'\x00')? + *i;
+    let field = &data[*i..end];
+    *i = end + 1;
+
+    Some(field)
+}
+
+/// An entry of the `REUC` extension: the stages a conflicted path had before
+/// it was resolved, so that the conflict can be recreated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolveUndo {
+    pub path: Vec<u8>,
+    /// The mode and hash of stages 1 to 3, when they existed.
+    pub stages: [Option<(u32, String)>; 3],
+}
+
+impl ResolveUndo {
+    pub fn parse(data: &[u8]) -> Result<Vec<Self>, String> {
+        let corrupt = || "error: corrupt REUC extension\nfatal: index file corrupt".to_string();
+
+        let mut entries = vec![];
+        let mut i = 0;
+
+        while i < data.len() {
+            let mut entry = ResolveUndo {
+                path: next_field(data, &mut i).ok_or_else(corrupt)?.to_vec(),
+                ..Default::default()
+            };
+
+            let mut modes = [0; 3];
+
+            for mode in modes.iter_mut() {
+                let field = std::str::from_utf8(next_field(data, &mut i).ok_or_else(corrupt)?)
+                    .map_err(|_| corrupt())?;
+                *mode = u32::from_str_radix(field, 8).map_err(|_| corrupt())?;
+            }
+
+            for (stage, mode) in entry.stages.iter_mut().zip(modes) {
+                if mode != 0 {
+                    let hash = data.get(i..i + 20).ok_or_else(corrupt)?;
+                    *stage = Some((mode, object::bytes_to_hash(hash)));
+                    i += 20;
+                }
+            }
+
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+
+    pub fn parse_into(entries: &[Self], output: &mut Vec<u8>) {
+        for entry in entries {
+            output.extend(&entry.path);
+            output.push(b'\x00');
+
+            for stage in &entry.stages {
+                let mode = stage.as_ref().map_or(0, |(mode, _)| *mode);
+                output.extend(format!("{:o}\x00", mode).as_bytes());
+            }
+
+            for (_, hash) in entry.stages.iter().flatten() {
+                output.extend(&object::hash_to_bytes(hash));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_resolve_undo_round_trip() {
+    let entries = vec![
+        ResolveUndo {
+            path: b"added.txt".to_vec(),
+            stages: [
+                None,
+                Some((
+                    0o100644,
+                    "bee4a103cd500299b3277749ce3b29fd31f439a0".to_string(),
+                )),
+                Some((
+                    0o100755,
+                    "7d11a85a54c02af57434e2bcd5ea7d7ea303e4ac".to_string(),
+                )),
+            ],
+        },
+        ResolveUndo {
+            path: b"file.txt".to_vec(),
+            stages: [
+                Some((
+                    0o100644,
+                    "7d11a85a54c02af57434e2bcd5ea7d7ea303e4ac".to_string(),
+                )),
+                None,
+                None,
+            ],
+        },
+    ];
+
+    let mut data = vec![];
+    ResolveUndo::parse_into(&entries, &mut data);
+
+    assert!(data.starts_with(b"added.txt\x000\x00100644\x00100755\x00"));
+    assert_eq!(ResolveUndo::parse(&data).unwrap(), entries);
+}
@@ -1,3 +1,8 @@
+pub mod diff;
 pub mod fs;
 pub mod index;
+pub mod object;
+pub mod refs;
+pub mod repository;
+pub mod revision;
 pub mod sub_commands;
@@ -1,8 +1,13 @@
+use papyrus::repository;
 use papyrus::sub_commands::SubCommand;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
 struct Opt {
+    /// Runs against this repository directory, such as a `.git`, instead of `.papyrus`
+    #[structopt(long = "git-dir")]
+    git_dir: Option<PathBuf>,
     #[structopt(subcommand)]
     sub_command: SubCommand,
 }
@@ -10,6 +15,11 @@ struct Opt {
 fn main() {
     let opt = Opt::from_args();
 
+    // like git, the option is handed down as the environment variable
+    if let Some(git_dir) = opt.git_dir {
+        std::env::set_var(repository::DIRECTORY_VARIABLE, git_dir);
+    }
+
     let output = opt.sub_command.execute();
 
     match output {
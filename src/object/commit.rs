@@ -0,0 +1,357 @@
+use crate::fs::FileSystem;
+use crate::object;
+use chrono::{FixedOffset, Local, Offset, TimeZone};
+use std::env;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    pub timestamp: i64,
+    /// Offset from UTC in minutes, e.g. `-180` for `-0300`.
+    pub offset: i32,
+}
+
+impl Signature {
+    /// Builds a signature from `PAPYRUS_<ROLE>_NAME`, `PAPYRUS_<ROLE>_EMAIL` and
+    /// the optional `PAPYRUS_<ROLE>_DATE` (`<timestamp> <+hhmm>`), where role is
+    /// `AUTHOR` or `COMMITTER`. The committer falls back to the author variables.
+    pub fn from_env(role: &str) -> Result<Self, String> {
+        let var = |field: &str| {
+            env::var(format!("PAPYRUS_{}_{}", role, field))
+                .or_else(|_| env::var(format!("PAPYRUS_AUTHOR_{}", field)))
+                .ok()
+        };
+
+        let (name, email) = match (var("NAME"), var("EMAIL")) {
+            (Some(name), Some(email)) => (name, email),
+            _ => {
+                return Err(format!(
+                    "*** Please tell me who you are.\n\nRun\n\n  \
+                     export PAPYRUS_{0}_NAME=\"Your Name\"\n  \
+                     export PAPYRUS_{0}_EMAIL=\"you@example.com\"\n\n\
+                     fatal: unable to auto-detect {1} identity",
+                    role,
+                    role.to_lowercase()
+                ))
+            }
+        };
+
+        let (timestamp, offset) = match var("DATE") {
+            Some(date) => {
+                parse_date(&date).ok_or_else(|| format!("fatal: invalid date format: {}", date))?
+            }
+            None => {
+                let now = Local::now();
+                (now.timestamp(), now.offset().fix().local_minus_utc() / 60)
+            }
+        };
+
+        Ok(Self {
+            name,
+            email,
+            timestamp,
+            offset,
+        })
+    }
+
+    /// The signature a reflog entry is made with, which, unlike a commit,
+    /// doesn't need an identity to be set up: git falls back to a made-up one.
+    pub fn for_reflog() -> Self {
+        Self::from_env("COMMITTER").unwrap_or_else(|_| {
+            let now = Local::now();
+
+            Self {
+                name: "unknown".to_string(),
+                email: "unknown".to_string(),
+                timestamp: now.timestamp(),
+                offset: now.offset().fix().local_minus_utc() / 60,
+            }
+        })
+    }
+
+    /// Formats the date the way `git log` does, in the signer's timezone. A
+    /// date that can't be shown, such as one with an offset of a day or more,
+    /// is given as it is stored.
+    pub fn format_date(&self) -> String {
+        let date = FixedOffset::east_opt(self.offset * 60)
+            .and_then(|offset| offset.timestamp_opt(self.timestamp, 0).single());
+
+        match date {
+            Some(date) => date.format("%a %b %-d %H:%M:%S %Y %z").to_string(),
+            None => format!("{} {}", self.timestamp, format_offset(self.offset)),
+        }
+    }
+
+    pub fn parse(signature: &str) -> Result<Self, String> {
+        let error = || format!("fatal: bad signature '{}'", signature);
+
+        let email_start = signature.find('<').ok_or_else(error)?;
+        let email_end = signature.rfind('>').ok_or_else(error)?;
+
+        if email_end < email_start {
+            return Err(error());
+        }
+
+        let (timestamp, offset) = parse_date(&signature[email_end + 1..]).ok_or_else(error)?;
+
+        Ok(Self {
+            name: signature[..email_start].trim_end().to_string(),
+            email: signature[email_start + 1..email_end].to_string(),
+            timestamp,
+            offset,
+        })
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} <{}> {} {}",
+            self.name,
+            self.email,
+            self.timestamp,
+            format_offset(self.offset)
+        )
+    }
+}
+
+/// Formats an offset in minutes as git stores it, e.g. `-0300` for `-180`.
+fn format_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+
+    format!("{}{:02}{:02}", sign, offset.abs() / 60, offset.abs() % 60)
+}
+
+fn parse_date(date: &str) -> Option<(i64, i32)> {
+    let mut parts = date.split_whitespace();
+
+    let timestamp = parts.next()?.parse().ok()?;
+    let offset = parse_offset(parts.next()?)?;
+
+    Some((timestamp, offset))
+}
+
+fn parse_offset(offset: &str) -> Option<i32> {
+    if !offset.is_ascii() || offset.len() != 5 || !offset[1..].chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let hours: i32 = offset[1..3].parse().ok()?;
+    let minutes: i32 = offset[3..].parse().ok()?;
+
+    match &offset[..1] {
+        "+" => Some(hours * 60 + minutes),
+        "-" => Some(-(hours * 60 + minutes)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub fn test_signature() -> Signature {
+    Signature {
+        name: "Jack".to_string(),
+        email: "jack@example.com".to_string(),
+        timestamp: 1591510158,
+        offset: -180,
+    }
+}
+
+/// Writes a commit whose tree holds the given `(path, contents, mode)` files.
+#[cfg(test)]
+pub fn test_commit(
+    fs: &mut FileSystem,
+    files: &[(&str, &str, u32)],
+    parents: Vec<String>,
+) -> String {
+    use crate::object::tree::TreeEntry;
+    use crate::sub_commands::{commit_tree, hash_object, write_tree};
+
+    let entries = files
+        .iter()
+        .map(|(path, contents, mode)| TreeEntry {
+            mode: *mode,
+            name: path.to_string(),
+            hash: hash_object::execute(fs, contents.as_bytes(), "blob".into(), true).unwrap(),
+        })
+        .collect();
+
+    let tree = write_tree::write_entries(fs, entries).unwrap();
+
+    commit_tree::execute(
+        fs,
+        tree,
+        parents,
+        vec!["test commit".to_string()],
+        test_signature(),
+        test_signature(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_format_date() {
+    assert_eq!(
+        test_signature().format_date(),
+        "Sun Jun 7 03:09:18 2020 -0300"
+    );
+
+    // chrono has no timezone this far from UTC
+    let signature = Signature {
+        offset: 25 * 60,
+        ..test_signature()
+    };
+    assert_eq!(signature.format_date(), "1591510158 +2500");
+}
+
+#[test]
+fn test_parse_date() {
+    assert_eq!(parse_date("1591510158 -0300"), Some((1591510158, -180)));
+    assert_eq!(parse_date("1591510158 +0530"), Some((1591510158, 330)));
+    assert_eq!(parse_date("1591510158"), None);
+    assert_eq!(parse_date("yesterday +0000"), None);
+    assert_eq!(parse_date("1591510158 é123"), None);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commit {
+    pub tree: String,
+    pub parents: Vec<String>,
+    pub author: Signature,
+    pub committer: Signature,
+    pub message: String,
+}
+
+impl Commit {
+    pub fn read(fs: &FileSystem, name: &str) -> Result<Self, String> {
+        let object = object::read(fs, name)?;
+
+        if object.object_type != "commit" {
+            return Err(format!("fatal: {} is not a valid 'commit' object", name));
+        }
+
+        Self::parse_from_object(&object.data)
+    }
+
+    pub fn parse_from_object(data: &[u8]) -> Result<Self, String> {
+        let contents =
+            std::str::from_utf8(data).map_err(|_| "fatal: corrupt commit object".to_string())?;
+
+        let (headers, message) = match contents.find("\n\n") {
+            Some(index) => (&contents[..index], &contents[index + 2..]),
+            None => (contents.trim_end_matches('\n'), ""),
+        };
+
+        let mut tree = None;
+        let mut parents = vec![];
+        let mut author = None;
+        let mut committer = None;
+
+        for line in headers.lines() {
+            // continuation lines of multi-line headers such as `gpgsig`
+            if line.starts_with(' ') {
+                continue;
+            }
+
+            let (key, value) = match line.find(' ') {
+                Some(index) => (&line[..index], &line[index + 1..]),
+                None => return Err(format!("fatal: bad commit header '{}'", line)),
+            };
+
+            match key {
+                "tree" => tree = Some(value.to_string()),
+                "parent" => parents.push(value.to_string()),
+                "author" => author = Some(Signature::parse(value)?),
+                "committer" => committer = Some(Signature::parse(value)?),
+                _ => {}
+            }
+        }
+
+        match (tree, author, committer) {
+            (Some(tree), Some(author), Some(committer)) => Ok(Self {
+                tree,
+                parents,
+                author,
+                committer,
+                message: message.to_string(),
+            }),
+            (None, _, _) => Err("fatal: commit object is missing a tree".to_string()),
+            (_, None, _) => Err("fatal: commit object is missing an author".to_string()),
+            (_, _, None) => Err("fatal: commit object is missing a committer".to_string()),
+        }
+    }
+
+    pub fn parse_into_object(&self) -> Vec<u8> {
+        let mut output = format!("tree {}\n", self.tree);
+
+        for parent in &self.parents {
+            output.push_str(&format!("parent {}\n", parent));
+        }
+
+        output.push_str(&format!("author {}\n", self.author));
+        output.push_str(&format!("committer {}\n", self.committer));
+        output.push('\n');
+        output.push_str(&self.message);
+
+        output.into_bytes()
+    }
+}
+
+#[test]
+fn test_commit_parse_into_object() {
+    let signature = test_signature();
+
+    let commit = Commit {
+        tree: "7d11a85a54c02af57434e2bcd5ea7d7ea303e4ac".to_string(),
+        parents: vec!["5c7f7d83d0da2baceb3789aaf457a699455992fe".to_string()],
+        author: signature.clone(),
+        committer: signature,
+        message: "first commit\n".to_string(),
+    };
+
+    assert_eq!(
+        String::from_utf8(commit.parse_into_object()).unwrap(),
+        "tree 7d11a85a54c02af57434e2bcd5ea7d7ea303e4ac\n\
+         parent 5c7f7d83d0da2baceb3789aaf457a699455992fe\n\
+         author Jack <jack@example.com> 1591510158 -0300\n\
+         committer Jack <jack@example.com> 1591510158 -0300\n\
+         \n\
+         first commit\n"
+    );
+}
+
+#[test]
+fn test_commit_parse_from_object() {
+    let object = b"tree 7d11a85a54c02af57434e2bcd5ea7d7ea303e4ac\n\
+                   parent 5c7f7d83d0da2baceb3789aaf457a699455992fe\n\
+                   parent 3a57a4f328d9448ce1e3ee4913712c63782dcaf2\n\
+                   author Jack Sparrow <jack@example.com> 1591510158 -0300\n\
+                   committer Anne <anne@example.com> 1591510200 +0100\n\
+                   gpgsig -----BEGIN PGP SIGNATURE-----\n \n -----END PGP SIGNATURE-----\n\
+                   \n\
+                   merge branch\n\nwith details\n";
+
+    let commit = Commit::parse_from_object(object).unwrap();
+
+    assert_eq!(commit.tree, "7d11a85a54c02af57434e2bcd5ea7d7ea303e4ac");
+    assert_eq!(
+        commit.parents,
+        vec![
+            "5c7f7d83d0da2baceb3789aaf457a699455992fe".to_string(),
+            "3a57a4f328d9448ce1e3ee4913712c63782dcaf2".to_string()
+        ]
+    );
+    assert_eq!(
+        commit.author,
+        Signature {
+            name: "Jack Sparrow".to_string(),
+            email: "jack@example.com".to_string(),
+            timestamp: 1591510158,
+            offset: -180,
+        }
+    );
+    assert_eq!(commit.committer.offset, 60);
+    assert_eq!(commit.message, "merge branch\n\nwith details\n");
+}
@@ -0,0 +1,240 @@
+use crate::fs::{FileSystem, Fs};
+use crate::repository;
+use crate::revision;
+use flate2::read::ZlibDecoder;
+use std::io::prelude::*;
+use std::path::PathBuf;
+
+pub mod commit;
+pub mod pack;
+pub mod tree;
+
+#[derive(Debug)]
+pub struct Object {
+    pub hash: String,
+    pub object_type: String,
+    pub data: Vec<u8>,
+}
+
+pub fn find(fs: &FileSystem, name: &str) -> Result<String, String> {
+    if name.len() < 4 || name.len() > 40 || !name.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("fatal: Not a valid object name {}", name));
+    }
+
+    let name = name.to_lowercase();
+
+    let object_folder_path = repository::path(fs).join("objects").join(&name[..2]);
+
+    let mut matching_objects = if fs.path_exists(&object_folder_path) {
+        fs.get_directory_files_starting_with(&object_folder_path, &name[..].into())
+            .iter()
+            .map(|object_file| {
+                let object_file = object_file.file_name().unwrap();
+                format!("{}{}", &name[..2], object_file.to_str().unwrap())
+            })
+            .collect()
+    } else {
+        vec![]
+    };
+
+    // objects that were packed are looked up in the pack indexes
+    matching_objects.append(&mut pack::find(fs, &name)?);
+    matching_objects.sort();
+    matching_objects.dedup();
+
+    match matching_objects.len() {
+        0 => Err(format!("fatal: Not a valid object name {}", name)),
+        1 => Ok(matching_objects.remove(0)),
+        _ => Err(format!(
+            "fatal: ambigious argument '{}', there are more than one object with same name",
+            name
+        )),
+    }
+}
+
+pub fn hash_to_bytes(hash: &str) -> [u8; 20] {
+    let mut bytes = [0; 20];
+
+    for (i, byte_pair) in hash.as_bytes().chunks(2).take(20).enumerate() {
+        let b = std::str::from_utf8(byte_pair).unwrap();
+        bytes[i] = u8::from_str_radix(b, 16).unwrap();
+    }
+
+    bytes
+}
+
+pub fn bytes_to_hash(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// An object name in its binary form, as the index stores it, displayed as
+/// the usual hexadecimal hash.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId(pub [u8; 20]);
+
+impl ObjectId {
+    pub fn from_hex(hash: &str) -> Result<Self, String> {
+        if hash.len() != 40 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("fatal: Not a valid object name {}", hash));
+        }
+
+        Ok(ObjectId(hash_to_bytes(hash)))
+    }
+}
+
+impl std::fmt::Display for ObjectId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", bytes_to_hash(&self.0))
+    }
+}
+
+/// Resolves a tree-ish, that is a tree or a commit pointing at one, into the
+/// hash of the tree.
+pub fn peel_to_tree(fs: &FileSystem, name: &str) -> Result<String, String> {
+    revision::peel(fs, &revision::resolve(fs, name)?, "tree")
+        .map_err(|_| format!("fatal: not a tree object: {}", name))
+}
+
+pub fn read(fs: &FileSystem, name: &str) -> Result<Object, String> {
+    let hash = find(fs, name)?;
+
+    let object_path = repository::path(fs)
+        .join("objects")
+        .join(&hash[..2])
+        .join(&hash[2..]);
+
+    if !fs.path_exists(&object_path) {
+        return pack::read(fs, &hash)?
+            .ok_or_else(|| format!("fatal: Not a valid object name {}", hash));
+    }
+
+    decode(&hash, &fs.get_file_contents_as_bytes(&object_path)?)
+}
+
+/// Decompresses a loose object, checking that its header is well formed and
+/// that the size it records is the size of the data.
+pub fn decode(hash: &str, compressed_object_contents: &[u8]) -> Result<Object, String> {
+    let corrupt = || format!("fatal: corrupt object {}", hash);
+
+    let mut decoder = ZlibDecoder::new(compressed_object_contents);
+    let mut object_contents = vec![];
+    decoder
+        .read_to_end(&mut object_contents)
+        .map_err(|err| format!("fatal: unable to unpack {}: {}", hash, err))?;
+
+    let null_index = object_contents
+        .iter()
+        .position(|b| *b == b'\x00')
+        .ok_or_else(corrupt)?;
+    let header = std::str::from_utf8(&object_contents[..null_index]).map_err(|_| corrupt())?;
+
+    let (object_type, size) = match header.split(' ').collect::<Vec<&str>>()[..] {
+        [object_type, size] => (object_type, size.parse::<usize>().map_err(|_| corrupt())?),
+        _ => return Err(corrupt()),
+    };
+
+    let data = object_contents[null_index + 1..].to_vec();
+
+    if data.len() != size {
+        return Err(corrupt());
+    }
+
+    Ok(Object {
+        hash: hash.to_string(),
+        object_type: object_type.to_string(),
+        data,
+    })
+}
+
+/// Lists the loose objects of the repository along with their paths.
+pub fn list_loose(fs: &FileSystem) -> Vec<(String, PathBuf)> {
+    let objects_path = repository::path(fs).join("objects");
+
+    fs.get_directory_files_recursively(&objects_path)
+        .into_iter()
+        .filter_map(|path| {
+            let hash = path
+                .strip_prefix(&objects_path)
+                .ok()?
+                .to_str()?
+                .replacen('/', "", 1);
+
+            if hash.len() == 40 && hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                Some((hash, path))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_read_existing_object() {
+    use crate::sub_commands::hash_object;
+    let mut fs = FileSystem::access();
+
+    hash_object::execute(&mut fs, b"awesome contents yo", "blob".into(), true).unwrap();
+
+    let object = read(&fs, "5c7f7d").unwrap();
+
+    assert_eq!(object.hash, "5c7f7d83d0da2baceb3789aaf457a699455992fe");
+    assert_eq!(object.object_type, "blob");
+    assert_eq!(object.data, b"awesome contents yo");
+}
+
+#[test]
+fn test_find_non_existing_object() {
+    let fs = FileSystem::access();
+
+    assert_eq!(
+        find(&fs, "5c7f7d83d0da2baceb3789aaf457a699455992fe").unwrap_err(),
+        "fatal: Not a valid object name 5c7f7d83d0da2baceb3789aaf457a699455992fe"
+    );
+    assert_eq!(
+        find(&fs, "master").unwrap_err(),
+        "fatal: Not a valid object name master"
+    );
+}
+
+#[test]
+fn test_read_truncated_object() {
+    use crate::sub_commands::hash_object;
+    let mut fs = FileSystem::access();
+
+    let hash = hash_object::execute(&mut fs, b"awesome contents yo", "blob".into(), true).unwrap();
+    let path = PathBuf::from(format!(
+        "{}/.papyrus/objects/{}/{}",
+        fs.current_directory(),
+        &hash[..2],
+        &hash[2..]
+    ));
+
+    let contents = fs.get_file_contents_as_bytes(&path).unwrap();
+    fs.write_file(&path, &contents[..contents.len() / 2]);
+
+    assert!(read(&fs, &hash).is_err());
+
+    // a header claiming more data than there is
+    fs.write_file(
+        &path,
+        &hash_object::zlib_compress(b"blob 30\x00awesome contents yo"),
+    );
+
+    assert_eq!(
+        read(&fs, &hash).unwrap_err(),
+        format!("fatal: corrupt object {}", hash)
+    );
+}
+
+#[test]
+fn test_object_id() {
+    let hash = "7d11a85a54c02af57434e2bcd5ea7d7ea303e4ac";
+    let id = ObjectId::from_hex(hash).unwrap();
+
+    assert_eq!(id.0[..2], [0x7d, 0x11]);
+    assert_eq!(id.to_string(), hash);
+    assert_eq!(
+        ObjectId::from_hex("7d11a85").unwrap_err(),
+        "fatal: Not a valid object name 7d11a85"
+    );
+}
@@ -0,0 +1,1131 @@
+use crate::fs::{FileSystem, Fs};
+use crate::object::{self, Object, ObjectId};
+use crate::repository;
+use crypto::digest::Digest;
+use crypto::sha1::Sha1;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::thread::LocalKey;
+
+pub const OBJ_COMMIT: u8 = 1;
+pub const OBJ_TREE: u8 = 2;
+pub const OBJ_BLOB: u8 = 3;
+pub const OBJ_TAG: u8 = 4;
+pub const OBJ_OFS_DELTA: u8 = 6;
+pub const OBJ_REF_DELTA: u8 = 7;
+
+const INDEX_SIGNATURE: [u8; 4] = [0xff, b't', b'O', b'c'];
+
+/// How many of the objects before it, of the same type, are tried as the
+/// base of a delta when writing a pack.
+const DELTA_WINDOW: usize = 10;
+
+/// Deltas are not chained any deeper than this, as reading an object means
+/// applying its whole chain.
+const MAX_DELTA_DEPTH: usize = 50;
+
+/// The longest chain of deltas git can write with `--depth`, past which
+/// reading gives up, as only a corrupt pack whose deltas loop gets there.
+const MAX_DELTA_CHAIN: usize = 4095;
+
+/// When computing deltas, the base is looked up by blocks of this size.
+const DELTA_BLOCK_SIZE: usize = 16;
+
+/// Offsets that don't fit in 31 bits are stored in a separate table of the
+/// index, pointed at by entries with this bit set.
+const LARGE_OFFSET_FLAG: u32 = 0x8000_0000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackIndexEntry {
+    pub hash: ObjectId,
+    pub crc32: u32,
+    pub offset: u64,
+}
+
+/// The `.idx` file of a pack, in version 2, listing where every object of
+/// the pack starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackIndex {
+    /// Sorted by hash.
+    pub entries: Vec<PackIndexEntry>,
+    /// The number of entries whose hash starts with at most each byte.
+    pub fanout: [u32; 256],
+    pub pack_checksum: [u8; 20],
+}
+
+impl PackIndex {
+    pub fn new(mut entries: Vec<PackIndexEntry>, pack_checksum: [u8; 20]) -> Self {
+        entries.sort_by_key(|entry| entry.hash);
+
+        let mut fanout = [0; 256];
+
+        for entry in &entries {
+            fanout[entry.hash.0[0] as usize] += 1;
+        }
+
+        for byte in 1..256 {
+            fanout[byte] += fanout[byte - 1];
+        }
+
+        Self {
+            entries,
+            fanout,
+            pack_checksum,
+        }
+    }
+
+    pub fn parse_from_file(data: &[u8]) -> Result<Self, String> {
+        let corrupt = || "fatal: corrupt pack index".to_string();
+        let read_u32 = |at: usize| -> Result<u32, String> {
+            data.get(at..at + 4)
+                .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+                .ok_or_else(corrupt)
+        };
+
+        if data.len() < 8 + 256 * 4 + 40 || data[..4] != INDEX_SIGNATURE {
+            return Err("error: unsupported pack index, only version 2 is".to_string());
+        }
+
+        if read_u32(4)? != 2 {
+            return Err(format!(
+                "error: unsupported pack index version {}",
+                read_u32(4)?
+            ));
+        }
+
+        let mut fanout = [0; 256];
+
+        for (byte, count) in fanout.iter_mut().enumerate() {
+            *count = read_u32(8 + byte * 4)?;
+        }
+
+        let count = fanout[255] as usize;
+
+        let hashes_start = 8 + 256 * 4;
+        let crcs_start = hashes_start + count * 20;
+        let offsets_start = crcs_start + count * 4;
+        let large_offsets_start = offsets_start + count * 4;
+
+        if data.len() < large_offsets_start + 40 {
+            return Err(corrupt());
+        }
+
+        let mut entries = vec![];
+
+        for i in 0..count {
+            let hash = ObjectId(
+                data[hashes_start + i * 20..hashes_start + (i + 1) * 20]
+                    .try_into()
+                    .unwrap(),
+            );
+            let crc32 = read_u32(crcs_start + i * 4)?;
+            let offset = read_u32(offsets_start + i * 4)?;
+
+            let offset = if offset & LARGE_OFFSET_FLAG != 0 {
+                let at = large_offsets_start + (offset & !LARGE_OFFSET_FLAG) as usize * 8;
+
+                data.get(at..at + 8)
+                    .map(|bytes| u64::from_be_bytes(bytes.try_into().unwrap()))
+                    .ok_or_else(corrupt)?
+            } else {
+                offset as u64
+            };
+
+            entries.push(PackIndexEntry {
+                hash,
+                crc32,
+                offset,
+            });
+        }
+
+        let mut pack_checksum = [0; 20];
+        pack_checksum.copy_from_slice(&data[data.len() - 40..data.len() - 20]);
+
+        Ok(Self {
+            entries,
+            fanout,
+            pack_checksum,
+        })
+    }
+
+    pub fn parse_into_file(&self) -> Vec<u8> {
+        let entries = &self.entries;
+
+        let mut output = INDEX_SIGNATURE.to_vec();
+        output.extend_from_slice(&2u32.to_be_bytes());
+
+        for count in self.fanout {
+            output.extend_from_slice(&count.to_be_bytes());
+        }
+
+        for entry in entries {
+            output.extend_from_slice(&entry.hash.0);
+        }
+
+        for entry in entries {
+            output.extend_from_slice(&entry.crc32.to_be_bytes());
+        }
+
+        let mut large_offsets = vec![];
+
+        for entry in entries {
+            let offset = if entry.offset >= LARGE_OFFSET_FLAG as u64 {
+                large_offsets.push(entry.offset);
+                LARGE_OFFSET_FLAG | (large_offsets.len() as u32 - 1)
+            } else {
+                entry.offset as u32
+            };
+
+            output.extend_from_slice(&offset.to_be_bytes());
+        }
+
+        for offset in large_offsets {
+            output.extend_from_slice(&offset.to_be_bytes());
+        }
+
+        output.extend_from_slice(&self.pack_checksum);
+
+        let mut hasher = Sha1::new();
+        hasher.input(&output);
+        let mut checksum = [0; 20];
+        hasher.result(&mut checksum);
+
+        output.extend_from_slice(&checksum);
+
+        output
+    }
+
+    /// The entries whose hash starts with a byte from `first` to `last`, as
+    /// the fanout table tells without searching.
+    fn bucket(&self, first: u8, last: u8) -> &[PackIndexEntry] {
+        let start = match first {
+            0 => 0,
+            _ => self.fanout[first as usize - 1] as usize,
+        };
+        let end = self.fanout[last as usize] as usize;
+
+        self.entries.get(start..end).unwrap_or(&[])
+    }
+
+    pub fn find(&self, hash: &str) -> Option<u64> {
+        let hash = ObjectId::from_hex(hash).ok()?;
+        let bucket = self.bucket(hash.0[0], hash.0[0]);
+
+        bucket
+            .binary_search_by(|entry| entry.hash.cmp(&hash))
+            .ok()
+            .map(|i| bucket[i].offset)
+    }
+
+    /// Lists the hashes starting with `prefix`, which lie between the
+    /// prefix padded with `0`s and with `f`s.
+    pub fn find_prefix(&self, prefix: &str) -> Vec<ObjectId> {
+        let (lowest, highest) = match (
+            ObjectId::from_hex(&format!("{:0<40}", prefix)),
+            ObjectId::from_hex(&format!("{:f<40}", prefix)),
+        ) {
+            (Ok(lowest), Ok(highest)) => (lowest, highest),
+            _ => return vec![],
+        };
+
+        let bucket = self.bucket(lowest.0[0], highest.0[0]);
+        let start = bucket.partition_point(|entry| entry.hash < lowest);
+
+        bucket[start..]
+            .iter()
+            .take_while(|entry| entry.hash <= highest)
+            .map(|entry| entry.hash)
+            .collect()
+    }
+}
+
+pub fn type_number(name: &str) -> Result<u8, String> {
+    match name {
+        "commit" => Ok(OBJ_COMMIT),
+        "tree" => Ok(OBJ_TREE),
+        "blob" => Ok(OBJ_BLOB),
+        "tag" => Ok(OBJ_TAG),
+        _ => Err(format!("fatal: unknown object type {}", name)),
+    }
+}
+
+pub fn type_name(object_type: u8) -> Result<&'static str, String> {
+    match object_type {
+        OBJ_COMMIT => Ok("commit"),
+        OBJ_TREE => Ok("tree"),
+        OBJ_BLOB => Ok("blob"),
+        OBJ_TAG => Ok("tag"),
+        _ => Err(format!("fatal: unknown object type {}", object_type)),
+    }
+}
+
+/// Reads the type and inflated size of the object starting at `offset`, and
+/// where its data starts.
+fn read_header(pack: &[u8], offset: usize) -> Result<(u8, usize, usize), String> {
+    let corrupt = || format!("fatal: corrupt pack entry at offset {}", offset);
+
+    let mut i = offset;
+    let mut byte = *pack.get(i).ok_or_else(corrupt)?;
+
+    let object_type = (byte >> 4) & 0b111;
+    let mut size = (byte & 0b1111) as usize;
+    let mut shift = 4;
+
+    while byte & 0x80 != 0 {
+        i += 1;
+        byte = *pack.get(i).ok_or_else(corrupt)?;
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+    }
+
+    Ok((object_type, size, i + 1))
+}
+
+fn inflate(pack: &[u8], start: usize, size: usize) -> Result<Vec<u8>, String> {
+    let mut data = vec![];
+
+    let compressed = pack.get(start..).ok_or("fatal: truncated pack")?;
+
+    ZlibDecoder::new(compressed)
+        .read_to_end(&mut data)
+        .map_err(|err| format!("fatal: unable to unpack pack entry: {}", err))?;
+
+    if data.len() != size {
+        return Err(format!("fatal: corrupt pack entry at offset {}", start));
+    }
+
+    Ok(data)
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Delta sizes are little endian numbers made of 7 bit groups.
+fn read_delta_size(delta: &[u8], i: &mut usize) -> Result<usize, String> {
+    let mut size = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = *delta.get(*i).ok_or("fatal: truncated delta")?;
+        *i += 1;
+
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(size);
+        }
+    }
+}
+
+/// Rebuilds an object from its base and a delta, which is a list of
+/// instructions to either copy a range of the base or insert new bytes.
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, String> {
+    let mut i = 0;
+
+    if read_delta_size(delta, &mut i)? != base.len() {
+        return Err("fatal: delta base size mismatch".to_string());
+    }
+
+    let target_size = read_delta_size(delta, &mut i)?;
+    let mut target = Vec::with_capacity(target_size);
+
+    while i < delta.len() {
+        let instruction = delta[i];
+        i += 1;
+
+        if instruction & 0x80 != 0 {
+            // which of the four offset and three size bytes are present
+            let mut copy_offset = 0;
+            let mut copy_size = 0;
+
+            for bit in 0..7 {
+                if instruction & (1 << bit) == 0 {
+                    continue;
+                }
+
+                let byte = *delta.get(i).ok_or("fatal: truncated delta")? as usize;
+                i += 1;
+
+                if bit < 4 {
+                    copy_offset |= byte << (bit * 8);
+                } else {
+                    copy_size |= byte << ((bit - 4) * 8);
+                }
+            }
+
+            if copy_size == 0 {
+                copy_size = 0x10000;
+            }
+
+            let copied = base
+                .get(copy_offset..copy_offset + copy_size)
+                .ok_or("fatal: delta copies outside of its base")?;
+            target.extend_from_slice(copied);
+        } else if instruction != 0 {
+            let inserted = delta
+                .get(i..i + instruction as usize)
+                .ok_or("fatal: truncated delta")?;
+            target.extend_from_slice(inserted);
+            i += instruction as usize;
+        } else {
+            return Err("fatal: unexpected delta opcode 0".to_string());
+        }
+    }
+
+    if target.len() != target_size {
+        return Err("fatal: delta target size mismatch".to_string());
+    }
+
+    Ok(target)
+}
+
+fn write_delta_size(delta: &mut Vec<u8>, mut size: usize) {
+    while size >= 0x80 {
+        delta.push(0x80 | (size & 0x7f) as u8);
+        size >>= 7;
+    }
+
+    delta.push(size as u8);
+}
+
+fn write_insert(delta: &mut Vec<u8>, bytes: &[u8]) {
+    for chunk in bytes.chunks(0x7f) {
+        delta.push(chunk.len() as u8);
+        delta.extend_from_slice(chunk);
+    }
+}
+
+fn write_copy(delta: &mut Vec<u8>, offset: usize, size: usize) {
+    let mut instruction = 0x80;
+    let mut arguments = vec![];
+
+    // only the bytes that aren't zero are written, a size of 0x10000 being
+    // written as no size at all
+    for (bit, byte) in (0..4)
+        .map(|i| (offset >> (i * 8)) as u8)
+        .chain((0..3).map(|i| (size >> (i * 8)) as u8))
+        .enumerate()
+    {
+        if byte != 0 {
+            instruction |= 1 << bit;
+            arguments.push(byte);
+        }
+    }
+
+    delta.push(instruction);
+    delta.extend(arguments);
+}
+
+/// Computes the delta that `apply_delta` turns `base` into `target` with,
+/// copying whatever blocks of the base show up in the target.
+pub fn create_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = vec![];
+    write_delta_size(&mut delta, base.len());
+    write_delta_size(&mut delta, target.len());
+
+    let mut blocks: HashMap<&[u8], usize> = HashMap::new();
+
+    for (i, block) in base.chunks_exact(DELTA_BLOCK_SIZE).enumerate() {
+        blocks.entry(block).or_insert(i * DELTA_BLOCK_SIZE);
+    }
+
+    let mut inserted: Vec<u8> = vec![];
+    let mut i = 0;
+
+    while i < target.len() {
+        let start = target
+            .get(i..i + DELTA_BLOCK_SIZE)
+            .and_then(|block| blocks.get(block));
+
+        let mut start = match start {
+            Some(&start) => start,
+            None => {
+                inserted.push(target[i]);
+                i += 1;
+                continue;
+            }
+        };
+
+        let mut size = DELTA_BLOCK_SIZE;
+
+        while start + size < base.len()
+            && i + size < target.len()
+            && base[start + size] == target[i + size]
+        {
+            size += 1;
+        }
+
+        i += size;
+
+        // the match may also start within what was about to be inserted
+        while start > 0 && inserted.last() == Some(&base[start - 1]) {
+            inserted.pop();
+            start -= 1;
+            size += 1;
+        }
+
+        write_insert(&mut delta, &inserted);
+        inserted.clear();
+
+        while size > 0 {
+            let copied = size.min(0xff_ffff);
+            write_copy(&mut delta, start, copied);
+            start += copied;
+            size -= copied;
+        }
+    }
+
+    write_insert(&mut delta, &inserted);
+
+    delta
+}
+
+/// Reads the object starting at `offset` of `pack` as its type and data,
+/// resolving deltas against their base, which for REF_DELTA can be outside
+/// of the pack. The chain of deltas is followed down to its base before
+/// they are applied back up, failing if it is longer than git makes them.
+pub fn read_at(
+    fs: &FileSystem,
+    pack: &[u8],
+    index: &PackIndex,
+    offset: u64,
+) -> Result<(String, Vec<u8>), String> {
+    let mut offset = offset as usize;
+    let mut deltas = vec![];
+
+    let (object_type, mut data) = loop {
+        let (object_type, size, mut start) = read_header(pack, offset)?;
+
+        let base_offset = match object_type {
+            OBJ_OFS_DELTA => {
+                // the distance to the base is big endian, with every
+                // continuation adding one so that no encoding is wasted
+                let mut byte = *pack.get(start).ok_or("fatal: truncated pack")?;
+                let mut distance = (byte & 0x7f) as usize;
+
+                while byte & 0x80 != 0 {
+                    start += 1;
+                    byte = *pack.get(start).ok_or("fatal: truncated pack")?;
+                    distance = ((distance + 1) << 7) | (byte & 0x7f) as usize;
+                }
+
+                start += 1;
+
+                offset
+                    .checked_sub(distance)
+                    .filter(|_| distance > 0)
+                    .ok_or_else(|| format!("fatal: bad delta base offset at {}", offset))?
+            }
+            OBJ_REF_DELTA => {
+                let base_hash = object::bytes_to_hash(
+                    pack.get(start..start + 20).ok_or("fatal: truncated pack")?,
+                );
+                start += 20;
+
+                match index.find(&base_hash) {
+                    Some(base_offset) => base_offset as usize,
+                    None => {
+                        deltas.push((start, size));
+                        let base = object::read(fs, &base_hash)?;
+                        break (base.object_type, base.data);
+                    }
+                }
+            }
+            _ => {
+                break (
+                    type_name(object_type)?.to_string(),
+                    inflate(pack, start, size)?,
+                )
+            }
+        };
+
+        deltas.push((start, size));
+
+        if deltas.len() > MAX_DELTA_CHAIN {
+            return Err(format!("fatal: delta chain too long at {}", offset));
+        }
+
+        offset = base_offset;
+    };
+
+    for (start, size) in deltas.into_iter().rev() {
+        data = apply_delta(&data, &inflate(pack, start, size)?)?;
+    }
+
+    Ok((object_type, data))
+}
+
+fn write_header(output: &mut Vec<u8>, object_type: u8, mut size: usize) {
+    let mut byte = (object_type << 4) | (size & 0b1111) as u8;
+    size >>= 4;
+
+    while size > 0 {
+        output.push(byte | 0x80);
+        byte = (size & 0x7f) as u8;
+        size >>= 7;
+    }
+
+    output.push(byte);
+}
+
+/// An object, along with the position of its base and the delta against it
+/// when it is stored as a delta.
+type Deltified = (Object, Option<(usize, Vec<u8>)>);
+
+/// Picks a base for every object that can be stored as a small enough delta
+/// against one of the objects before it, once they are sorted by type and
+/// decreasing size, as the object and its delta against the base.
+fn find_deltas(objects: Vec<Object>) -> Result<Vec<Deltified>, String> {
+    let mut objects = objects
+        .into_iter()
+        .map(|object| Ok((type_number(&object.object_type)?, object)))
+        .collect::<Result<Vec<(u8, Object)>, String>>()?;
+
+    objects.sort_by(|(a_type, a), (b_type, b)| {
+        a_type
+            .cmp(b_type)
+            .then(b.data.len().cmp(&a.data.len()))
+            .then(a.hash.cmp(&b.hash))
+    });
+
+    let mut depths = vec![0; objects.len()];
+    let mut deltas: Vec<Option<(usize, Vec<u8>)>> = vec![];
+
+    for (i, (object_type, object)) in objects.iter().enumerate() {
+        let mut best: Option<(usize, Vec<u8>)> = None;
+
+        for base in i.saturating_sub(DELTA_WINDOW)..i {
+            if objects[base].0 != *object_type || depths[base] >= MAX_DELTA_DEPTH {
+                continue;
+            }
+
+            let delta = create_delta(&objects[base].1.data, &object.data);
+
+            // a delta has to make up for its base offset and compression
+            // working better on whole objects
+            let small_enough = delta.len() + 20 < object.data.len() / 2;
+            let smallest = best
+                .as_ref()
+                .is_none_or(|(_, best)| delta.len() < best.len());
+
+            if small_enough && smallest {
+                best = Some((base, delta));
+            }
+        }
+
+        if let Some((base, _)) = best {
+            depths[i] = depths[base] + 1;
+        }
+
+        deltas.push(best);
+    }
+
+    Ok(objects
+        .into_iter()
+        .map(|(_, object)| object)
+        .zip(deltas)
+        .collect())
+}
+
+/// Encodes `objects` into a pack, storing them as OFS_DELTA against each
+/// other where that saves space, and builds its index.
+pub fn encode(objects: Vec<Object>) -> Result<(Vec<u8>, PackIndex), String> {
+    let objects = find_deltas(objects)?;
+
+    let mut pack = b"PACK".to_vec();
+    pack.extend_from_slice(&2u32.to_be_bytes());
+    pack.extend_from_slice(&(objects.len() as u32).to_be_bytes());
+
+    let mut entries: Vec<PackIndexEntry> = vec![];
+
+    for (object, delta) in objects {
+        let offset = pack.len();
+        let mut entry = vec![];
+
+        match delta {
+            Some((base, delta)) => {
+                write_header(&mut entry, OBJ_OFS_DELTA, delta.len());
+
+                let mut distance = offset - entries[base].offset as usize;
+                let mut encoded = vec![(distance & 0x7f) as u8];
+                distance >>= 7;
+
+                while distance > 0 {
+                    distance -= 1;
+                    encoded.push(0x80 | (distance & 0x7f) as u8);
+                    distance >>= 7;
+                }
+
+                encoded.reverse();
+                entry.extend(encoded);
+                entry.extend(deflate(&delta));
+            }
+            None => {
+                write_header(
+                    &mut entry,
+                    type_number(&object.object_type)?,
+                    object.data.len(),
+                );
+                entry.extend(deflate(&object.data));
+            }
+        }
+
+        let mut crc32 = Crc::new();
+        crc32.update(&entry);
+
+        entries.push(PackIndexEntry {
+            hash: ObjectId::from_hex(&object.hash)?,
+            crc32: crc32.sum(),
+            offset: offset as u64,
+        });
+
+        pack.extend(entry);
+    }
+
+    let mut hasher = Sha1::new();
+    hasher.input(&pack);
+    let mut pack_checksum = [0; 20];
+    hasher.result(&mut pack_checksum);
+
+    pack.extend_from_slice(&pack_checksum);
+
+    Ok((pack, PackIndex::new(entries, pack_checksum)))
+}
+
+fn pack_directory(fs: &FileSystem) -> PathBuf {
+    repository::path(fs).join("objects").join("pack")
+}
+
+/// Lists the indexes of every pack of the repository.
+pub fn list_indexes(fs: &FileSystem) -> Vec<PathBuf> {
+    fs.get_directory_files_recursively(&pack_directory(fs))
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|extension| extension == "idx"))
+        .collect()
+}
+
+type Cache<T> = RefCell<HashMap<PathBuf, Rc<T>>>;
+
+thread_local! {
+    // a command looks objects up in the same packs over and over, and as a
+    // pack is named after its contents, what was read of it never goes stale
+    static INDEXES: Cache<PackIndex> = RefCell::new(HashMap::new());
+    static PACKS: Cache<Vec<u8>> = RefCell::new(HashMap::new());
+}
+
+/// Gets what is cached for `path`, reading it the first time.
+fn cached<T>(
+    cache: &'static LocalKey<Cache<T>>,
+    path: &Path,
+    read: impl FnOnce() -> Result<T, String>,
+) -> Result<Rc<T>, String> {
+    if let Some(value) = cache.with(|cache| cache.borrow().get(path).cloned()) {
+        return Ok(value);
+    }
+
+    let value = Rc::new(read()?);
+    cache.with(|cache| cache.borrow_mut().insert(path.to_path_buf(), value.clone()));
+
+    Ok(value)
+}
+
+fn read_index(fs: &FileSystem, path: &Path) -> Result<Rc<PackIndex>, String> {
+    cached(&INDEXES, path, || {
+        PackIndex::parse_from_file(&fs.get_file_contents_as_bytes(&path.to_path_buf())?)
+    })
+}
+
+/// Lists the hashes of the packed objects starting with `prefix`.
+pub fn find(fs: &FileSystem, prefix: &str) -> Result<Vec<String>, String> {
+    let mut hashes = vec![];
+
+    for path in list_indexes(fs) {
+        hashes.extend(
+            read_index(fs, &path)?
+                .find_prefix(prefix)
+                .iter()
+                .map(|hash| hash.to_string()),
+        );
+    }
+
+    Ok(hashes)
+}
+
+fn read_pack_file(fs: &FileSystem, index_path: &Path) -> Result<Rc<Vec<u8>>, String> {
+    let path = index_path.with_extension("pack");
+
+    cached(&PACKS, &path, || {
+        let pack = fs.get_file_contents_as_bytes(&path)?;
+
+        if pack.get(..4) != Some(b"PACK") {
+            return Err(format!("fatal: {:?} is not a pack", path));
+        }
+
+        Ok(pack)
+    })
+}
+
+/// Reads a packed object, `None` meaning that no pack has it.
+pub fn read(fs: &FileSystem, hash: &str) -> Result<Option<Object>, String> {
+    for path in list_indexes(fs) {
+        let index = read_index(fs, &path)?;
+
+        if let Some(offset) = index.find(hash) {
+            let pack = read_pack_file(fs, &path)?;
+            let (object_type, data) = read_at(fs, &pack, &index, offset)?;
+
+            return Ok(Some(Object {
+                hash: hash.to_string(),
+                object_type,
+                data,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Reads every object of the pack whose index is at `index_path`.
+pub fn read_all(fs: &FileSystem, index_path: &Path) -> Result<Vec<Object>, String> {
+    let index = read_index(fs, index_path)?;
+    let pack = read_pack_file(fs, index_path)?;
+
+    index
+        .entries
+        .iter()
+        .map(|entry| {
+            let (object_type, data) = read_at(fs, &pack, &index, entry.offset)?;
+
+            Ok(Object {
+                hash: entry.hash.to_string(),
+                object_type,
+                data,
+            })
+        })
+        .collect()
+}
+
+/// Checks that the pack whose index is at `index_path` ends with the hash
+/// of its contents, and that it is the pack the index was built for.
+pub fn verify(fs: &FileSystem, index_path: &Path) -> Result<(), String> {
+    let index = read_index(fs, index_path)?;
+    let pack = read_pack_file(fs, index_path)?;
+
+    if pack.len() < 32 {
+        return Err("fatal: truncated pack".to_string());
+    }
+
+    let (contents, trailer) = pack.split_at(pack.len() - 20);
+
+    let mut hasher = Sha1::new();
+    hasher.input(contents);
+    let mut checksum = [0; 20];
+    hasher.result(&mut checksum);
+
+    if checksum != trailer {
+        return Err(format!(
+            "fatal: {:?} SHA1 checksum mismatch",
+            index_path.with_extension("pack")
+        ));
+    }
+
+    if checksum != index.pack_checksum {
+        return Err(format!("fatal: {:?} does not match its pack", index_path));
+    }
+
+    Ok(())
+}
+
+/// Writes `objects` into a new pack of the repository along with its index,
+/// returning the path of the index.
+pub fn write(fs: &mut FileSystem, objects: Vec<Object>) -> Result<PathBuf, String> {
+    let (pack, index) = encode(objects)?;
+
+    let directory = pack_directory(fs);
+    let name = format!("pack-{}", object::bytes_to_hash(&index.pack_checksum));
+
+    if !fs.path_exists(&directory) {
+        fs.create_directory(&directory);
+    }
+
+    let pack_path = directory.join(format!("{}.pack", name));
+    fs.create_file(&pack_path);
+    fs.write_file(&pack_path, &pack);
+
+    let index_path = directory.join(format!("{}.idx", name));
+    fs.create_file(&index_path);
+    fs.write_file(&index_path, &index.parse_into_file());
+
+    Ok(index_path)
+}
+
+/// Writes a pack and an index pointing at its two blobs, the second being
+/// a delta against the first, encoded as an OFS_DELTA or a REF_DELTA.
+#[cfg(test)]
+fn write_test_pack(fs: &mut FileSystem, pack: &[u8], delta_offset: u64) {
+    let name = "pack-2831bf46f6c27263b614b8b2c6ce5ea622b53e09";
+    let directory = pack_directory(fs);
+
+    let index = PackIndex::new(
+        vec![
+            PackIndexEntry {
+                hash: ObjectId::from_hex("b5f5e7a62f788e3780ff494c81d14d41fc7b8fb2").unwrap(),
+                crc32: 0,
+                offset: 12,
+            },
+            PackIndexEntry {
+                hash: ObjectId::from_hex("74fde6a3be636a9551f9a89570e9027f88dfacbb").unwrap(),
+                crc32: 0,
+                offset: delta_offset,
+            },
+        ],
+        [0; 20],
+    );
+
+    fs.create_directory(&directory);
+
+    let pack_path = directory.join(format!("{}.pack", name));
+    fs.create_file(&pack_path);
+    fs.write_file(&pack_path, pack);
+
+    let index_path = directory.join(format!("{}.idx", name));
+    fs.create_file(&index_path);
+    fs.write_file(&index_path, &index.parse_into_file());
+}
+
+#[cfg(test)]
+fn test_pack_contents() -> (String, String) {
+    let base = (0..12)
+        .map(|i| format!("line {} of the file\n", i))
+        .collect::<String>();
+    let changed = format!("{}the end\n", base.replace("line 5 of", "LINE FIVE of"));
+
+    (base, changed)
+}
+
+#[test]
+fn test_pack_index_round_trip() {
+    let index = PackIndex::new(
+        vec![
+            PackIndexEntry {
+                hash: ObjectId::from_hex("74fde6a3be636a9551f9a89570e9027f88dfacbb").unwrap(),
+                crc32: 0xdeadbeef,
+                offset: 90,
+            },
+            PackIndexEntry {
+                hash: ObjectId::from_hex("b5f5e7a62f788e3780ff494c81d14d41fc7b8fb2").unwrap(),
+                crc32: 1,
+                offset: 0x1_0000_0000,
+            },
+        ],
+        [7; 20],
+    );
+
+    let file = index.parse_into_file();
+
+    assert_eq!(&file[..8], b"\xfftOc\x00\x00\x00\x02");
+    assert_eq!(PackIndex::parse_from_file(&file).unwrap(), index);
+    assert_eq!(
+        index.find("b5f5e7a62f788e3780ff494c81d14d41fc7b8fb2"),
+        Some(0x1_0000_0000)
+    );
+    assert_eq!(index.find("0000000000000000000000000000000000000000"), None);
+
+    let hashes = |prefix| {
+        index
+            .find_prefix(prefix)
+            .iter()
+            .map(|hash| hash.to_string())
+            .collect::<Vec<String>>()
+    };
+    assert_eq!(
+        hashes("b5f5e7"),
+        ["b5f5e7a62f788e3780ff494c81d14d41fc7b8fb2"]
+    );
+    assert_eq!(hashes("7"), ["74fde6a3be636a9551f9a89570e9027f88dfacbb"]);
+    assert_eq!(hashes("").len(), 2);
+    assert!(hashes("b5f5e8").is_empty());
+    assert!(hashes("xyz").is_empty());
+}
+
+#[test]
+fn test_apply_delta() {
+    // copy 5 bytes from offset 6 of the base, then insert "ly!"
+    let delta = b"\x0b\x08\x91\x06\x05\x03ly!";
+
+    assert_eq!(apply_delta(b"hello world", delta).unwrap(), b"worldly!");
+    assert_eq!(
+        apply_delta(b"hello world!", delta).unwrap_err(),
+        "fatal: delta base size mismatch"
+    );
+    assert_eq!(
+        apply_delta(b"hello world", b"\x0b\x09\x91\x06\x05\x03ly!").unwrap_err(),
+        "fatal: delta target size mismatch"
+    );
+}
+
+#[test]
+fn test_read_ofs_delta() {
+    use crate::sub_commands::cat_file;
+    let mut fs = FileSystem::access();
+
+    // written by `git pack-objects --delta-base-offset`
+    let pack = [
+        80, 65, 67, 75, 0, 0, 0, 2, 0, 0, 0, 2, 177, 15, 120, 156, 203, 201, 204, 75, 85, 48, 80,
+        200, 79, 83, 40, 201, 72, 85, 72, 203, 204, 73, 229, 202, 1, 9, 25, 98, 10, 25, 97, 10, 25,
+        99, 10, 153, 160, 8, 249, 120, 250, 185, 42, 184, 121, 134, 185, 98, 42, 52, 195, 20, 50,
+        199, 20, 178, 192, 20, 178, 196, 226, 90, 108, 62, 64, 245, 2, 136, 145, 154, 151, 194, 5,
+        0, 99, 191, 76, 200, 224, 1, 78, 120, 156, 251, 200, 248, 140, 113, 66, 60, 91, 78, 102,
+        94, 170, 130, 233, 196, 140, 70, 0, 55, 205, 6, 70, 40, 49, 191, 70, 246, 194, 114, 99,
+        182, 20, 184, 178, 198, 206, 94, 166, 34, 181, 62, 9,
+    ];
+    write_test_pack(&mut fs, &pack, 90);
+
+    let (base, changed) = test_pack_contents();
+
+    let object = object::read(&fs, "74fde6").unwrap();
+    assert_eq!(object.hash, "74fde6a3be636a9551f9a89570e9027f88dfacbb");
+    assert_eq!(object.object_type, "blob");
+    assert_eq!(object.data, base.as_bytes());
+
+    assert_eq!(
+        cat_file::execute(&fs, "blob".into(), "b5f5e7a".into()).unwrap(),
+        changed.as_bytes()
+    );
+}
+
+#[test]
+fn test_read_ref_delta() {
+    let mut fs = FileSystem::access();
+
+    // written by `git pack-objects`
+    let pack = [
+        80, 65, 67, 75, 0, 0, 0, 2, 0, 0, 0, 2, 177, 15, 120, 156, 203, 201, 204, 75, 85, 48, 80,
+        200, 79, 83, 40, 201, 72, 85, 72, 203, 204, 73, 229, 202, 1, 9, 25, 98, 10, 25, 97, 10, 25,
+        99, 10, 153, 160, 8, 249, 120, 250, 185, 42, 184, 121, 134, 185, 98, 42, 52, 195, 20, 50,
+        199, 20, 178, 192, 20, 178, 196, 226, 90, 108, 62, 64, 245, 2, 136, 145, 154, 151, 194, 5,
+        0, 99, 191, 76, 200, 240, 1, 181, 245, 231, 166, 47, 120, 142, 55, 128, 255, 73, 76, 129,
+        209, 77, 65, 252, 123, 143, 178, 120, 156, 251, 200, 248, 140, 113, 66, 60, 91, 78, 102,
+        94, 170, 130, 233, 196, 140, 70, 0, 55, 205, 6, 70, 36, 157, 77, 236, 216, 9, 77, 123, 148,
+        181, 81, 181, 71, 25, 139, 98, 98, 143, 166, 83,
+    ];
+    write_test_pack(&mut fs, &pack, 90);
+
+    let (base, _) = test_pack_contents();
+
+    assert_eq!(
+        read(&fs, "74fde6a3be636a9551f9a89570e9027f88dfacbb")
+            .unwrap()
+            .unwrap()
+            .data,
+        base.as_bytes()
+    );
+    assert!(read(&fs, "5c7f7d83d0da2baceb3789aaf457a699455992fe")
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_read_delta_against_itself() {
+    let mut fs = FileSystem::access();
+
+    // a REF_DELTA whose base is the object itself never reaches a base
+    let hash = "74fde6a3be636a9551f9a89570e9027f88dfacbb";
+    let mut pack = b"PACK\x00\x00\x00\x02\x00\x00\x00\x01\x70".to_vec();
+    pack.extend_from_slice(&object::hash_to_bytes(hash));
+    pack.extend(deflate(b""));
+
+    let index = PackIndex::new(
+        vec![PackIndexEntry {
+            hash: ObjectId::from_hex(hash).unwrap(),
+            crc32: 0,
+            offset: 12,
+        }],
+        [0; 20],
+    );
+
+    assert_eq!(
+        read_at(&fs, &pack, &index, 12).unwrap_err(),
+        "fatal: delta chain too long at 12"
+    );
+
+    let directory = pack_directory(&fs);
+    fs.create_directory(&directory);
+
+    let index_path = directory.join("pack-0000000000000000000000000000000000000000.idx");
+    fs.create_file(&index_path);
+    fs.write_file(&index_path, &index.parse_into_file());
+    fs.create_file(&index_path.with_extension("pack"));
+    fs.write_file(&index_path.with_extension("pack"), &pack);
+
+    assert!(read(&fs, hash).is_err());
+}
+
+#[test]
+fn test_create_delta() {
+    let (base, changed) = test_pack_contents();
+
+    let delta = create_delta(base.as_bytes(), changed.as_bytes());
+
+    assert!(delta.len() < changed.len() / 4);
+    assert_eq!(
+        apply_delta(base.as_bytes(), &delta).unwrap(),
+        changed.as_bytes()
+    );
+    assert_eq!(
+        apply_delta(b"", &create_delta(b"", b"new")).unwrap(),
+        b"new"
+    );
+}
+
+#[test]
+fn test_write() {
+    use crate::sub_commands::hash_object;
+    let mut fs = FileSystem::access();
+
+    let (base, changed) = test_pack_contents();
+    let objects = [base, changed]
+        .iter()
+        .map(|contents| Object {
+            hash: hash_object::hash(contents.as_bytes(), "blob"),
+            object_type: "blob".to_string(),
+            data: contents.as_bytes().to_vec(),
+        })
+        .collect::<Vec<Object>>();
+
+    let index_path = write(&mut fs, objects).unwrap();
+    let index = read_index(&fs, &index_path).unwrap();
+    let pack = read_pack_file(&fs, &index_path).unwrap();
+
+    // the bigger, changed file comes first and the base is a delta of it
+    let base_offset = index.find("74fde6a3be636a9551f9a89570e9027f88dfacbb");
+    assert_eq!(
+        index.find("b5f5e7a62f788e3780ff494c81d14d41fc7b8fb2"),
+        Some(12)
+    );
+    assert_eq!(
+        read_header(&pack, base_offset.unwrap() as usize).unwrap().0,
+        OBJ_OFS_DELTA
+    );
+    assert_eq!(
+        index_path.file_name().unwrap().to_str().unwrap(),
+        format!(
+            "pack-{}.idx",
+            object::bytes_to_hash(&pack[pack.len() - 20..])
+        )
+    );
+
+    let (base, _) = test_pack_contents();
+    assert_eq!(object::read(&fs, "74fde6").unwrap().data, base.as_bytes());
+}
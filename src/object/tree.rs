@@ -0,0 +1,223 @@
+use crate::fs::FileSystem;
+use crate::object;
+use std::cmp::Ordering;
+
+pub const TREE_MODE: u32 = 0o40000;
+pub const SUBMODULE_MODE: u32 = 0o160000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub mode: u32,
+    pub name: String,
+    pub hash: String,
+}
+
+impl TreeEntry {
+    pub fn is_tree(&self) -> bool {
+        self.mode == TREE_MODE
+    }
+
+    pub fn object_type(&self) -> &'static str {
+        match self.mode {
+            TREE_MODE => "tree",
+            SUBMODULE_MODE => "commit",
+            _ => "blob",
+        }
+    }
+
+    /// Trees are sorted as if directory names ended with a `/`, so `src.rs`
+    /// comes before the `src` directory.
+    fn sort_key(&self) -> Vec<u8> {
+        let mut key = self.name.as_bytes().to_vec();
+
+        if self.is_tree() {
+            key.push(b'/');
+        }
+
+        key
+    }
+}
+
+impl PartialOrd for TreeEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TreeEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tree {
+    pub entries: Vec<TreeEntry>,
+}
+
+impl Tree {
+    pub fn read(fs: &FileSystem, name: &str) -> Result<Self, String> {
+        let object = object::read(fs, name)?;
+
+        if object.object_type != "tree" {
+            return Err(format!("fatal: {} is not a valid 'tree' object", name));
+        }
+
+        Self::parse_from_object(&object.data)
+    }
+
+    /// Lists every non-tree entry reachable from the tree, with names being
+    /// full paths starting at `prefix`.
+    pub fn read_recursive(
+        fs: &FileSystem,
+        name: &str,
+        prefix: &str,
+    ) -> Result<Vec<TreeEntry>, String> {
+        let mut entries = vec![];
+
+        for entry in Self::read(fs, name)?.entries {
+            let path = format!("{}{}", prefix, entry.name);
+
+            if entry.is_tree() {
+                entries.append(&mut Self::read_recursive(
+                    fs,
+                    &entry.hash,
+                    &format!("{}/", path),
+                )?);
+            } else {
+                entries.push(TreeEntry {
+                    name: path,
+                    ..entry
+                });
+            }
+        }
+
+        Ok(entries)
+    }
+
+    pub fn parse_from_object(data: &[u8]) -> Result<Self, String> {
+        let mut entries = vec![];
+        let mut i = 0;
+
+        while i < data.len() {
+            let corrupt = || "fatal: corrupt tree object".to_string();
+
+            let space_index = data[i..]
+                .iter()
+                .position(|b| *b == b' ')
+                .ok_or_else(corrupt)?
+                + i;
+            let null_index = data[space_index..]
+                .iter()
+                .position(|b| *b == b'\x00')
+                .ok_or_else(corrupt)?
+                + space_index;
+
+            if null_index + 21 > data.len() {
+                return Err(corrupt());
+            }
+
+            let mode = std::str::from_utf8(&data[i..space_index])
+                .ok()
+                .and_then(|mode| u32::from_str_radix(mode, 8).ok())
+                .ok_or_else(corrupt)?;
+            let name = std::str::from_utf8(&data[space_index + 1..null_index])
+                .map_err(|_| corrupt())?
+                .to_string();
+            let hash = object::bytes_to_hash(&data[null_index + 1..null_index + 21]);
+
+            entries.push(TreeEntry { mode, name, hash });
+
+            i = null_index + 21;
+        }
+
+        Ok(Self { entries })
+    }
+
+    pub fn parse_into_object(&self) -> Vec<u8> {
+        let mut entries = self.entries.clone();
+        entries.sort();
+
+        let mut output = vec![];
+
+        for entry in entries {
+            output.append(&mut format!("{:o} {}", entry.mode, entry.name).into_bytes());
+            output.push(b'\x00');
+
+            output.extend_from_slice(&object::hash_to_bytes(&entry.hash));
+        }
+
+        output
+    }
+}
+
+/// Writes a tree with an executable `README` and `src/main.rs`.
+#[cfg(test)]
+pub fn write_test_tree(fs: &mut FileSystem) -> String {
+    use crate::sub_commands::hash_object;
+
+    let readme = hash_object::execute(fs, b"readme\n", "blob".into(), true).unwrap();
+    let main = hash_object::execute(fs, b"fn main() {}\n", "blob".into(), true).unwrap();
+
+    let src = Tree {
+        entries: vec![TreeEntry {
+            mode: 0o100644,
+            name: "main.rs".to_string(),
+            hash: main,
+        }],
+    };
+    let src = hash_object::execute(fs, &src.parse_into_object(), "tree".into(), true).unwrap();
+
+    let root = Tree {
+        entries: vec![
+            TreeEntry {
+                mode: 0o100755,
+                name: "README".to_string(),
+                hash: readme,
+            },
+            TreeEntry {
+                mode: TREE_MODE,
+                name: "src".to_string(),
+                hash: src,
+            },
+        ],
+    };
+
+    hash_object::execute(fs, &root.parse_into_object(), "tree".into(), true).unwrap()
+}
+
+#[test]
+fn test_tree_round_trip() {
+    let tree = Tree {
+        entries: vec![
+            TreeEntry {
+                mode: TREE_MODE,
+                name: "src".to_string(),
+                hash: "4b825dc642cb6eb9a060e54bf8d69288fbee4904".to_string(),
+            },
+            TreeEntry {
+                mode: 0o100644,
+                name: "src.rs".to_string(),
+                hash: "16a80e3983ecbd703a3f4358588cfe08d5219a2c".to_string(),
+            },
+        ],
+    };
+
+    let object = tree.parse_into_object();
+
+    assert_eq!(&object[..14], b"100644 src.rs\x00");
+
+    let parsed = Tree::parse_from_object(&object).unwrap();
+
+    assert_eq!(parsed.entries[0], tree.entries[1]);
+    assert_eq!(parsed.entries[1], tree.entries[0]);
+    assert_eq!(parsed.entries[1].object_type(), "tree");
+}
+
+#[test]
+fn test_tree_parse_truncated() {
+    assert_eq!(
+        Tree::parse_from_object(b"100644 src.rs\x00\x16\xa8").unwrap_err(),
+        "fatal: corrupt tree object"
+    );
+}
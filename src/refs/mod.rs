@@ -0,0 +1,498 @@
+use crate::fs::{FileSystem, Fs};
+use crate::object::commit::Signature;
+use crate::repository;
+use std::path::PathBuf;
+
+const MAX_SYMBOLIC_REF_DEPTH: usize = 5;
+
+/// What a reflog records as the old value of a ref that didn't exist.
+const NULL_HASH: &str = "0000000000000000000000000000000000000000";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Ref {
+    Direct(String),
+    Symbolic(String),
+}
+
+fn ref_path(fs: &FileSystem, name: &str) -> PathBuf {
+    repository::path(fs).join(name)
+}
+
+/// Follows the same rules as `git check-ref-format`, plus allowing the
+/// one-level `HEAD`.
+pub fn is_valid_name(name: &str) -> bool {
+    if name == "HEAD" {
+        return true;
+    }
+
+    if name.is_empty()
+        || name.starts_with('/')
+        || name.ends_with('/')
+        || name.ends_with('.')
+        || name.contains("..")
+        || name.contains("//")
+        || name.contains("@{")
+        || name == "@"
+    {
+        return false;
+    }
+
+    let has_forbidden_character = name
+        .chars()
+        .any(|c| c.is_ascii_control() || " ~^:?*[\\".contains(c));
+
+    let has_bad_component = name
+        .split('/')
+        .any(|component| component.starts_with('.') || component.ends_with(".lock"));
+
+    !has_forbidden_character && !has_bad_component
+}
+
+/// Reads the refs git moved into `packed-refs`, as their name and hash,
+/// leaving out the peeled values of tags on the `^` lines.
+fn read_packed(fs: &FileSystem) -> Result<Vec<(String, String)>, String> {
+    let path = ref_path(fs, "packed-refs");
+
+    if !fs.path_exists(&path) {
+        return Ok(vec![]);
+    }
+
+    fs.get_file_contents(&path)?
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('^'))
+        .map(|line| match line.split_once(' ') {
+            Some((hash, name)) => Ok((name.to_string(), hash.to_string())),
+            None => Err(format!("fatal: unexpected line in packed-refs: {}", line)),
+        })
+        .collect()
+}
+
+pub fn read(fs: &FileSystem, name: &str) -> Result<Option<Ref>, String> {
+    let path = ref_path(fs, name);
+
+    if !fs.path_exists(&path) {
+        // loose refs take precedence over packed ones
+        return Ok(read_packed(fs)?
+            .into_iter()
+            .find(|(packed_name, _)| packed_name == name)
+            .map(|(_, hash)| Ref::Direct(hash)));
+    }
+
+    let contents = fs.get_file_contents(&path)?;
+    let contents = contents.trim_end();
+
+    if let Some(target) = contents.strip_prefix("ref: ") {
+        return Ok(Some(Ref::Symbolic(target.to_string())));
+    }
+
+    if contents.len() != 40 || !contents.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("fatal: bad ref {}: {}", name, contents));
+    }
+
+    Ok(Some(Ref::Direct(contents.to_string())))
+}
+
+/// Follows symbolic refs until reaching the ref that holds a hash, which is
+/// returned together with its value, if the ref already exists.
+pub fn resolve_name(fs: &FileSystem, name: &str) -> Result<(String, Option<String>), String> {
+    let mut current = name.to_string();
+
+    for _ in 0..MAX_SYMBOLIC_REF_DEPTH {
+        match read(fs, &current)? {
+            Some(Ref::Symbolic(target)) => current = target,
+            Some(Ref::Direct(hash)) => return Ok((current, Some(hash))),
+            None => return Ok((current, None)),
+        }
+    }
+
+    Err(format!("fatal: symbolic ref loop at {}", name))
+}
+
+pub fn resolve(fs: &FileSystem, name: &str) -> Result<Option<String>, String> {
+    Ok(resolve_name(fs, name)?.1)
+}
+
+fn write(fs: &mut FileSystem, name: &str, contents: &str) {
+    let path = ref_path(fs, name);
+
+    if let Some(parent) = path.parent() {
+        if !fs.path_exists(parent) {
+            fs.create_directory(&parent.to_path_buf());
+        }
+    }
+
+    if !fs.path_exists(&path) {
+        fs.create_file(&path);
+    }
+
+    fs.write_file(&path, contents.as_bytes());
+}
+
+/// Points `name` at `hash`, writing through symbolic refs unless `no_deref`
+/// is set, in which case `name` itself becomes a direct ref.
+pub fn update(fs: &mut FileSystem, name: &str, hash: &str, no_deref: bool) -> Result<(), String> {
+    if !is_valid_name(name) {
+        return Err(format!("fatal: invalid ref name '{}'", name));
+    }
+
+    let target = if no_deref {
+        name.to_string()
+    } else {
+        resolve_name(fs, name)?.0
+    };
+
+    write(fs, &target, &format!("{}\n", hash));
+
+    Ok(())
+}
+
+pub fn update_symbolic(fs: &mut FileSystem, name: &str, target: &str) -> Result<(), String> {
+    if !is_valid_name(name) {
+        return Err(format!("fatal: invalid ref name '{}'", name));
+    }
+
+    if !target.starts_with("refs/") || !is_valid_name(target) {
+        return Err(format!(
+            "fatal: Refusing to point {} outside of refs/",
+            name
+        ));
+    }
+
+    write(fs, name, &format!("ref: {}\n", target));
+
+    Ok(())
+}
+
+pub fn delete(fs: &mut FileSystem, name: &str) -> Result<(), String> {
+    let path = ref_path(fs, name);
+    let packed = read_packed(fs)?
+        .iter()
+        .any(|(packed_name, _)| packed_name == name);
+
+    if !fs.path_exists(&path) && !packed {
+        return Err(format!("error: unable to delete ref {}: not found", name));
+    }
+
+    if fs.path_exists(&path) {
+        fs.remove_file(&path);
+    }
+
+    if packed {
+        let packed_path = ref_path(fs, "packed-refs");
+        let mut lines = vec![];
+        let mut deleted = false;
+
+        // the peeled value of a deleted tag goes away with it
+        for line in fs.get_file_contents(&packed_path)?.lines() {
+            if line.starts_with('^') && deleted {
+                continue;
+            }
+
+            deleted = line.split_once(' ').map(|(_, packed_name)| packed_name) == Some(name);
+
+            if !deleted {
+                lines.push(format!("{}\n", line));
+            }
+        }
+
+        fs.write_file(&packed_path, lines.concat().as_bytes());
+    }
+
+    Ok(())
+}
+
+/// Adds an entry to the reflog of `name`, recording that it moved from `old`
+/// to `new` and why, in the format git uses.
+pub fn append_log(
+    fs: &mut FileSystem,
+    name: &str,
+    (old, new): (Option<&str>, &str),
+    committer: &Signature,
+    message: &str,
+) -> Result<(), String> {
+    let log_name = format!("logs/{}", name);
+    let path = ref_path(fs, &log_name);
+
+    let mut log = if fs.path_exists(&path) {
+        fs.get_file_contents(&path)?
+    } else {
+        String::new()
+    };
+
+    log.push_str(&format!(
+        "{} {} {}\t{}\n",
+        old.unwrap_or(NULL_HASH),
+        new,
+        committer,
+        message
+    ));
+
+    write(fs, &log_name, &log);
+
+    Ok(())
+}
+
+/// Finds what `HEAD` was checked out at before the `n`-th last checkout, a
+/// branch name or a commit, from the entries its reflog has for checkouts.
+pub fn previous_checkout(fs: &FileSystem, n: usize) -> Result<Option<String>, String> {
+    let path = ref_path(fs, "logs/HEAD");
+
+    if !fs.path_exists(&path) || n == 0 {
+        return Ok(None);
+    }
+
+    Ok(fs
+        .get_file_contents(&path)?
+        .lines()
+        .rev()
+        .filter_map(|line| {
+            let message = line.split_once('\t')?.1;
+            let (from, _) = message
+                .strip_prefix("checkout: moving from ")?
+                .split_once(" to ")?;
+
+            Some(from.to_string())
+        })
+        .nth(n - 1))
+}
+
+/// Lists every direct ref under `refs/`, sorted by name.
+pub fn list(fs: &FileSystem) -> Result<Vec<(String, String)>, String> {
+    let papyrus_path = repository::path(fs);
+    let refs_path = papyrus_path.join("refs");
+
+    let mut refs = vec![];
+
+    for path in fs.get_directory_files_recursively(&refs_path) {
+        let name = path
+            .strip_prefix(&papyrus_path)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        if let Some(hash) = resolve(fs, &name)? {
+            refs.push((name, hash));
+        }
+    }
+
+    for (name, hash) in read_packed(fs)? {
+        if !refs.iter().any(|(loose_name, _)| *loose_name == name) {
+            refs.push((name, hash));
+        }
+    }
+
+    refs.sort();
+
+    Ok(refs)
+}
+
+/// Expands a short name such as `master` or `v1.0` into the full ref name
+/// and its value, trying the same places git does, in the same order.
+pub fn dwim(fs: &FileSystem, name: &str) -> Result<Option<(String, String)>, String> {
+    if !is_valid_name(name) {
+        return Ok(None);
+    }
+
+    let candidates = [
+        name.to_string(),
+        format!("refs/{}", name),
+        format!("refs/tags/{}", name),
+        format!("refs/heads/{}", name),
+        format!("refs/remotes/{}", name),
+    ];
+
+    for candidate in candidates.iter() {
+        if let Some(hash) = resolve(fs, candidate)? {
+            return Ok(Some((candidate.clone(), hash)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Shortens `refs/heads/master` to `master`, the way branch names are shown.
+pub fn shorten(name: &str) -> &str {
+    ["refs/heads/", "refs/tags/", "refs/remotes/", "refs/"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(name)
+}
+
+#[test]
+fn test_is_valid_name() {
+    assert!(is_valid_name("HEAD"));
+    assert!(is_valid_name("refs/heads/master"));
+    assert!(is_valid_name("refs/heads/feature/cool-thing"));
+    assert!(!is_valid_name("refs/heads/.hidden"));
+    assert!(!is_valid_name("refs/heads/a..b"));
+    assert!(!is_valid_name("refs/heads/master.lock"));
+    assert!(!is_valid_name("refs/heads/with space"));
+    assert!(!is_valid_name("refs/heads/master^2"));
+    assert!(!is_valid_name("refs/heads/"));
+}
+
+#[test]
+fn test_update_through_symbolic_ref() {
+    let mut fs = FileSystem::access();
+
+    update_symbolic(&mut fs, "HEAD", "refs/heads/master").unwrap();
+
+    assert_eq!(
+        resolve_name(&fs, "HEAD").unwrap(),
+        ("refs/heads/master".to_string(), None)
+    );
+
+    update(
+        &mut fs,
+        "HEAD",
+        "3a57a4f328d9448ce1e3ee4913712c63782dcaf2",
+        false,
+    )
+    .unwrap();
+
+    assert_eq!(
+        read(&fs, "HEAD").unwrap(),
+        Some(Ref::Symbolic("refs/heads/master".to_string()))
+    );
+    assert_eq!(
+        read(&fs, "refs/heads/master").unwrap(),
+        Some(Ref::Direct(
+            "3a57a4f328d9448ce1e3ee4913712c63782dcaf2".to_string()
+        ))
+    );
+    assert_eq!(
+        list(&fs).unwrap(),
+        vec![(
+            "refs/heads/master".to_string(),
+            "3a57a4f328d9448ce1e3ee4913712c63782dcaf2".to_string()
+        )]
+    );
+}
+
+#[test]
+fn test_update_no_deref_detaches_head() {
+    let mut fs = FileSystem::access();
+
+    update_symbolic(&mut fs, "HEAD", "refs/heads/master").unwrap();
+    update(
+        &mut fs,
+        "HEAD",
+        "3a57a4f328d9448ce1e3ee4913712c63782dcaf2",
+        true,
+    )
+    .unwrap();
+
+    assert_eq!(
+        read(&fs, "HEAD").unwrap(),
+        Some(Ref::Direct(
+            "3a57a4f328d9448ce1e3ee4913712c63782dcaf2".to_string()
+        ))
+    );
+    assert_eq!(read(&fs, "refs/heads/master").unwrap(), None);
+}
+
+#[test]
+fn test_dwim() {
+    let mut fs = FileSystem::access();
+
+    update_symbolic(&mut fs, "HEAD", "refs/heads/master").unwrap();
+    update(
+        &mut fs,
+        "refs/heads/v1.0",
+        "3a57a4f328d9448ce1e3ee4913712c63782dcaf2",
+        false,
+    )
+    .unwrap();
+    update(
+        &mut fs,
+        "refs/tags/v1.0",
+        "4b825dc642cb6eb9a060e54bf8d69288fbee4904",
+        false,
+    )
+    .unwrap();
+
+    assert_eq!(
+        dwim(&fs, "v1.0").unwrap(),
+        Some((
+            "refs/tags/v1.0".to_string(),
+            "4b825dc642cb6eb9a060e54bf8d69288fbee4904".to_string()
+        ))
+    );
+    assert_eq!(
+        dwim(&fs, "heads/v1.0").unwrap(),
+        Some((
+            "refs/heads/v1.0".to_string(),
+            "3a57a4f328d9448ce1e3ee4913712c63782dcaf2".to_string()
+        ))
+    );
+    assert_eq!(dwim(&fs, "HEAD").unwrap(), None);
+    assert_eq!(dwim(&fs, "missing").unwrap(), None);
+}
+
+#[test]
+fn test_symbolic_ref_loop() {
+    let mut fs = FileSystem::access();
+
+    update_symbolic(&mut fs, "refs/heads/a", "refs/heads/b").unwrap();
+    update_symbolic(&mut fs, "refs/heads/b", "refs/heads/a").unwrap();
+
+    assert_eq!(
+        resolve(&fs, "refs/heads/a").unwrap_err(),
+        "fatal: symbolic ref loop at refs/heads/a"
+    );
+}
+
+#[test]
+fn test_packed_refs() {
+    let mut fs = FileSystem::access();
+
+    let packed_path = ref_path(&fs, "packed-refs");
+    fs.create_file(&packed_path);
+    fs.write_file(
+        &packed_path,
+        b"# pack-refs with: peeled fully-peeled sorted \n\
+          1111111111111111111111111111111111111111 refs/heads/master\n\
+          2222222222222222222222222222222222222222 refs/tags/v1.0\n\
+          ^3333333333333333333333333333333333333333\n",
+    );
+
+    assert_eq!(
+        read(&fs, "refs/tags/v1.0").unwrap(),
+        Some(Ref::Direct(
+            "2222222222222222222222222222222222222222".to_string()
+        ))
+    );
+
+    // a loose ref shadows the packed one
+    update(
+        &mut fs,
+        "refs/heads/master",
+        "4444444444444444444444444444444444444444",
+        false,
+    )
+    .unwrap();
+
+    assert_eq!(
+        list(&fs).unwrap(),
+        vec![
+            (
+                "refs/heads/master".to_string(),
+                "4444444444444444444444444444444444444444".to_string()
+            ),
+            (
+                "refs/tags/v1.0".to_string(),
+                "2222222222222222222222222222222222222222".to_string()
+            ),
+        ]
+    );
+
+    delete(&mut fs, "refs/tags/v1.0").unwrap();
+    delete(&mut fs, "refs/heads/master").unwrap();
+
+    assert_eq!(list(&fs).unwrap(), vec![]);
+    assert_eq!(
+        fs.get_file_contents(&packed_path).unwrap(),
+        "# pack-refs with: peeled fully-peeled sorted \n"
+    );
+}
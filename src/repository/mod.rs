@@ -0,0 +1,222 @@
+use crate::fs::{FileSystem, Fs};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf};
+
+/// Names the repository directory to use instead of `.papyrus`, such as the
+/// `.git` directory of an existing git repository, relative to the current
+/// directory unless it is absolute.
+pub const DIRECTORY_VARIABLE: &str = "PAPYRUS_DIR";
+
+fn resolve_path(current_directory: &str, configured: Option<&str>) -> PathBuf {
+    let current_directory = PathBuf::from(current_directory);
+
+    match configured {
+        Some(directory) if !directory.is_empty() => current_directory.join(directory),
+        _ => current_directory.join(".papyrus"),
+    }
+}
+
+/// The directory holding the objects, refs and index of the repository.
+pub fn path(fs: &FileSystem) -> PathBuf {
+    resolve_path(
+        &fs.current_directory(),
+        std::env::var(DIRECTORY_VARIABLE).ok().as_deref(),
+    )
+}
+
+/// Where a command runs: the root of the worktree, which commands work from,
+/// and where papyrus was started from, relative to that root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repository {
+    pub worktree: PathBuf,
+    pub prefix: PathBuf,
+    /// The repository directory when it isn't the `.papyrus` of the
+    /// worktree, as for a bare repository, whose directory is also where
+    /// commands run from.
+    pub git_dir: Option<PathBuf>,
+}
+
+/// Whether `directory` is itself a repository directory, as a bare one is,
+/// going by what git checks: a `HEAD`, and `objects` and `refs` directories.
+fn is_repository_directory(fs: &FileSystem, directory: &Path) -> bool {
+    ["HEAD", "objects", "refs"]
+        .iter()
+        .all(|name| fs.path_exists(&directory.join(name)))
+}
+
+impl Repository {
+    /// Walks up from the current directory until finding one that holds a
+    /// `.papyrus` or is a bare repository, without crossing into another
+    /// filesystem. A repository given through `PAPYRUS_DIR` is not looked
+    /// for, the current directory being its worktree.
+    pub fn discover(fs: &FileSystem) -> Result<Self, String> {
+        let current_directory = PathBuf::from(fs.current_directory());
+
+        if std::env::var_os(DIRECTORY_VARIABLE).is_some() {
+            return Ok(Self {
+                worktree: current_directory,
+                prefix: PathBuf::new(),
+                git_dir: None,
+            });
+        }
+
+        let device = fs.metadata(&current_directory)?.dev();
+
+        for directory in current_directory.ancestors() {
+            match fs.metadata(&directory) {
+                Ok(metadata) if metadata.dev() == device => {}
+                Ok(_) => {
+                    return Err(format!(
+                        "fatal: not a papyrus repository (or any parent up to mount point {})",
+                        directory.join("..").display()
+                    ))
+                }
+                Err(_) => break,
+            }
+
+            let bare = if fs.path_exists(&directory.join(".papyrus")) {
+                false
+            } else if is_repository_directory(fs, directory) {
+                true
+            } else {
+                continue;
+            };
+
+            return Ok(Self {
+                worktree: directory.to_path_buf(),
+                prefix: current_directory
+                    .strip_prefix(directory)
+                    .unwrap()
+                    .to_path_buf(),
+                git_dir: bare.then(|| directory.to_path_buf()),
+            });
+        }
+
+        Err(
+            "fatal: not a papyrus repository (or any of the parent directories): .papyrus"
+                .to_string(),
+        )
+    }
+
+    /// Turns a path given relative to where papyrus was started from, or an
+    /// absolute one, into a path relative to the root of the worktree.
+    pub fn worktree_path(&self, path: &Path) -> Result<PathBuf, String> {
+        let outside = || {
+            format!(
+                "fatal: {}: '{}' is outside repository",
+                path.display(),
+                path.display()
+            )
+        };
+
+        let full_path = self.worktree.join(&self.prefix).join(path);
+        let mut normalized = PathBuf::new();
+
+        for component in full_path
+            .strip_prefix(&self.worktree)
+            .map_err(|_| outside())?
+            .components()
+        {
+            match component {
+                Component::Normal(name) => normalized.push(name),
+                Component::ParentDir if normalized.parent().is_none() => return Err(outside()),
+                Component::ParentDir => {
+                    normalized.pop();
+                }
+                _ => {}
+            }
+        }
+
+        Ok(normalized)
+    }
+}
+
+#[test]
+fn test_resolve_path() {
+    assert_eq!(
+        resolve_path("/Users/jack/cool_project", None),
+        PathBuf::from("/Users/jack/cool_project/.papyrus")
+    );
+    assert_eq!(
+        resolve_path("/Users/jack/cool_project", Some("")),
+        PathBuf::from("/Users/jack/cool_project/.papyrus")
+    );
+    assert_eq!(
+        resolve_path("/Users/jack/cool_project", Some(".git")),
+        PathBuf::from("/Users/jack/cool_project/.git")
+    );
+    assert_eq!(
+        resolve_path("/Users/jack/cool_project", Some("/tmp/other.git")),
+        PathBuf::from("/tmp/other.git")
+    );
+}
+
+#[test]
+fn test_discover() {
+    let mut fs = FileSystem::access();
+
+    assert_eq!(
+        Repository::discover(&fs),
+        Err(
+            "fatal: not a papyrus repository (or any of the parent directories): .papyrus"
+                .to_string()
+        )
+    );
+
+    fs.create_directory(&"/Users/jack/cool_project/.papyrus");
+    fs.create_directory(&"/Users/jack/cool_project/src");
+    fs.create_directory(&"/Users/jack/cool_project/src/deep");
+    fs.set_current_directory(&"/Users/jack/cool_project/src/deep");
+
+    let repository = Repository::discover(&fs).unwrap();
+
+    assert_eq!(
+        repository,
+        Repository {
+            worktree: PathBuf::from("/Users/jack/cool_project"),
+            prefix: PathBuf::from("src/deep"),
+            git_dir: None,
+        }
+    );
+    assert_eq!(
+        repository.worktree_path(Path::new("file.rs")),
+        Ok(PathBuf::from("src/deep/file.rs"))
+    );
+    assert_eq!(
+        repository.worktree_path(Path::new("../../README.md")),
+        Ok(PathBuf::from("README.md"))
+    );
+    assert_eq!(
+        repository.worktree_path(Path::new("/Users/jack/cool_project/./src/lib.rs")),
+        Ok(PathBuf::from("src/lib.rs"))
+    );
+    assert_eq!(
+        repository.worktree_path(Path::new("../../../other/file.rs")),
+        Err(
+            "fatal: ../../../other/file.rs: '../../../other/file.rs' is outside repository"
+                .to_string()
+        )
+    );
+    assert!(repository.worktree_path(Path::new("/etc/passwd")).is_err());
+}
+
+#[test]
+fn test_discover_bare() {
+    let mut fs = FileSystem::access();
+
+    fs.create_directory(&"/Users/jack/bare.git");
+    fs.create_file(&"/Users/jack/bare.git/HEAD");
+    fs.create_directory(&"/Users/jack/bare.git/refs");
+    fs.create_directory(&"/Users/jack/bare.git/objects");
+    fs.create_directory(&"/Users/jack/bare.git/objects/info");
+    fs.set_current_directory(&"/Users/jack/bare.git/objects/info");
+
+    assert_eq!(
+        Repository::discover(&fs),
+        Ok(Repository {
+            worktree: PathBuf::from("/Users/jack/bare.git"),
+            prefix: PathBuf::from("objects/info"),
+            git_dir: Some(PathBuf::from("/Users/jack/bare.git")),
+        })
+    );
+}
@@ -0,0 +1,344 @@
+use crate::fs::FileSystem;
+use crate::index;
+use crate::object::commit::Commit;
+use crate::object::tree::Tree;
+use crate::object::{self, Object};
+use crate::refs;
+
+fn unknown_revision(spec: &str) -> String {
+    format!(
+        "fatal: ambiguous argument '{}': unknown revision or path not in the working tree.",
+        spec
+    )
+}
+
+/// The object an annotated tag points at.
+fn tag_target(tag: &Object) -> Result<String, String> {
+    String::from_utf8_lossy(&tag.data)
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("object "))
+        .map(|hash| hash.to_string())
+        .ok_or_else(|| format!("fatal: corrupt tag object {}", tag.hash))
+}
+
+/// Follows tags, and commits to their tree, until reaching an object of
+/// `object_type`, `object` meaning any object and an empty type meaning
+/// anything that isn't a tag.
+pub fn peel(fs: &FileSystem, hash: &str, object_type: &str) -> Result<String, String> {
+    let mut object = object::read(fs, hash)?;
+
+    loop {
+        if object.object_type == object_type
+            || object_type == "object"
+            || (object_type.is_empty() && object.object_type != "tag")
+        {
+            return Ok(object.hash);
+        }
+
+        let next = match &object.object_type[..] {
+            "tag" => tag_target(&object)?,
+            "commit" if object_type == "tree" => Commit::parse_from_object(&object.data)?.tree,
+            _ => {
+                return Err(format!(
+                    "fatal: object {} is a {}, not a {}",
+                    object.hash, object.object_type, object_type
+                ))
+            }
+        };
+
+        object = object::read(fs, &next)?;
+    }
+}
+
+/// The branch name or commit `@{-<n>}` stands for: what was checked out
+/// before the n-th last checkout. Anything else isn't expanded.
+pub fn previous_checkout(fs: &FileSystem, spec: &str) -> Result<Option<String>, String> {
+    let n = match spec.strip_prefix("@{-").and_then(|n| n.strip_suffix('}')) {
+        Some(n) => n.parse::<usize>().map_err(|_| unknown_revision(spec))?,
+        None => return Ok(None),
+    };
+
+    refs::previous_checkout(fs, n)?
+        .map(Some)
+        .ok_or_else(|| unknown_revision(spec))
+}
+
+/// Resolves a ref name such as `master`, `@` standing for `HEAD`, `@{-<n>}`
+/// for what was checked out before, or an object name, full or abbreviated.
+fn resolve_name(fs: &FileSystem, name: &str) -> Result<String, String> {
+    if let Some(previous) = previous_checkout(fs, name)? {
+        return resolve_name(fs, &previous);
+    }
+
+    let name = if name == "@" { "HEAD" } else { name };
+
+    if let Some((_, hash)) = refs::dwim(fs, name)? {
+        return Ok(hash);
+    }
+
+    object::find(fs, name)
+}
+
+/// Looks `path` up in the index, at the given merge stage.
+fn resolve_index_path(fs: &FileSystem, path: &str, stage: u8) -> Result<String, String> {
+    index::read_entries(fs)?
+        .into_iter()
+        .find(|entry| entry.path == path.as_bytes() && entry.stage == stage)
+        .map(|entry| entry.hash.to_string())
+        .ok_or_else(|| {
+            format!(
+                "fatal: path '{}' does not exist (neither on disk nor in the index)",
+                path
+            )
+        })
+}
+
+/// Looks `path` up in the tree `revision` points at.
+fn resolve_tree_path(fs: &FileSystem, revision: &str, path: &str) -> Result<String, String> {
+    let mut hash = peel(fs, &resolve(fs, revision)?, "tree")?;
+    let missing = || format!("fatal: path '{}' does not exist in '{}'", path, revision);
+
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        let tree = Tree::read(fs, &hash).map_err(|_| missing())?;
+
+        hash = tree
+            .entries
+            .into_iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| entry.hash)
+            .ok_or_else(missing)?;
+    }
+
+    Ok(hash)
+}
+
+/// Resolves a revision into the object hash it names: a ref, `@{-<n>}` or an
+/// object name followed by any number of `~<n>` (the n-th first parent), `^<n>`
+/// (the n-th parent) and `^{<type>}` (peeling to a type), or `<rev>:<path>`
+/// and `:[<stage>:]<path>` for what is at a path of a tree or of the index.
+pub fn resolve(fs: &FileSystem, spec: &str) -> Result<String, String> {
+    if let Some(path) = spec.strip_prefix(':') {
+        return match path.as_bytes() {
+            [stage @ b'0'..=b'3', b':', ..] => resolve_index_path(fs, &path[2..], stage - b'0'),
+            _ => resolve_index_path(fs, path, 0),
+        };
+    }
+
+    if let Some(index) = spec.find(':') {
+        return resolve_tree_path(fs, &spec[..index], &spec[index + 1..]);
+    }
+
+    let (name, mut suffixes) = spec.split_at(spec.find(['~', '^']).unwrap_or(spec.len()));
+
+    if suffixes.is_empty() {
+        return resolve_name(fs, name);
+    }
+
+    let mut hash = resolve_name(fs, name).map_err(|_| unknown_revision(spec))?;
+
+    while !suffixes.is_empty() {
+        let (operator, rest) = suffixes.split_at(1);
+
+        if operator == "^" && rest.starts_with('{') {
+            let end = rest.find('}').ok_or_else(|| unknown_revision(spec))?;
+
+            hash = peel(fs, &hash, &rest[1..end])?;
+            suffixes = &rest[end + 1..];
+            continue;
+        }
+
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let count = match digits {
+            0 => 1,
+            _ => rest[..digits]
+                .parse::<usize>()
+                .map_err(|_| unknown_revision(spec))?,
+        };
+        suffixes = &rest[digits..];
+
+        hash = peel(fs, &hash, "commit").map_err(|_| unknown_revision(spec))?;
+
+        if operator == "~" {
+            for _ in 0..count {
+                hash = Commit::read(fs, &hash)?
+                    .parents
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| unknown_revision(spec))?;
+            }
+        } else if count > 0 {
+            hash = Commit::read(fs, &hash)?
+                .parents
+                .into_iter()
+                .nth(count - 1)
+                .ok_or_else(|| unknown_revision(spec))?;
+        }
+    }
+
+    Ok(hash)
+}
+
+#[cfg(test)]
+fn set_up_history(fs: &mut FileSystem) -> Vec<String> {
+    use crate::object::commit::test_commit;
+    use crate::sub_commands::hash_object;
+
+    let first = test_commit(fs, &[("file.txt", "first\n", 0o100644)], vec![]);
+    let second = test_commit(
+        fs,
+        &[("file.txt", "second\n", 0o100644)],
+        vec![first.clone()],
+    );
+    let side = test_commit(fs, &[("side.txt", "side\n", 0o100644)], vec![first.clone()]);
+    let merge = test_commit(
+        fs,
+        &[
+            ("file.txt", "second\n", 0o100644),
+            ("src/lib.rs", "lib\n", 0o100644),
+        ],
+        vec![second.clone(), side.clone()],
+    );
+    refs::update(fs, "refs/heads/master", &merge, false).unwrap();
+    refs::update_symbolic(fs, "HEAD", "refs/heads/master").unwrap();
+
+    let tag = hash_object::execute(
+        fs,
+        format!(
+            "object {}\ntype commit\ntag v1.0\ntagger Jack <jack@example.com> 1591510158 -0300\n\nrelease\n",
+            second
+        )
+        .as_bytes(),
+        "tag".into(),
+        true,
+    )
+    .unwrap();
+    refs::update(fs, "refs/tags/v1.0", &tag, false).unwrap();
+
+    vec![first, second, side, merge, tag]
+}
+
+#[test]
+fn test_resolve_ancestry() {
+    use crate::fs::Fs;
+    let mut fs = FileSystem::access();
+
+    let history = set_up_history(&mut fs);
+    let (first, second, side, merge) = (&history[0], &history[1], &history[2], &history[3]);
+
+    assert_eq!(&resolve(&fs, "HEAD").unwrap(), merge);
+    assert_eq!(&resolve(&fs, "@").unwrap(), merge);
+    assert_eq!(&resolve(&fs, "master^").unwrap(), second);
+    assert_eq!(&resolve(&fs, "master^2").unwrap(), side);
+    assert_eq!(&resolve(&fs, "HEAD^0").unwrap(), merge);
+    assert_eq!(&resolve(&fs, "HEAD~2").unwrap(), first);
+    assert_eq!(&resolve(&fs, "HEAD^2~").unwrap(), first);
+    assert_eq!(&resolve(&fs, &format!("{}~~", &merge[..7])).unwrap(), first);
+    assert_eq!(&resolve(&fs, "v1.0~1").unwrap(), first);
+
+    assert_eq!(
+        resolve(&fs, "HEAD~3").unwrap_err(),
+        "fatal: ambiguous argument 'HEAD~3': unknown revision or path not in the working tree."
+    );
+    assert_eq!(
+        resolve(&fs, "master^3").unwrap_err(),
+        "fatal: ambiguous argument 'master^3': unknown revision or path not in the working tree."
+    );
+    assert_eq!(
+        resolve(&fs, "nothing").unwrap_err(),
+        "fatal: Not a valid object name nothing"
+    );
+}
+
+#[test]
+fn test_resolve_previous_checkout() {
+    use crate::fs::Fs;
+    use crate::object::commit::test_signature;
+    let mut fs = FileSystem::access();
+
+    let history = set_up_history(&mut fs);
+    let (first, second, merge) = (&history[0], &history[1], &history[3]);
+    refs::update(&mut fs, "refs/heads/topic", second, false).unwrap();
+
+    for (old, new, message) in [
+        (merge, second, "checkout: moving from master to topic"),
+        (second, first, "commit: not a checkout"),
+        (
+            first,
+            merge,
+            &format!("checkout: moving from topic to {}", merge),
+        ),
+    ] {
+        let entry = (Some(&old[..]), &new[..]);
+        refs::append_log(&mut fs, "HEAD", entry, &test_signature(), message).unwrap();
+    }
+
+    assert_eq!(&resolve(&fs, "@{-1}").unwrap(), second);
+    assert_eq!(&resolve(&fs, "@{-1}~1").unwrap(), first);
+    assert_eq!(&resolve(&fs, "@{-2}").unwrap(), merge);
+
+    assert_eq!(
+        resolve(&fs, "@{-3}").unwrap_err(),
+        "fatal: ambiguous argument '@{-3}': unknown revision or path not in the working tree."
+    );
+}
+
+#[test]
+fn test_resolve_peeling_and_paths() {
+    use crate::fs::Fs;
+    use crate::sub_commands::hash_object;
+    let mut fs = FileSystem::access();
+
+    let history = set_up_history(&mut fs);
+    let (second, merge, tag) = (&history[1], &history[3], &history[4]);
+
+    let merge_tree = Commit::read(&fs, merge).unwrap().tree;
+
+    assert_eq!(&resolve(&fs, "v1.0").unwrap(), tag);
+    assert_eq!(&resolve(&fs, "v1.0^{}").unwrap(), second);
+    assert_eq!(&resolve(&fs, "v1.0^{commit}").unwrap(), second);
+    assert_eq!(&resolve(&fs, "v1.0^{tag}").unwrap(), tag);
+    assert_eq!(
+        resolve(&fs, "v1.0^{tree}").unwrap(),
+        Commit::read(&fs, second).unwrap().tree
+    );
+    assert_eq!(resolve(&fs, "HEAD^{tree}").unwrap(), merge_tree);
+    assert_eq!(resolve(&fs, "HEAD:").unwrap(), merge_tree);
+    assert_eq!(
+        resolve(&fs, "HEAD:src/lib.rs").unwrap(),
+        hash_object::hash(b"lib\n", "blob")
+    );
+    assert_eq!(
+        resolve(&fs, "v1.0:file.txt").unwrap(),
+        hash_object::hash(b"second\n", "blob")
+    );
+
+    assert_eq!(
+        resolve(&fs, "HEAD^{blob}").unwrap_err(),
+        format!("fatal: object {} is a commit, not a blob", merge)
+    );
+    assert_eq!(
+        resolve(&fs, "HEAD:src/main.rs").unwrap_err(),
+        "fatal: path 'src/main.rs' does not exist in 'HEAD'"
+    );
+    assert_eq!(
+        resolve(&fs, ":file.txt").unwrap_err(),
+        "fatal: path 'file.txt' does not exist (neither on disk nor in the index)"
+    );
+
+    let entry = index::IndexEntry {
+        hash: object::ObjectId::from_hex(&hash_object::hash(b"second\n", "blob")).unwrap(),
+        path: b"file.txt".to_vec(),
+        ..Default::default()
+    };
+    index::write_entries(&mut fs, vec![entry]);
+
+    assert_eq!(
+        resolve(&fs, ":file.txt").unwrap(),
+        hash_object::hash(b"second\n", "blob")
+    );
+    assert_eq!(resolve(&fs, ":0:file.txt"), resolve(&fs, ":file.txt"));
+    assert!(resolve(&fs, ":2:file.txt").is_err());
+}
@@ -1,113 +1,82 @@
 use crate::fs::{FileSystem, Fs};
-use crate::index::IndexEntry;
+use crate::index::resolve_undo::ResolveUndo;
+use crate::index::{self, IndexEntry};
+use crate::object::ObjectId;
 use crate::sub_commands::hash_object;
-use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::collections::BTreeMap;
+use std::os::unix::ffi::OsStrExt;
 use std::path::PathBuf;
-use std::str::from_utf8;
 
+/// Stages the files, which resolves the conflicts they were in, their
+/// stages being kept aside so that the conflicts can be recreated.
 pub fn execute(fs: &mut FileSystem, files: Vec<PathBuf>) -> Result<String, String> {
-    let index_path = format!("{}/.papyrus/index", fs.current_directory());
-
-    let index_file_exists = fs.path_exists(&index_path);
-
-    let mut entries: Vec<IndexEntry> = if !index_file_exists {
-        fs.create_file(&index_path);
-        vec![]
-    } else {
-        let index_content = fs
-            .get_file_contents_as_bytes(&index_path.clone().into())
-            .unwrap();
-
-        let index_entries = IndexEntry::parse_from_file(&index_content)?;
-
-        index_entries
-            .into_iter()
-            .filter(|ie| {
-                let path = from_utf8(&ie.path).unwrap();
-                !files.contains(&PathBuf::from(path))
+    let mut index = index::read(fs)?;
+
+    let (replaced, mut entries): (Vec<IndexEntry>, Vec<IndexEntry>) =
+        index.entries.iter().cloned().partition(|ie| {
+            files
+                .iter()
+                .any(|file| ie.path == file.as_os_str().as_bytes())
+        });
+
+    let mut resolved = BTreeMap::new();
+
+    for entry in replaced.iter().filter(|entry| entry.stage > 0) {
+        resolved
+            .entry(entry.path.clone())
+            .or_insert_with(|| ResolveUndo {
+                path: entry.path.clone(),
+                ..Default::default()
             })
-            .collect()
-    };
+            .stages[entry.stage as usize - 1] =
+            Some((u32::from(entry.mode), entry.hash.to_string()));
+    }
 
-    for file in files {
-        let file_str = file.to_str().unwrap();
+    index
+        .resolve_undo
+        .retain(|undo| !resolved.contains_key(&undo.path));
+    index.resolve_undo.extend(resolved.into_values());
+    index.resolve_undo.sort_by(|a, b| a.path.cmp(&b.path));
 
+    for file in files {
         if !fs.path_exists(&file) {
             return Err(format!(
                 "fatal: pathspec '{}' did not match any files",
-                file_str
+                file.display()
             ));
         }
         let contents = fs.get_file_contents_as_bytes(&file)?;
 
         let sha1 = hash_object::execute(fs, &contents, "blob".into(), true)?;
 
-        let metadata = fs.metadata(&file)?;
-        let permissions = metadata.permissions();
-
-        let mut entry = IndexEntry::default();
-
-        let ctime_bytes = metadata.ctime().to_be_bytes();
-        entry
-            .ctime_s
-            .copy_from_slice(&ctime_bytes[ctime_bytes.len() - 4..]);
-
-        entry.ctime_n.copy_from_slice(&[0, 0, 0, 0]);
-
-        let mtime_bytes = metadata.mtime().to_be_bytes();
-        entry
-            .mtime_s
-            .copy_from_slice(&mtime_bytes[mtime_bytes.len() - 4..]);
-
-        entry.mtime_n.copy_from_slice(&[0, 0, 0, 0]);
-
-        let dev_bytes = metadata.dev().to_be_bytes();
-        entry.dev.copy_from_slice(&dev_bytes[dev_bytes.len() - 4..]);
-
-        let ino_bytes = metadata.ino().to_be_bytes();
-        entry.ino.copy_from_slice(&ino_bytes[ino_bytes.len() - 4..]);
-
-        entry
-            .mode
-            .copy_from_slice(&permissions.mode().to_be_bytes());
-        entry.uid.copy_from_slice(&metadata.uid().to_be_bytes());
-        entry.gid.copy_from_slice(&metadata.gid().to_be_bytes());
-
-        let size_bytes = metadata.len().to_be_bytes();
-        entry
-            .size
-            .copy_from_slice(&size_bytes[size_bytes.len() - 4..]);
-
-        let sha1_bytes = sha1.as_bytes();
-        let mut output: Vec<u8> = vec![];
-
-        for byte_pair in sha1_bytes.chunks(2) {
-            let b = from_utf8(byte_pair).unwrap();
-            let b = u8::from_str_radix(&b, 16).unwrap();
-            output.push(b);
-        }
-
-        entry.sha1.copy_from_slice(&output);
-
-        let flags_bytes = file_str.len().to_be_bytes();
-        entry
-            .flags
-            .copy_from_slice(&flags_bytes[flags_bytes.len() - 2..]);
-
-        for p in file_str.as_bytes() {
-            entry.path.push(*p);
-        }
+        let entry = create_index_entry(fs, &file, file.as_os_str().as_bytes(), &sha1)?;
 
         entries.push(entry);
     }
 
-    entries.sort();
+    index.set_entries(entries);
+    index::write(fs, index);
 
-    let new_index_file_content = IndexEntry::parse_into_file(entries);
+    Ok("".to_string())
+}
 
-    fs.write_file(&index_path, &new_index_file_content);
+/// Builds the index entry of a file whose contents are already stored as the
+/// blob `sha1`, taking the stat data from the working tree.
+pub fn create_index_entry(
+    fs: &FileSystem,
+    file: &PathBuf,
+    path: &[u8],
+    sha1: &str,
+) -> Result<IndexEntry, String> {
+    let mut entry = IndexEntry {
+        hash: ObjectId::from_hex(sha1)?,
+        path: path.to_vec(),
+        ..Default::default()
+    };
 
-    Ok("".to_string())
+    entry.set_stat(&fs.metadata(file)?);
+
+    Ok(entry)
 }
 
 #[test]
@@ -115,7 +84,7 @@ fn execute_when_index_file_doesnt_exist() {
     use crate::sub_commands::init;
     let mut fs = FileSystem::access();
 
-    init::execute(&mut fs).unwrap();
+    init::execute(&mut fs, None, false, None).unwrap();
 
     let file1_path = format!("{}/file1.txt", fs.current_directory());
     let file1_content = "cool content";
@@ -160,7 +129,7 @@ fn execute_when_index_file_already_exists() {
     use crate::sub_commands::init;
     let mut fs = FileSystem::access();
 
-    init::execute(&mut fs).unwrap();
+    init::execute(&mut fs, None, false, None).unwrap();
 
     let file1_path = format!("{}/file1.txt", fs.current_directory());
     let file1_content = "cool content";
@@ -223,7 +192,7 @@ fn execute_when_one_of_passing_files() {
     use crate::sub_commands::init;
     let mut fs = FileSystem::access();
 
-    init::execute(&mut fs).unwrap();
+    init::execute(&mut fs, None, false, None).unwrap();
 
     let file1_path = format!("{}/file1.txt", fs.current_directory());
     let file1_content = "cool content";
@@ -238,3 +207,88 @@ fn execute_when_one_of_passing_files() {
         "fatal: pathspec '/Users/jack/cool_project/file2.txt' did not match any files"
     );
 }
+
+#[test]
+fn execute_resolving_a_conflict() {
+    use crate::sub_commands::ls_files;
+    let mut fs = FileSystem::access();
+
+    let file_path = format!("{}/file.txt", fs.current_directory());
+    fs.create_file(&file_path);
+    fs.write_file(&file_path, b"resolved\n");
+
+    let stage = |stage: u8, contents: &[u8], path: &str| IndexEntry {
+        hash: ObjectId::from_hex(&hash_object::hash(contents, "blob")).unwrap(),
+        stage,
+        path: path.as_bytes().to_vec(),
+        ..Default::default()
+    };
+
+    index::write_entries(
+        &mut fs,
+        vec![
+            stage(3, b"theirs\n", "file.txt"),
+            stage(1, b"base\n", "file.txt"),
+            stage(2, b"ours\n", "file.txt"),
+            stage(0, b"other\n", "other.txt"),
+        ],
+    );
+
+    assert_eq!(
+        ls_files::execute(&fs, false, true).unwrap(),
+        format!(
+            "100644 {} 1\tfile.txt\n100644 {} 2\tfile.txt\n100644 {} 3\tfile.txt",
+            hash_object::hash(b"base\n", "blob"),
+            hash_object::hash(b"ours\n", "blob"),
+            hash_object::hash(b"theirs\n", "blob")
+        )
+    );
+
+    execute(&mut fs, vec!["file.txt".into()]).unwrap();
+
+    let index = index::read(&fs).unwrap();
+
+    assert_eq!(ls_files::execute(&fs, false, true).unwrap(), "");
+    assert_eq!(
+        ls_files::execute(&fs, true, false).unwrap(),
+        format!(
+            "100644 {} 0\tfile.txt\n100644 {} 0\tother.txt",
+            hash_object::hash(b"resolved\n", "blob"),
+            hash_object::hash(b"other\n", "blob")
+        )
+    );
+    assert_eq!(index.resolve_undo.len(), 1);
+    assert_eq!(
+        index.resolve_undo[0].stages[2],
+        Some((0o100644, hash_object::hash(b"theirs\n", "blob")))
+    );
+}
+
+#[test]
+fn execute_with_a_path_that_isnt_utf8() {
+    let mut fs = FileSystem::access();
+
+    let hash = hash_object::execute(&mut fs, b"latin-1\n", "blob".into(), true).unwrap();
+    index::write_entries(
+        &mut fs,
+        vec![IndexEntry {
+            hash: ObjectId::from_hex(&hash).unwrap(),
+            path: b"caf\xe9.txt".to_vec(),
+            ..Default::default()
+        }],
+    );
+
+    let file_path = format!("{}/README", fs.current_directory());
+    fs.create_file(&file_path);
+    fs.write_file(&file_path, b"readme\n");
+
+    execute(&mut fs, vec![PathBuf::from("README")]).unwrap();
+
+    let paths = index::read_entries(&fs)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.path)
+        .collect::<Vec<Vec<u8>>>();
+
+    assert_eq!(paths, vec![b"README".to_vec(), b"caf\xe9.txt".to_vec()]);
+}
@@ -1,15 +1,18 @@
-use crate::fs::{FileSystem, Fs};
-use flate2::read::ZlibDecoder;
-use std::io::prelude::*;
-use std::path::PathBuf;
+use crate::fs::FileSystem;
+use crate::object;
+use crate::object::tree::Tree;
+use crate::revision;
+use std::io::{BufRead, Write};
 
-const POSSIBLE_FIRST_PARAMETER: [&str; 3] = ["-t", "blob", "tree"];
+const POSSIBLE_FIRST_PARAMETER: [&str; 7] = ["-t", "-s", "-p", "blob", "tree", "commit", "tag"];
 
+/// Returns the bytes to print as they are, since blobs don't need to be
+/// valid UTF-8.
 pub fn execute(
     fs: &FileSystem,
     file_type_or_type_flag: String,
     file_name: String,
-) -> Result<String, String> {
+) -> Result<Vec<u8>, String> {
     if !POSSIBLE_FIRST_PARAMETER
         .iter()
         .any(|p| p == &file_type_or_type_flag)
@@ -20,59 +23,91 @@ pub fn execute(
         ));
     }
 
-    let current_directory = fs.current_directory();
-    let mut full_file_path = PathBuf::from(format!(
-        "{}/.papyrus/objects/{}",
-        current_directory,
-        &file_name[..2]
-    ));
-    let matching_object_files =
-        fs.get_directory_files_starting_with(&full_file_path, &file_name[..].into());
-
-    if matching_object_files.is_empty() {
-        return Err(format!("fatal: Not a valid object name {}", file_name));
-    }
+    let object = object::read(fs, &revision::resolve(fs, &file_name)?)?;
 
-    if matching_object_files.len() > 1 {
-        return Err(format!(
-            "fatal: ambigious argument '{}', there are more than one object with same name",
-            file_name
-        ));
+    match &file_type_or_type_flag[..] {
+        "-t" => Ok(object.object_type.into_bytes()),
+        "-s" => Ok(object.data.len().to_string().into_bytes()),
+        "-p" => pretty_print(object),
+        object_type if object_type == object.object_type => Ok(object.data),
+        _ => Err(format!("fatal: papyrus cat-file {}: bad file", file_name)),
     }
+}
 
-    full_file_path.push(&matching_object_files[0]);
+/// Reads one object name per line from `input` and writes a
+/// `<hash> <type> <size>` line for each to `output`, followed by the
+/// contents and a line feed unless `check` is set. Names that can't be
+/// found are reported as missing without stopping.
+pub fn execute_batch<R: BufRead, W: Write>(
+    fs: &FileSystem,
+    input: R,
+    output: &mut W,
+    check: bool,
+) -> Result<(), String> {
+    let write_error = |err: std::io::Error| format!("fatal: unable to write to stdout: {}", err);
 
-    let compressed_object_contents = fs.get_file_contents_as_bytes(&full_file_path).unwrap();
+    for line in input.lines() {
+        let line = line.map_err(|err| format!("fatal: unable to read stdin: {}", err))?;
+        let name = line.trim();
 
-    let mut decoder = ZlibDecoder::new(&compressed_object_contents[..]);
-    let mut object_contents = String::new();
-    decoder.read_to_string(&mut object_contents).unwrap();
+        let object = match revision::resolve(fs, name).and_then(|hash| object::read(fs, &hash)) {
+            Ok(object) => object,
+            Err(_) => {
+                writeln!(output, "{} missing", name).map_err(write_error)?;
+                continue;
+            }
+        };
 
-    match &file_type_or_type_flag[..] {
-        "blob" | "tree" => Ok(get_object_data(&object_contents)),
-        "-t" => Ok(get_file_type(&object_contents)),
-        _ => unreachable!(),
-    }
-}
+        writeln!(
+            output,
+            "{} {} {}",
+            object.hash,
+            object.object_type,
+            object.data.len()
+        )
+        .map_err(write_error)?;
 
-fn get_object_data(object_contents: &str) -> String {
-    let null_index = object_contents.find('\x00').unwrap() + 1;
+        if !check {
+            output.write_all(&object.data).map_err(write_error)?;
+            output.write_all(b"\n").map_err(write_error)?;
+        }
 
-    let object_data = object_contents[null_index..].to_string();
+        // whoever is on the other end may be waiting for this object
+        // before asking for the next one
+        output.flush().map_err(write_error)?;
+    }
 
-    object_data.trim_end().to_string()
+    Ok(())
 }
 
-fn get_file_type(object_contents: &str) -> String {
-    let space_index = object_contents.find(' ').unwrap();
+/// Trees are decoded into one line per entry, the other objects are
+/// already readable.
+fn pretty_print(object: object::Object) -> Result<Vec<u8>, String> {
+    if object.object_type != "tree" {
+        return Ok(object.data);
+    }
 
-    let file_type = object_contents[..space_index].to_string();
+    let tree = Tree::parse_from_object(&object.data)?;
 
-    file_type.to_string()
+    Ok(tree
+        .entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "{:06o} {} {}\t{}\n",
+                entry.mode,
+                entry.object_type(),
+                entry.hash,
+                entry.name
+            )
+        })
+        .collect::<String>()
+        .into_bytes())
 }
 
 #[test]
 fn test_execute_existing_file_contents() {
+    use crate::fs::Fs;
     use crate::sub_commands::hash_object;
     let mut fs = FileSystem::access();
 
@@ -87,12 +122,13 @@ fn test_execute_existing_file_contents() {
             "5c7f7d83d0da2baceb3789aaf457a699455992fe".into()
         )
         .unwrap(),
-        "awesome contents yo"
+        b"awesome contents yo"
     );
 }
 
 #[test]
 fn test_execute_non_existing_file() {
+    use crate::fs::Fs;
     let fs = FileSystem::access();
 
     assert_eq!(
@@ -108,6 +144,7 @@ fn test_execute_non_existing_file() {
 
 #[test]
 fn test_execute_existing_file_starts_with() {
+    use crate::fs::Fs;
     use crate::sub_commands::hash_object;
     let mut fs = FileSystem::access();
 
@@ -117,12 +154,13 @@ fn test_execute_existing_file_starts_with() {
 
     assert_eq!(
         execute(&fs, "blob".into(), "5c7f7d".into()).unwrap(),
-        "awesome contents yo"
+        b"awesome contents yo"
     );
 }
 
 #[test]
 fn test_execute_wrong_first_parameter() {
+    use crate::fs::Fs;
     let fs = FileSystem::access();
 
     assert_eq!(
@@ -132,12 +170,13 @@ fn test_execute_wrong_first_parameter() {
             "5c7f7d83d0da2baceb3789aaf457a699455992fe".into()
         )
         .unwrap_err(),
-        "fatal: papyrus cat-file first parameter can only receive one of (-t, blob, tree)"
+        "fatal: papyrus cat-file first parameter can only receive one of (-t, -s, -p, blob, tree, commit, tag)"
     );
 }
 
 #[test]
 fn test_execute_existing_file_type() {
+    use crate::fs::Fs;
     use crate::sub_commands::hash_object;
     let mut fs = FileSystem::access();
 
@@ -152,6 +191,88 @@ fn test_execute_existing_file_type() {
             "5c7f7d83d0da2baceb3789aaf457a699455992fe".into()
         )
         .unwrap(),
-        "blob"
+        b"blob"
+    );
+}
+
+#[test]
+fn test_execute_binary_blob() {
+    use crate::fs::Fs;
+    use crate::sub_commands::hash_object;
+    let mut fs = FileSystem::access();
+
+    let contents = b"\x89PNG\r\n\x1a\n\x00\xff";
+    let hash = hash_object::execute(&mut fs, contents, "blob".into(), true).unwrap();
+
+    assert_eq!(execute(&fs, "blob".into(), hash.clone()).unwrap(), contents);
+    assert_eq!(execute(&fs, "-s".into(), hash.clone()).unwrap(), b"10");
+    assert_eq!(
+        execute(&fs, "tree".into(), hash.clone()).unwrap_err(),
+        format!("fatal: papyrus cat-file {}: bad file", hash)
+    );
+}
+
+#[test]
+fn test_execute_pretty_print() {
+    use crate::fs::Fs;
+    use crate::object::commit::test_commit;
+    use crate::sub_commands::init;
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    let tree = crate::object::tree::write_test_tree(&mut fs);
+
+    assert_eq!(
+        String::from_utf8(execute(&fs, "-p".into(), tree).unwrap()).unwrap(),
+        "100755 blob 8178c76d627cade75005b40711b92f4177bc6cfc\tREADME\n\
+         040000 tree 5d90422423db5ef6b431e8b9e60e0baf04b8742a\tsrc\n"
+    );
+
+    let commit = test_commit(&mut fs, &[("README", "readme\n", 0o100644)], vec![]);
+    crate::refs::update(&mut fs, "refs/heads/master", &commit, false).unwrap();
+
+    let pretty = execute(&fs, "-p".into(), "master".into()).unwrap();
+
+    assert_eq!(
+        execute(&fs, "commit".into(), commit.clone()).unwrap(),
+        pretty
+    );
+    assert!(pretty.starts_with(b"tree "));
+    assert_eq!(execute(&fs, "-t".into(), commit).unwrap(), b"commit");
+}
+
+#[test]
+fn test_execute_batch() {
+    use crate::fs::Fs;
+    use crate::sub_commands::hash_object;
+    let mut fs = FileSystem::access();
+
+    hash_object::execute(&mut fs, b"awesome contents yo", "blob".into(), true).unwrap();
+    hash_object::execute(&mut fs, b"\x00\x01", "blob".into(), true).unwrap();
+
+    let input = b"5c7f7d\ndeadbeef\nbdc955b7b2e610ad5a72302b139a2e6cb325519a\n";
+
+    let mut output = vec![];
+    execute_batch(&fs, &input[..], &mut output, false).unwrap();
+
+    assert_eq!(
+        output,
+        b"5c7f7d83d0da2baceb3789aaf457a699455992fe blob 19\n\
+          awesome contents yo\n\
+          deadbeef missing\n\
+          bdc955b7b2e610ad5a72302b139a2e6cb325519a blob 2\n\
+          \x00\x01\n"
+            .to_vec()
+    );
+
+    let mut output = vec![];
+    execute_batch(&fs, &input[..], &mut output, true).unwrap();
+
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "5c7f7d83d0da2baceb3789aaf457a699455992fe blob 19\n\
+         deadbeef missing\n\
+         bdc955b7b2e610ad5a72302b139a2e6cb325519a blob 2\n"
     );
 }
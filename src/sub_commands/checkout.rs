@@ -0,0 +1,658 @@
+use crate::fs::{FileSystem, Fs};
+use crate::index::{self, IndexEntry};
+use crate::object;
+use crate::object::commit::{Commit, Signature};
+use crate::object::tree::Tree;
+use crate::refs;
+use crate::revision;
+use crate::sub_commands::{add, hash_object};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// The files of a tree, as path to mode and blob hash.
+pub type Entries = BTreeMap<String, (u32, String)>;
+
+/// The entries of the index, by path and stage, so that the stages of a
+/// conflict are all kept. Paths are kept as bytes, as the index has them.
+pub type IndexEntries = BTreeMap<(Vec<u8>, u8), IndexEntry>;
+
+/// What `HEAD` should point at once the working tree has been switched.
+pub enum Head {
+    Branch(String),
+    Detached,
+}
+
+pub fn execute(
+    fs: &mut FileSystem,
+    target: Option<String>,
+    new_branch: Option<String>,
+) -> Result<String, String> {
+    let target = target
+        .map(|target| expand_previous(fs, target))
+        .transpose()?;
+
+    match (target, new_branch) {
+        (target, Some(new_branch)) => create_branch(fs, &new_branch, target),
+        (Some(target), None) => {
+            if refs::resolve(fs, &format!("refs/heads/{}", target))?.is_some() {
+                switch_branch(fs, &target)
+            } else {
+                detach(fs, &target)
+            }
+        }
+        (None, None) => Err("fatal: you must specify a branch to checkout".to_string()),
+    }
+}
+
+/// Turns `@{-<n>}` into the branch or commit it stands for, so that going
+/// back to a branch checks the branch out instead of detaching at it.
+pub fn expand_previous(fs: &FileSystem, target: String) -> Result<String, String> {
+    Ok(revision::previous_checkout(fs, &target)?.unwrap_or(target))
+}
+
+pub fn switch_branch(fs: &mut FileSystem, branch: &str) -> Result<String, String> {
+    let branch_ref = format!("refs/heads/{}", branch);
+
+    let commit = refs::resolve(fs, &branch_ref)?
+        .ok_or_else(|| format!("fatal: invalid reference: {}", branch))?;
+
+    if refs::read(fs, "HEAD")? == Some(refs::Ref::Symbolic(branch_ref.clone())) {
+        return Ok(format!("Already on '{}'", branch));
+    }
+
+    switch_to(fs, &commit, Head::Branch(branch_ref))?;
+
+    Ok(format!("Switched to branch '{}'", branch))
+}
+
+pub fn create_branch(
+    fs: &mut FileSystem,
+    branch: &str,
+    start_point: Option<String>,
+) -> Result<String, String> {
+    let branch_ref = format!("refs/heads/{}", branch);
+
+    if !refs::is_valid_name(&branch_ref) {
+        return Err(format!("fatal: '{}' is not a valid branch name.", branch));
+    }
+
+    if refs::resolve(fs, &branch_ref)?.is_some() {
+        return Err(format!("fatal: a branch named '{}' already exists", branch));
+    }
+
+    let commit = match start_point {
+        Some(start_point) => Some(resolve_commit(fs, &start_point)?),
+        None => refs::resolve(fs, "HEAD")?,
+    };
+
+    match commit {
+        Some(commit) => {
+            switch_to(fs, &commit, Head::Branch(branch_ref.clone()))?;
+            refs::update(fs, &branch_ref, &commit, true)?;
+        }
+        // on an unborn branch there is nothing to check out, the new branch
+        // will be born with the next commit
+        None => refs::update_symbolic(fs, "HEAD", &branch_ref)?,
+    }
+
+    Ok(format!("Switched to a new branch '{}'", branch))
+}
+
+pub fn detach(fs: &mut FileSystem, target: &str) -> Result<String, String> {
+    let commit = resolve_commit(fs, target)?;
+
+    switch_to(fs, &commit, Head::Detached)?;
+
+    let summary = Commit::read(fs, &commit)?
+        .message
+        .lines()
+        .next()
+        .unwrap_or("")
+        .to_string();
+
+    Ok(format!("HEAD is now at {} {}", &commit[..7], summary))
+}
+
+fn resolve_commit(fs: &FileSystem, name: &str) -> Result<String, String> {
+    let hash = revision::resolve(fs, name).map_err(|_| {
+        format!(
+            "error: pathspec '{}' did not match any file(s) known to papyrus",
+            name
+        )
+    })?;
+
+    revision::peel(fs, &hash, "commit")
+        .map_err(|_| format!("fatal: reference is not a tree: {}", name))
+}
+
+/// Lists every file of the tree of `commit` as path to mode and blob hash,
+/// nothing being listed when there is no commit yet.
+pub fn flatten_tree(fs: &FileSystem, commit: Option<&str>) -> Result<Entries, String> {
+    let tree = match commit {
+        Some(commit) => Commit::read(fs, commit)?.tree,
+        None => return Ok(BTreeMap::new()),
+    };
+
+    Ok(Tree::read_recursive(fs, &tree, "")?
+        .into_iter()
+        .map(|entry| (entry.name, (entry.mode, entry.hash)))
+        .collect())
+}
+
+pub fn working_tree_hash(fs: &FileSystem, path: &PathBuf) -> Result<Option<String>, String> {
+    if !fs.path_exists(path) {
+        return Ok(None);
+    }
+
+    let contents = fs.get_file_contents_as_bytes(path)?;
+
+    Ok(Some(hash_object::hash(&contents, "blob")))
+}
+
+/// Where the file an index entry's `path` names is in the working tree,
+/// whether or not the path is UTF-8.
+pub fn worktree_file(fs: &FileSystem, path: &[u8]) -> PathBuf {
+    PathBuf::from(fs.current_directory()).join(OsStr::from_bytes(path))
+}
+
+/// Keys the index `entries` by path and stage.
+pub fn by_path_and_stage(entries: Vec<IndexEntry>) -> IndexEntries {
+    entries
+        .into_iter()
+        .map(|entry| ((entry.path.clone(), entry.stage), entry))
+        .collect()
+}
+
+/// Replaces the working tree and index with the tree of `commit` and points
+/// `HEAD` at it. Files that are the same in both commits are left alone, so
+/// local changes to them are carried over, but nothing is done if a local
+/// change would be overwritten.
+pub fn switch_to(fs: &mut FileSystem, commit: &str, head: Head) -> Result<(), String> {
+    let current_directory = fs.current_directory();
+    let worktree_path = |path: &str| PathBuf::from(format!("{}/{}", current_directory, path));
+
+    let current_commit = refs::resolve(fs, "HEAD")?;
+    let current = flatten_tree(fs, current_commit.as_deref())?;
+    let target = flatten_tree(fs, Some(commit))?;
+
+    let index_entries = index::read_entries(fs)?;
+
+    // staying on the same commit leaves the index alone, conflicts included
+    if current_commit.as_deref() != Some(commit) {
+        check_resolved(&index_entries)?;
+    }
+
+    let mut index_entries = by_path_and_stage(index_entries);
+
+    let changed_paths = current
+        .keys()
+        .chain(target.keys())
+        .filter(|path| current.get(*path) != target.get(*path))
+        .cloned()
+        .collect::<BTreeSet<String>>();
+
+    check_overwritten(
+        fs,
+        &changed_paths,
+        (&current, &target),
+        &index_entries,
+        ("checkout", "switch branches"),
+    )?;
+
+    // removals go first, so a file can be replaced by a directory of the
+    // same name
+    for path in changed_paths
+        .iter()
+        .filter(|path| !target.contains_key(*path))
+    {
+        let file = worktree_path(path);
+
+        if fs.path_exists(&file) {
+            fs.remove_file(&file);
+        }
+
+        remove_empty_parents(fs, &file, &current_directory);
+        index_entries.remove(&(path.as_bytes().to_vec(), 0));
+    }
+
+    for path in changed_paths
+        .iter()
+        .filter(|path| target.contains_key(*path))
+    {
+        let (mode, hash) = &target[path];
+        let file = worktree_path(path);
+
+        let blob = object::read(fs, hash)?;
+        write_worktree_file(fs, &file, &blob.data, *mode);
+
+        let entry = add::create_index_entry(fs, &file, path.as_bytes(), hash)?;
+        index_entries.insert((path.as_bytes().to_vec(), 0), entry);
+    }
+
+    index::write_entries(fs, index_entries.into_values().collect());
+
+    // the reflog remembers where HEAD was, for `@{-<n>}`
+    let from = match refs::read(fs, "HEAD")? {
+        Some(refs::Ref::Symbolic(branch_ref)) => refs::shorten(&branch_ref).to_string(),
+        _ => current_commit.clone().unwrap_or_default(),
+    };
+
+    let to = match head {
+        Head::Branch(branch_ref) => {
+            refs::update_symbolic(fs, "HEAD", &branch_ref)?;
+            refs::shorten(&branch_ref).to_string()
+        }
+        Head::Detached => {
+            refs::update(fs, "HEAD", commit, true)?;
+            commit.to_string()
+        }
+    };
+
+    refs::append_log(
+        fs,
+        "HEAD",
+        (current_commit.as_deref(), commit),
+        &Signature::for_reflog(),
+        &format!("checkout: moving from {} to {}", from, to),
+    )
+}
+
+/// Fails while the index holds conflicts, which only have one entry per path
+/// once resolved, listing the paths that still need merging.
+pub fn check_resolved(entries: &[IndexEntry]) -> Result<(), String> {
+    let unmerged = entries
+        .iter()
+        .filter(|entry| entry.stage > 0)
+        .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+        .collect::<BTreeSet<String>>();
+
+    if unmerged.is_empty() {
+        return Ok(());
+    }
+
+    Err(format!(
+        "{}\nerror: you need to resolve your current index first",
+        unmerged
+            .iter()
+            .map(|path| format!("{}: needs merge", path))
+            .collect::<Vec<String>>()
+            .join("\n")
+    ))
+}
+
+/// Fails if taking the `paths` that differ from the `current` entries to the
+/// `target` ones would lose a change in the index or working tree, or an
+/// untracked file. A path missing from `target` is removed, and nothing the
+/// working tree has for it is expected. `command` and `action` name what is
+/// being done in the error.
+pub fn check_overwritten(
+    fs: &FileSystem,
+    paths: &BTreeSet<String>,
+    (current, target): (&Entries, &Entries),
+    index_entries: &IndexEntries,
+    (command, action): (&str, &str),
+) -> Result<(), String> {
+    let current_directory = fs.current_directory();
+    let worktree_path = |path: &str| PathBuf::from(format!("{}/{}", current_directory, path));
+
+    let mut local_changes = vec![];
+    let mut untracked = vec![];
+
+    for path in paths {
+        let staged = index_entries
+            .get(&(path.as_bytes().to_vec(), 0))
+            .map(|entry| (u32::from(entry.mode), entry.hash.to_string()));
+        let on_disk = working_tree_hash(fs, &worktree_path(path))?;
+
+        let current_entry = current.get(path);
+        let target_entry = target.get(path);
+        let target_hash = target_entry.map(|(_, hash)| hash);
+
+        // a change that already matches the target is not lost by switching
+        if staged.as_ref() != current_entry && staged.as_ref() != target_entry {
+            local_changes.push(path);
+        } else if on_disk.is_none() || on_disk.as_ref() == target_hash {
+            continue;
+        } else if let Some((_, staged_hash)) = &staged {
+            if on_disk.as_ref() != Some(staged_hash) {
+                local_changes.push(path);
+            }
+        } else {
+            untracked.push(path);
+        }
+    }
+
+    let format_paths = |paths: Vec<&String>| {
+        paths
+            .iter()
+            .map(|path| format!("\t{}", path))
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+
+    if !local_changes.is_empty() {
+        return Err(format!(
+            "error: Your local changes to the following files would be overwritten by {}:\n\
+             {}\n\
+             Please commit your changes or stash them before you {}.\n\
+             Aborting",
+            command,
+            format_paths(local_changes),
+            action
+        ));
+    }
+
+    if !untracked.is_empty() {
+        return Err(format!(
+            "error: The following untracked working tree files would be overwritten by {}:\n\
+             {}\n\
+             Please move or remove them before you {}.\n\
+             Aborting",
+            command,
+            format_paths(untracked),
+            action
+        ));
+    }
+
+    Ok(())
+}
+
+/// Writes `contents` to `file`, creating its directory if needed, and makes
+/// it executable when `mode` is.
+pub fn write_worktree_file(fs: &mut FileSystem, file: &Path, contents: &[u8], mode: u32) {
+    if let Some(parent) = file.parent() {
+        if !fs.path_exists(parent) {
+            fs.create_directory(&parent.to_path_buf());
+        }
+    }
+
+    let file = file.to_path_buf();
+
+    if !fs.path_exists(&file) {
+        fs.create_file(&file);
+    }
+
+    fs.write_file(&file, contents);
+    fs.set_permissions(&file, if mode & 0o111 != 0 { 0o755 } else { 0o644 });
+}
+
+/// Removes the directories `file` was in that are left empty, up to the
+/// top of the working tree.
+pub fn remove_empty_parents(fs: &mut FileSystem, file: &Path, current_directory: &str) {
+    let mut directory = file.parent();
+
+    while let Some(path) = directory {
+        if path == Path::new(current_directory) {
+            break;
+        }
+
+        if fs.path_exists(path)
+            && fs
+                .get_directory_files_recursively(&path.to_path_buf())
+                .is_empty()
+        {
+            fs.remove_directory(&path.to_path_buf());
+        }
+
+        directory = path.parent();
+    }
+}
+
+#[cfg(test)]
+fn set_up_branches(fs: &mut FileSystem) -> (String, String) {
+    use crate::object::commit::test_commit;
+    use crate::sub_commands::init;
+
+    init::execute(fs, None, false, None).unwrap();
+
+    let base = test_commit(
+        fs,
+        &[
+            ("README", "readme\n", 0o100644),
+            ("bin/run", "#!/bin/sh\n", 0o100755),
+            ("notes.txt", "notes\n", 0o100644),
+        ],
+        vec![],
+    );
+    let topic = test_commit(
+        fs,
+        &[
+            ("README", "readme\n", 0o100644),
+            ("notes.txt", "changed notes\n", 0o100644),
+            ("src/main.rs", "fn main() {}\n", 0o100644),
+        ],
+        vec![base.clone()],
+    );
+
+    refs::update(fs, "refs/heads/base", &base, false).unwrap();
+    refs::update(fs, "refs/heads/topic", &topic, false).unwrap();
+
+    (base, topic)
+}
+
+#[test]
+fn test_execute_switches_branches() {
+    use crate::sub_commands::ls_files;
+    use std::os::unix::fs::PermissionsExt;
+    let mut fs = FileSystem::access();
+
+    set_up_branches(&mut fs);
+    let path = |name: &str| PathBuf::from(format!("{}/{}", fs.current_directory(), name));
+    let (readme, run, notes, main) = (
+        path("README"),
+        path("bin/run"),
+        path("notes.txt"),
+        path("src/main.rs"),
+    );
+
+    assert_eq!(
+        execute(&mut fs, Some("base".to_string()), None).unwrap(),
+        "Switched to branch 'base'"
+    );
+    assert_eq!(fs.get_file_contents_as_bytes(&run).unwrap(), b"#!/bin/sh\n");
+    assert_eq!(fs.metadata(&run).unwrap().permissions().mode(), 0o100755);
+    assert_eq!(
+        ls_files::execute(&fs, false, false).unwrap(),
+        "README\nbin/run\nnotes.txt"
+    );
+
+    assert_eq!(
+        execute(&mut fs, Some("topic".to_string()), None).unwrap(),
+        "Switched to branch 'topic'"
+    );
+    assert!(fs.path_exists(&readme));
+    assert!(!fs.path_exists(&run));
+    assert_eq!(
+        fs.get_file_contents_as_bytes(&notes).unwrap(),
+        b"changed notes\n"
+    );
+    assert_eq!(
+        fs.get_file_contents_as_bytes(&main).unwrap(),
+        b"fn main() {}\n"
+    );
+    assert_eq!(
+        ls_files::execute(&fs, false, false).unwrap(),
+        "README\nnotes.txt\nsrc/main.rs"
+    );
+    assert_eq!(
+        refs::read(&fs, "HEAD").unwrap(),
+        Some(refs::Ref::Symbolic("refs/heads/topic".to_string()))
+    );
+
+    assert_eq!(
+        execute(&mut fs, Some("topic".to_string()), None).unwrap(),
+        "Already on 'topic'"
+    );
+}
+
+#[test]
+fn test_execute_detached_head() {
+    let mut fs = FileSystem::access();
+
+    let (base, _) = set_up_branches(&mut fs);
+
+    assert_eq!(
+        execute(&mut fs, Some(base[..8].to_string()), None).unwrap(),
+        format!("HEAD is now at {} test commit", &base[..7])
+    );
+    assert_eq!(
+        refs::read(&fs, "HEAD").unwrap(),
+        Some(refs::Ref::Direct(base))
+    );
+}
+
+#[test]
+fn test_execute_previous_checkout() {
+    let mut fs = FileSystem::access();
+
+    let (base, topic) = set_up_branches(&mut fs);
+
+    execute(&mut fs, Some("base".to_string()), None).unwrap();
+    execute(&mut fs, Some("topic".to_string()), None).unwrap();
+
+    assert_eq!(revision::resolve(&fs, "@{-1}").unwrap(), base);
+    assert_eq!(
+        execute(&mut fs, Some("@{-1}".to_string()), None).unwrap(),
+        "Switched to branch 'base'"
+    );
+
+    execute(&mut fs, Some(topic[..8].to_string()), None).unwrap();
+
+    // a detached HEAD is gone back to by its commit
+    assert_eq!(
+        execute(&mut fs, Some("@{-2}".to_string()), None).unwrap(),
+        "Switched to branch 'topic'"
+    );
+    assert_eq!(revision::resolve(&fs, "@{-1}").unwrap(), topic);
+    assert_eq!(
+        execute(&mut fs, Some("@{-1}".to_string()), None).unwrap(),
+        format!("HEAD is now at {} test commit", &topic[..7])
+    );
+}
+
+#[test]
+fn test_execute_with_conflicts() {
+    use crate::object::ObjectId;
+    let mut fs = FileSystem::access();
+
+    let (base, topic) = set_up_branches(&mut fs);
+    execute(&mut fs, Some("base".to_string()), None).unwrap();
+
+    let mut entries = index::read_entries(&fs).unwrap();
+    entries.retain(|entry| entry.path != b"notes.txt");
+
+    for stage in 1..=3 {
+        entries.push(IndexEntry {
+            hash: ObjectId::from_hex(&base).unwrap(),
+            stage,
+            path: b"notes.txt".to_vec(),
+            ..Default::default()
+        });
+    }
+
+    index::write_entries(&mut fs, entries.clone());
+
+    assert_eq!(
+        execute(&mut fs, Some("topic".to_string()), None).unwrap_err(),
+        "notes.txt: needs merge\n\
+         error: you need to resolve your current index first"
+    );
+    assert_eq!(index::read_entries(&fs).unwrap(), entries);
+
+    // a new branch on the same commit keeps the conflicts
+    execute(&mut fs, None, Some("fix".to_string())).unwrap();
+    assert_eq!(index::read_entries(&fs).unwrap(), entries);
+    assert!(detach(&mut fs, &topic).is_err());
+}
+
+#[test]
+fn test_execute_new_branch() {
+    let mut fs = FileSystem::access();
+
+    let (base, _) = set_up_branches(&mut fs);
+
+    assert_eq!(
+        execute(
+            &mut fs,
+            Some("base".to_string()),
+            Some("feature".to_string())
+        )
+        .unwrap(),
+        "Switched to a new branch 'feature'"
+    );
+    assert_eq!(
+        refs::resolve_name(&fs, "HEAD").unwrap(),
+        ("refs/heads/feature".to_string(), Some(base))
+    );
+    assert_eq!(
+        execute(&mut fs, None, Some("feature".to_string())).unwrap_err(),
+        "fatal: a branch named 'feature' already exists"
+    );
+}
+
+#[test]
+fn test_execute_refuses_to_overwrite_local_changes() {
+    let mut fs = FileSystem::access();
+
+    set_up_branches(&mut fs);
+    execute(&mut fs, Some("base".to_string()), None).unwrap();
+
+    let notes = PathBuf::from(format!("{}/notes.txt", fs.current_directory()));
+    fs.write_file(&notes, b"local notes\n");
+
+    assert_eq!(
+        execute(&mut fs, Some("topic".to_string()), None).unwrap_err(),
+        "error: Your local changes to the following files would be overwritten by checkout:\n\
+         \tnotes.txt\n\
+         Please commit your changes or stash them before you switch branches.\n\
+         Aborting"
+    );
+    assert_eq!(
+        refs::read(&fs, "HEAD").unwrap(),
+        Some(refs::Ref::Symbolic("refs/heads/base".to_string()))
+    );
+    assert_eq!(
+        fs.get_file_contents_as_bytes(&notes).unwrap(),
+        b"local notes\n"
+    );
+}
+
+#[test]
+fn test_execute_carries_unrelated_local_changes() {
+    let mut fs = FileSystem::access();
+
+    set_up_branches(&mut fs);
+    execute(&mut fs, Some("base".to_string()), None).unwrap();
+
+    let readme = PathBuf::from(format!("{}/README", fs.current_directory()));
+    fs.write_file(&readme, b"local readme\n");
+
+    execute(&mut fs, Some("topic".to_string()), None).unwrap();
+
+    assert_eq!(
+        fs.get_file_contents_as_bytes(&readme).unwrap(),
+        b"local readme\n"
+    );
+}
+
+#[test]
+fn test_execute_refuses_to_overwrite_untracked_files() {
+    let mut fs = FileSystem::access();
+
+    set_up_branches(&mut fs);
+    execute(&mut fs, Some("base".to_string()), None).unwrap();
+
+    let main = PathBuf::from(format!("{}/src/main.rs", fs.current_directory()));
+    fs.create_file(&main);
+    fs.write_file(&main, b"fn main() { todo!() }\n");
+
+    assert_eq!(
+        execute(&mut fs, Some("topic".to_string()), None).unwrap_err(),
+        "error: The following untracked working tree files would be overwritten by checkout:\n\
+         \tsrc/main.rs\n\
+         Please move or remove them before you switch branches.\n\
+         Aborting"
+    );
+}
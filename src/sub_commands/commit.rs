@@ -0,0 +1,238 @@
+use crate::fs::{FileSystem, Fs};
+use crate::index;
+use crate::object::commit::{Commit, Signature};
+use crate::refs;
+use crate::repository;
+use crate::sub_commands::{commit_tree, write_tree};
+
+const EMPTY_TREE_HASH: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+pub fn execute(
+    fs: &mut FileSystem,
+    messages: Vec<String>,
+    author: Signature,
+    committer: Signature,
+) -> Result<String, String> {
+    let (head_ref, parent) = refs::resolve_name(fs, "HEAD")?;
+
+    let branch = if head_ref == "HEAD" {
+        "detached HEAD"
+    } else {
+        refs::shorten(&head_ref)
+    };
+
+    if index::read_entries(fs)?.iter().any(|entry| entry.stage > 0) {
+        return Err([
+            "error: Committing is not possible because you have unmerged files.",
+            "hint: Fix them up in the work tree, and then use 'papyrus add <file>'",
+            "hint: as appropriate to mark resolution and make a commit.",
+            "fatal: Exiting because of an unresolved conflict.",
+        ]
+        .join("\n"));
+    }
+
+    // concluding a merge makes a commit with the merged one as second parent
+    let merge_head = refs::resolve(fs, "MERGE_HEAD")?;
+    let merge_message_path = repository::path(fs).join("MERGE_MSG");
+
+    let messages = match (messages.is_empty(), &merge_head) {
+        (false, _) => messages,
+        (true, Some(_)) if fs.path_exists(&merge_message_path) => {
+            let message = fs
+                .get_file_contents(&merge_message_path)?
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .collect::<Vec<&str>>()
+                .join("\n");
+            vec![message.trim().to_string()]
+        }
+        _ => return Err("Aborting commit due to empty commit message.".to_string()),
+    };
+
+    let tree = write_tree::execute(fs)?;
+
+    let parent_tree = match &parent {
+        Some(parent) => Commit::read(fs, parent)?.tree,
+        None => EMPTY_TREE_HASH.to_string(),
+    };
+
+    if tree == parent_tree && merge_head.is_none() {
+        return Err(format!("On branch {}\nnothing to commit", branch));
+    }
+
+    let summary = messages
+        .first()
+        .and_then(|message| message.lines().next())
+        .unwrap_or("")
+        .to_string();
+
+    let parents = parent
+        .iter()
+        .chain(&merge_head)
+        .cloned()
+        .collect::<Vec<String>>();
+    let hash = commit_tree::execute(fs, tree, parents, messages, author, committer)?;
+
+    refs::update(fs, "HEAD", &hash, false)?;
+
+    if merge_head.is_some() {
+        refs::delete(fs, "MERGE_HEAD")?;
+
+        if fs.path_exists(&merge_message_path) {
+            fs.remove_file(&merge_message_path);
+        }
+    }
+
+    let root_commit = if parent.is_none() {
+        " (root-commit)"
+    } else {
+        ""
+    };
+
+    Ok(format!(
+        "[{}{} {}] {}",
+        branch,
+        root_commit,
+        &hash[..7],
+        summary
+    ))
+}
+
+#[test]
+fn test_execute_creates_history() {
+    use crate::fs::Fs;
+    use crate::object::commit::test_signature;
+    use crate::sub_commands::{add, init};
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    let file_path = format!("{}/file1.txt", fs.current_directory());
+    fs.create_file(&file_path);
+    fs.write_file(&file_path, b"cool content");
+    add::execute(&mut fs, vec![file_path.clone().into()]).unwrap();
+
+    let output = execute(
+        &mut fs,
+        vec!["first commit".to_string()],
+        test_signature(),
+        test_signature(),
+    )
+    .unwrap();
+
+    let first = refs::resolve(&fs, "refs/heads/master").unwrap().unwrap();
+    assert_eq!(
+        output,
+        format!("[master (root-commit) {}] first commit", &first[..7])
+    );
+
+    fs.write_file(&file_path, b"moar content");
+    add::execute(&mut fs, vec![file_path.into()]).unwrap();
+
+    let output = execute(
+        &mut fs,
+        vec!["second commit".to_string()],
+        test_signature(),
+        test_signature(),
+    )
+    .unwrap();
+
+    let second = refs::resolve(&fs, "HEAD").unwrap().unwrap();
+    assert_eq!(output, format!("[master {}] second commit", &second[..7]));
+
+    let commit = Commit::read(&fs, &second).unwrap();
+    assert_eq!(commit.parents, vec![first]);
+    assert_eq!(commit.message, "second commit\n");
+}
+
+#[test]
+fn test_execute_nothing_to_commit() {
+    use crate::fs::Fs;
+    use crate::object::commit::test_signature;
+    use crate::sub_commands::{add, init};
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    assert_eq!(
+        execute(
+            &mut fs,
+            vec!["empty".to_string()],
+            test_signature(),
+            test_signature(),
+        )
+        .unwrap_err(),
+        "On branch master\nnothing to commit"
+    );
+
+    let file_path = format!("{}/file1.txt", fs.current_directory());
+    fs.create_file(&file_path);
+    fs.write_file(&file_path, b"cool content");
+    add::execute(&mut fs, vec![file_path.into()]).unwrap();
+
+    execute(
+        &mut fs,
+        vec!["first commit".to_string()],
+        test_signature(),
+        test_signature(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        execute(
+            &mut fs,
+            vec!["again".to_string()],
+            test_signature(),
+            test_signature(),
+        )
+        .unwrap_err(),
+        "On branch master\nnothing to commit"
+    );
+}
+
+#[test]
+fn test_execute_detached_head() {
+    use crate::fs::Fs;
+    use crate::object::commit::test_signature;
+    use crate::sub_commands::{add, init};
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    let file_path = format!("{}/file1.txt", fs.current_directory());
+    fs.create_file(&file_path);
+    fs.write_file(&file_path, b"cool content");
+    add::execute(&mut fs, vec![file_path.clone().into()]).unwrap();
+
+    execute(
+        &mut fs,
+        vec!["first commit".to_string()],
+        test_signature(),
+        test_signature(),
+    )
+    .unwrap();
+
+    let first = refs::resolve(&fs, "HEAD").unwrap().unwrap();
+    refs::update(&mut fs, "HEAD", &first, true).unwrap();
+
+    fs.write_file(&file_path, b"moar content");
+    add::execute(&mut fs, vec![file_path.into()]).unwrap();
+
+    let output = execute(
+        &mut fs,
+        vec!["detached commit".to_string()],
+        test_signature(),
+        test_signature(),
+    )
+    .unwrap();
+
+    let second = refs::resolve(&fs, "HEAD").unwrap().unwrap();
+    assert_eq!(
+        output,
+        format!("[detached HEAD {}] detached commit", &second[..7])
+    );
+    assert_eq!(
+        refs::resolve(&fs, "refs/heads/master").unwrap(),
+        Some(first)
+    );
+}
@@ -0,0 +1,148 @@
+use crate::fs::FileSystem;
+use crate::object::{self, commit::Commit, commit::Signature};
+use crate::revision;
+use crate::sub_commands::hash_object;
+
+pub fn execute(
+    fs: &mut FileSystem,
+    tree: String,
+    parents: Vec<String>,
+    messages: Vec<String>,
+    author: Signature,
+    committer: Signature,
+) -> Result<String, String> {
+    let tree_object = object::read(fs, &revision::resolve(fs, &tree)?)?;
+
+    if tree_object.object_type != "tree" {
+        return Err(format!("fatal: {} is not a valid 'tree' object", tree));
+    }
+
+    let mut parent_hashes: Vec<String> = vec![];
+
+    for parent in parents {
+        let parent_object = object::read(fs, &revision::resolve(fs, &parent)?)?;
+
+        if parent_object.object_type != "commit" {
+            return Err(format!("fatal: {} is not a valid 'commit' object", parent));
+        }
+
+        // a parent given twice is only recorded once, as git does
+        if !parent_hashes.contains(&parent_object.hash) {
+            parent_hashes.push(parent_object.hash);
+        }
+    }
+
+    let mut message = messages.join("\n\n");
+    if !message.ends_with('\n') {
+        message.push('\n');
+    }
+
+    let commit = Commit {
+        tree: tree_object.hash,
+        parents: parent_hashes,
+        author,
+        committer,
+        message,
+    };
+
+    hash_object::execute(fs, &commit.parse_into_object(), "commit".into(), true)
+}
+
+#[test]
+fn test_execute_root_commit() {
+    use crate::fs::Fs;
+    use crate::object::commit::test_signature;
+    let mut fs = FileSystem::access();
+
+    let tree = hash_object::execute(&mut fs, b"", "tree".into(), true).unwrap();
+
+    assert_eq!(
+        execute(
+            &mut fs,
+            tree[..7].to_string(),
+            vec![],
+            vec!["first commit".to_string()],
+            test_signature(),
+            test_signature(),
+        )
+        .unwrap(),
+        "3a57a4f328d9448ce1e3ee4913712c63782dcaf2"
+    );
+
+    let commit = object::read(&fs, "3a57a4f328d9448ce1e3ee4913712c63782dcaf2").unwrap();
+
+    assert_eq!(commit.object_type, "commit");
+    assert_eq!(
+        String::from_utf8(commit.data).unwrap(),
+        "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+         author Jack <jack@example.com> 1591510158 -0300\n\
+         committer Jack <jack@example.com> 1591510158 -0300\n\
+         \n\
+         first commit\n"
+    );
+}
+
+#[test]
+fn test_execute_with_parents() {
+    use crate::fs::Fs;
+    use crate::object::commit::test_signature;
+    let mut fs = FileSystem::access();
+
+    let tree = hash_object::execute(&mut fs, b"", "tree".into(), true).unwrap();
+    let parent = execute(
+        &mut fs,
+        tree.clone(),
+        vec![],
+        vec!["first commit".to_string()],
+        test_signature(),
+        test_signature(),
+    )
+    .unwrap();
+
+    let child = execute(
+        &mut fs,
+        tree,
+        vec![parent.clone(), parent.clone()],
+        vec!["second commit".to_string(), "with a body".to_string()],
+        test_signature(),
+        test_signature(),
+    )
+    .unwrap();
+
+    let commit = object::read(&fs, &child).unwrap();
+
+    assert_eq!(
+        String::from_utf8(commit.data).unwrap(),
+        format!(
+            "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+             parent {}\n\
+             author Jack <jack@example.com> 1591510158 -0300\n\
+             committer Jack <jack@example.com> 1591510158 -0300\n\
+             \n\
+             second commit\n\nwith a body\n",
+            parent
+        )
+    );
+}
+
+#[test]
+fn test_execute_tree_is_not_a_tree() {
+    use crate::fs::Fs;
+    use crate::object::commit::test_signature;
+    let mut fs = FileSystem::access();
+
+    let blob = hash_object::execute(&mut fs, b"cool content", "blob".into(), true).unwrap();
+
+    assert_eq!(
+        execute(
+            &mut fs,
+            blob.clone(),
+            vec![],
+            vec!["first commit".to_string()],
+            test_signature(),
+            test_signature(),
+        )
+        .unwrap_err(),
+        format!("fatal: {} is not a valid 'tree' object", blob)
+    );
+}
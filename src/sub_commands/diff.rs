@@ -0,0 +1,335 @@
+use crate::diff::{self, Algorithm};
+use crate::fs::{FileSystem, Fs};
+use crate::index;
+use crate::object;
+use crate::object::commit::Commit;
+use crate::refs;
+use crate::revision;
+use crate::sub_commands::checkout;
+use std::collections::{BTreeMap, BTreeSet};
+use std::os::unix::fs::PermissionsExt;
+
+const NULL_HASH: &str = "0000000000000000000000000000000000000000";
+
+/// Git only looks this far into a file when deciding whether it is binary.
+const BINARY_CHECK_LENGTH: usize = 8000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DiffFile {
+    mode: u32,
+    hash: String,
+}
+
+/// Where the contents of the files of a snapshot are read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Objects,
+    WorkingTree,
+}
+
+struct Snapshot {
+    source: Source,
+    /// The files by path, kept as bytes as the index has them.
+    files: BTreeMap<Vec<u8>, DiffFile>,
+}
+
+pub fn execute(
+    fs: &FileSystem,
+    commits: Vec<String>,
+    cached: bool,
+    context: usize,
+    algorithm: Algorithm,
+) -> Result<String, String> {
+    let (old, new) = match (&commits[..], cached) {
+        ([], false) => (index_snapshot(fs)?, working_tree_snapshot(fs)?),
+        ([], true) => (commit_snapshot(fs, "HEAD")?, index_snapshot(fs)?),
+        ([commit], false) => (commit_snapshot(fs, commit)?, working_tree_snapshot(fs)?),
+        ([commit], true) => (commit_snapshot(fs, commit)?, index_snapshot(fs)?),
+        ([old, new], false) => (commit_snapshot(fs, old)?, commit_snapshot(fs, new)?),
+        _ => return Err("usage: papyrus diff [--cached] [<commit> [<commit>]]".to_string()),
+    };
+
+    let paths = old
+        .files
+        .keys()
+        .chain(new.files.keys())
+        .filter(|path| old.files.get(*path) != new.files.get(*path))
+        .collect::<BTreeSet<&Vec<u8>>>();
+
+    let mut output = vec![];
+
+    for path in paths {
+        output.append(&mut diff_file(
+            fs,
+            path,
+            (&old, old.files.get(path)),
+            (&new, new.files.get(path)),
+            context,
+            algorithm,
+        )?);
+    }
+
+    Ok(output.join("\n"))
+}
+
+fn commit_snapshot(fs: &FileSystem, name: &str) -> Result<Snapshot, String> {
+    // an unborn HEAD is compared as an empty tree
+    let commit = match name {
+        "HEAD" => refs::resolve(fs, name)?,
+        _ => Some(revision::peel(fs, &revision::resolve(fs, name)?, "commit")?),
+    };
+
+    if let Some(commit) = &commit {
+        Commit::read(fs, commit)?;
+    }
+
+    let files = checkout::flatten_tree(fs, commit.as_deref())?
+        .into_iter()
+        .map(|(path, (mode, hash))| (path.into_bytes(), DiffFile { mode, hash }))
+        .collect();
+
+    Ok(Snapshot {
+        source: Source::Objects,
+        files,
+    })
+}
+
+fn index_snapshot(fs: &FileSystem) -> Result<Snapshot, String> {
+    let files = index::read_entries(fs)?
+        .into_iter()
+        .map(|entry| {
+            (
+                entry.path,
+                DiffFile {
+                    mode: u32::from(entry.mode),
+                    hash: entry.hash.to_string(),
+                },
+            )
+        })
+        .collect();
+
+    Ok(Snapshot {
+        source: Source::Objects,
+        files,
+    })
+}
+
+/// The tracked files as they are in the working tree, files that aren't in
+/// the index being left out.
+fn working_tree_snapshot(fs: &FileSystem) -> Result<Snapshot, String> {
+    let mut files = BTreeMap::new();
+
+    for entry in index::read_entries(fs)? {
+        let file = checkout::worktree_file(fs, &entry.path);
+
+        if let Some(hash) = checkout::working_tree_hash(fs, &file)? {
+            let mode = index::normalize_mode(fs.metadata(&file)?.permissions().mode());
+
+            files.insert(entry.path, DiffFile { mode, hash });
+        }
+    }
+
+    Ok(Snapshot {
+        source: Source::WorkingTree,
+        files,
+    })
+}
+
+fn read_contents(
+    fs: &FileSystem,
+    snapshot: &Snapshot,
+    path: &[u8],
+    file: Option<&DiffFile>,
+) -> Result<Vec<u8>, String> {
+    match (file, snapshot.source) {
+        (None, _) => Ok(vec![]),
+        (Some(file), Source::Objects) => Ok(object::read(fs, &file.hash)?.data),
+        (Some(_), Source::WorkingTree) => {
+            fs.get_file_contents_as_bytes(&checkout::worktree_file(fs, path))
+        }
+    }
+}
+
+fn is_binary(contents: &[u8]) -> bool {
+    contents
+        .iter()
+        .take(BINARY_CHECK_LENGTH)
+        .any(|byte| *byte == b'\x00')
+}
+
+fn diff_file(
+    fs: &FileSystem,
+    path: &[u8],
+    (old_snapshot, old): (&Snapshot, Option<&DiffFile>),
+    (new_snapshot, new): (&Snapshot, Option<&DiffFile>),
+    context: usize,
+    algorithm: Algorithm,
+) -> Result<Vec<String>, String> {
+    let name = String::from_utf8_lossy(path);
+    let mut output = vec![format!("diff --git a/{} b/{}", name, name)];
+
+    let old_hash = old.map(|file| &file.hash[..]).unwrap_or(NULL_HASH);
+    let new_hash = new.map(|file| &file.hash[..]).unwrap_or(NULL_HASH);
+
+    match (old, new) {
+        (None, Some(new)) => output.push(format!("new file mode {:06o}", new.mode)),
+        (Some(old), None) => output.push(format!("deleted file mode {:06o}", old.mode)),
+        (Some(old), Some(new)) if old.mode != new.mode => {
+            output.push(format!("old mode {:06o}", old.mode));
+            output.push(format!("new mode {:06o}", new.mode));
+        }
+        _ => {}
+    }
+
+    if old_hash == new_hash {
+        return Ok(output);
+    }
+
+    match (old, new) {
+        (Some(old), Some(new)) if old.mode == new.mode => output.push(format!(
+            "index {}..{} {:06o}",
+            &old_hash[..7],
+            &new_hash[..7],
+            new.mode
+        )),
+        _ => output.push(format!("index {}..{}", &old_hash[..7], &new_hash[..7])),
+    }
+
+    let old_name = old
+        .map(|_| format!("a/{}", name))
+        .unwrap_or_else(|| "/dev/null".to_string());
+    let new_name = new
+        .map(|_| format!("b/{}", name))
+        .unwrap_or_else(|| "/dev/null".to_string());
+
+    let old_contents = read_contents(fs, old_snapshot, path, old)?;
+    let new_contents = read_contents(fs, new_snapshot, path, new)?;
+
+    if is_binary(&old_contents) || is_binary(&new_contents) {
+        output.push(format!("Binary files {} and {} differ", old_name, new_name));
+        return Ok(output);
+    }
+
+    output.push(format!("--- {}", old_name));
+    output.push(format!("+++ {}", new_name));
+
+    output.append(&mut diff::unified_diff(
+        &old_contents,
+        &new_contents,
+        context,
+        algorithm,
+    ));
+
+    Ok(output)
+}
+
+#[test]
+fn test_execute_working_tree_and_cached() {
+    use crate::object::commit::test_commit;
+    use crate::sub_commands::{add, hash_object, init};
+    use std::path::PathBuf;
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    let commit = test_commit(
+        &mut fs,
+        &[
+            ("README", "readme\n", 0o100644),
+            ("run.sh", "#!/bin/sh\n", 0o100644),
+        ],
+        vec![],
+    );
+    refs::update(&mut fs, "refs/heads/base", &commit, false).unwrap();
+    checkout::execute(&mut fs, Some("base".to_string()), None).unwrap();
+
+    assert_eq!(
+        execute(&fs, vec![], false, 3, Algorithm::Myers).unwrap(),
+        ""
+    );
+
+    let readme = PathBuf::from(format!("{}/README", fs.current_directory()));
+    fs.write_file(&readme, b"readme\nmore\n");
+
+    let run = PathBuf::from(format!("{}/run.sh", fs.current_directory()));
+    fs.set_permissions(&run, 0o755);
+
+    assert_eq!(
+        execute(&fs, vec![], false, 3, Algorithm::Myers).unwrap(),
+        "diff --git a/README b/README\n\
+         index 8178c76..bceaad7 100644\n\
+         --- a/README\n\
+         +++ b/README\n\
+         @@ -1 +1,2 @@\n \
+         readme\n\
+         +more\n\
+         diff --git a/run.sh b/run.sh\n\
+         old mode 100644\n\
+         new mode 100755"
+    );
+    assert_eq!(execute(&fs, vec![], true, 3, Algorithm::Myers).unwrap(), "");
+
+    let binary = PathBuf::from(format!("{}/logo.png", fs.current_directory()));
+    fs.create_file(&binary);
+    fs.write_file(&binary, b"\x89PNG\x00\x01");
+    let sha1 = hash_object::execute(&mut fs, b"\x89PNG\x00\x01", "blob".into(), true).unwrap();
+    let mut entries = index::read_entries(&fs).unwrap();
+    entries.push(add::create_index_entry(&fs, &binary, b"logo.png", &sha1).unwrap());
+    index::write_entries(&mut fs, entries);
+
+    assert_eq!(
+        execute(&fs, vec![], true, 3, Algorithm::Myers).unwrap(),
+        "diff --git a/logo.png b/logo.png\n\
+         new file mode 100644\n\
+         index 0000000..f584f40\n\
+         Binary files /dev/null and b/logo.png differ"
+    );
+}
+
+#[test]
+fn test_execute_between_commits() {
+    use crate::object::commit::test_commit;
+    use crate::sub_commands::init;
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    let old = test_commit(
+        &mut fs,
+        &[
+            ("README", "readme\n", 0o100644),
+            ("notes.txt", "notes\n", 0o100644),
+        ],
+        vec![],
+    );
+    let new = test_commit(&mut fs, &[("README", "readme\n", 0o100644)], vec![]);
+
+    assert_eq!(
+        execute(
+            &fs,
+            vec![old.clone(), new.clone()],
+            false,
+            3,
+            Algorithm::Myers
+        )
+        .unwrap(),
+        "diff --git a/notes.txt b/notes.txt\n\
+         deleted file mode 100644\n\
+         index bfa6551..0000000\n\
+         --- a/notes.txt\n\
+         +++ /dev/null\n\
+         @@ -1 +0,0 @@\n\
+         -notes"
+    );
+    assert_eq!(
+        execute(
+            &fs,
+            vec![old, new, "HEAD".to_string()],
+            false,
+            3,
+            Algorithm::Myers
+        )
+        .unwrap_err(),
+        "usage: papyrus diff [--cached] [<commit> [<commit>]]"
+    );
+}
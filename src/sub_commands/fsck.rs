@@ -0,0 +1,286 @@
+use crate::fs::{FileSystem, Fs};
+use crate::index;
+use crate::object::commit::Commit;
+use crate::object::tree::{Tree, SUBMODULE_MODE};
+use crate::object::{self, pack, Object};
+use crate::refs;
+use crate::repository;
+use crate::sub_commands::hash_object;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// The valid objects, as their type and the objects they point at.
+type Checked = HashMap<String, (String, Vec<(String, String)>)>;
+
+/// Checks the syntax of an object, returning the objects it points at as
+/// their expected type and hash.
+fn links(object: &Object) -> Result<Vec<(String, String)>, String> {
+    match &object.object_type[..] {
+        "blob" => Ok(vec![]),
+        "commit" => {
+            let commit = Commit::parse_from_object(&object.data)?;
+
+            Ok(std::iter::once(("tree".to_string(), commit.tree))
+                .chain(
+                    commit
+                        .parents
+                        .into_iter()
+                        .map(|parent| ("commit".to_string(), parent)),
+                )
+                .collect())
+        }
+        "tree" => Ok(Tree::parse_from_object(&object.data)?
+            .entries
+            .into_iter()
+            .filter(|entry| entry.mode != SUBMODULE_MODE)
+            .map(|entry| (entry.object_type().to_string(), entry.hash))
+            .collect()),
+        "tag" => {
+            let contents = String::from_utf8_lossy(&object.data);
+            let mut lines = contents.lines();
+
+            let target = lines.next().and_then(|line| line.strip_prefix("object "));
+            let target_type = lines.next().and_then(|line| line.strip_prefix("type "));
+            let name = lines.next().and_then(|line| line.strip_prefix("tag "));
+
+            match (target, target_type, name) {
+                (Some(target), Some(target_type), Some(_)) => {
+                    Ok(vec![(target_type.to_string(), target.to_string())])
+                }
+                _ => Err("fatal: corrupt tag object".to_string()),
+            }
+        }
+        object_type => Err(format!("fatal: unknown object type {}", object_type)),
+    }
+}
+
+/// Checks that an object hashes to its name and is well formed, keeping it
+/// in `objects` along with its links if so.
+fn check(object: Object, location: &str, objects: &mut Checked, errors: &mut Vec<String>) {
+    let actual = hash_object::hash(&object.data, &object.object_type);
+
+    if actual != object.hash {
+        errors.push(format!(
+            "error: hash mismatch for {} (expected {})",
+            location, object.hash
+        ));
+        return;
+    }
+
+    match links(&object) {
+        Ok(links) => {
+            objects.insert(object.hash, (object.object_type, links));
+        }
+        Err(err) => errors.push(format!(
+            "error: in {} {}: {}",
+            object.object_type,
+            object.hash,
+            err.trim_start_matches("fatal: ")
+        )),
+    }
+}
+
+/// Verifies every object, loose or packed, the packs and the index, then
+/// reports the objects that are pointed at but missing and the ones that
+/// nothing points at, failing if anything is broken.
+pub fn execute(fs: &FileSystem) -> Result<String, String> {
+    let mut objects = Checked::new();
+    let mut errors = vec![];
+
+    let papyrus_path = format!("{}/", repository::path(fs).display());
+
+    for (hash, path) in object::list_loose(fs) {
+        let location = path.to_str().unwrap().trim_start_matches(&papyrus_path);
+
+        match object::decode(&hash, &fs.get_file_contents_as_bytes(&path)?) {
+            Ok(object) => check(object, location, &mut objects, &mut errors),
+            Err(_) => errors.push(format!(
+                "error: {}: object corrupt or missing: {}",
+                hash, location
+            )),
+        }
+    }
+
+    for path in pack::list_indexes(fs) {
+        let location = path.to_str().unwrap().trim_start_matches(&papyrus_path);
+
+        if let Err(err) = pack::verify(fs, &path) {
+            errors.push(err.replacen("fatal: ", "error: ", 1));
+            continue;
+        }
+
+        match pack::read_all(fs, &path) {
+            Ok(packed) => {
+                for object in packed {
+                    check(object, location, &mut objects, &mut errors);
+                }
+            }
+            Err(err) => errors.push(format!(
+                "error: {}: {}",
+                location,
+                err.trim_start_matches("fatal: ")
+            )),
+        }
+    }
+
+    let mut roots = vec![];
+
+    for (name, hash) in refs::list(fs)? {
+        roots.push((name, hash));
+    }
+
+    if let Some(hash) = refs::resolve(fs, "HEAD")? {
+        roots.push(("HEAD".to_string(), hash));
+    }
+
+    let mut pending = vec![];
+
+    for (name, hash) in roots {
+        if objects.contains_key(&hash) {
+            pending.push(("commit".to_string(), hash));
+        } else {
+            errors.push(format!("error: {}: invalid sha1 pointer {}", name, hash));
+        }
+    }
+
+    match index::read_entries(fs) {
+        Ok(entries) => pending.extend(
+            entries
+                .iter()
+                .map(|entry| ("blob".to_string(), entry.hash.to_string())),
+        ),
+        Err(err) => errors.push(err),
+    }
+
+    let mut missing = BTreeSet::new();
+    let mut reachable = HashSet::new();
+
+    while let Some((object_type, hash)) = pending.pop() {
+        if !reachable.insert(hash.clone()) {
+            continue;
+        }
+
+        match objects.get(&hash) {
+            Some((_, links)) => pending.extend(links.iter().cloned()),
+            None => {
+                missing.insert((hash, object_type));
+            }
+        }
+    }
+
+    let mut referenced = HashSet::new();
+
+    for (_, links) in objects.values() {
+        for (object_type, hash) in links {
+            referenced.insert(hash);
+
+            if !objects.contains_key(hash) {
+                missing.insert((hash.clone(), object_type.clone()));
+            }
+        }
+    }
+
+    let mut report = errors;
+
+    report.extend(
+        missing
+            .into_iter()
+            .map(|(hash, object_type)| format!("missing {} {}", object_type, hash)),
+    );
+
+    let broken = !report.is_empty();
+
+    let mut dangling = objects
+        .iter()
+        .filter(|(hash, _)| !reachable.contains(*hash) && !referenced.contains(hash))
+        .map(|(hash, (object_type, _))| format!("dangling {} {}", object_type, hash))
+        .collect::<Vec<String>>();
+    dangling.sort();
+
+    report.extend(dangling);
+
+    if broken {
+        Err(report.join("\n"))
+    } else {
+        Ok(report.join("\n"))
+    }
+}
+
+#[test]
+fn test_execute_healthy() {
+    use crate::object::commit::test_commit;
+    let mut fs = FileSystem::access();
+
+    let first = test_commit(&mut fs, &[("file.txt", "first\n", 0o100644)], vec![]);
+    let second = test_commit(&mut fs, &[("file.txt", "second\n", 0o100644)], vec![first]);
+    refs::update(&mut fs, "refs/heads/master", &second, false).unwrap();
+
+    assert_eq!(execute(&fs), Ok("".to_string()));
+
+    let dangling = hash_object::execute(&mut fs, b"forgotten\n", "blob".into(), true).unwrap();
+
+    assert_eq!(execute(&fs), Ok(format!("dangling blob {}", dangling)));
+}
+
+#[test]
+fn test_execute_broken() {
+    use crate::object::commit::test_commit;
+    use std::path::PathBuf;
+    let mut fs = FileSystem::access();
+
+    let commit = test_commit(&mut fs, &[("file.txt", "contents\n", 0o100644)], vec![]);
+    refs::update(&mut fs, "refs/heads/master", &commit, false).unwrap();
+
+    let object_path = |fs: &FileSystem, hash: &str| {
+        PathBuf::from(format!(
+            "{}/.papyrus/objects/{}/{}",
+            fs.current_directory(),
+            &hash[..2],
+            &hash[2..]
+        ))
+    };
+
+    // a truncated blob, and another blob stored under the wrong name
+    let blob = hash_object::hash(b"contents\n", "blob");
+    let contents = fs
+        .get_file_contents_as_bytes(&object_path(&fs, &blob))
+        .unwrap();
+    fs.write_file(&object_path(&fs, &blob), &contents[..contents.len() - 4]);
+
+    let wrong_name = "0123456789012345678901234567890123456789";
+    fs.create_directory(&object_path(&fs, wrong_name).parent().unwrap());
+    fs.write_file(
+        &object_path(&fs, wrong_name),
+        &hash_object::zlib_compress(b"blob 3\x00abc"),
+    );
+
+    assert_eq!(
+        execute(&fs),
+        Err([
+            "error: hash mismatch for objects/01/23456789012345678901234567890123456789 (expected 0123456789012345678901234567890123456789)".to_string(),
+            format!(
+                "error: {}: object corrupt or missing: objects/{}/{}",
+                blob,
+                &blob[..2],
+                &blob[2..]
+            ),
+            format!("missing blob {}", blob),
+        ]
+        .join("\n"))
+    );
+}
+
+#[test]
+fn test_execute_bad_index() {
+    let mut fs = FileSystem::access();
+
+    let index_path = format!("{}/.papyrus/index", fs.current_directory());
+    fs.create_file(&index_path);
+    fs.write_file(
+        &index_path,
+        &[b'D', b'I', b'R', b'C', 0, 0, 0, 2, 0, 0, 0, 0],
+    );
+
+    assert!(execute(&fs)
+        .unwrap_err()
+        .ends_with("fatal: index file corrupt"));
+}
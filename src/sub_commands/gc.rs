@@ -0,0 +1,303 @@
+use crate::fs::{FileSystem, Fs};
+use crate::index::{self, cached_tree::CachedTree};
+use crate::object::commit::Commit;
+use crate::object::tree::{Tree, SUBMODULE_MODE};
+use crate::object::{self, pack, Object};
+use crate::refs;
+use crate::sub_commands::hash_object;
+use std::collections::{HashMap, HashSet};
+use std::os::unix::fs::MetadataExt;
+
+/// Parses a `--prune` date such as `2.weeks.ago`, `now` or `never` into the
+/// timestamp loose objects have to be older than to be pruned, `None`
+/// meaning that nothing is.
+pub fn parse_expiry(value: &str, now: i64) -> Result<Option<i64>, String> {
+    let malformed = || format!("fatal: malformed expiration date '{}'", value);
+
+    match value {
+        "never" => return Ok(None),
+        "now" | "all" => return Ok(Some(now)),
+        _ => {}
+    }
+
+    let parts = value.split('.').collect::<Vec<&str>>();
+
+    let (count, unit) = match parts[..] {
+        [count, unit, "ago"] => (count.parse::<i64>().map_err(|_| malformed())?, unit),
+        _ => return Err(malformed()),
+    };
+
+    let seconds = match unit.trim_end_matches('s') {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 60 * 60,
+        "day" => 24 * 60 * 60,
+        "week" => 7 * 24 * 60 * 60,
+        _ => return Err(malformed()),
+    };
+
+    Ok(Some(now - count * seconds))
+}
+
+/// Lists the trees the index's `TREE` extension still has hashes for.
+fn cached_trees(tree: &CachedTree, hashes: &mut Vec<String>) {
+    hashes.extend(tree.hash.clone());
+
+    for child in &tree.children {
+        cached_trees(child, hashes);
+    }
+}
+
+/// Walks every object reachable from the refs, HEAD, a merge in progress and
+/// the index, reading them from `packed` when they are there.
+fn reachable_objects(
+    fs: &FileSystem,
+    packed: &mut HashMap<String, Object>,
+) -> Result<Vec<Object>, String> {
+    let mut pending = refs::list(fs)?
+        .into_iter()
+        .map(|(_, hash)| hash)
+        .collect::<Vec<String>>();
+
+    // a detached HEAD, or the commit being merged, isn't kept alive by any ref
+    for name in ["HEAD", "MERGE_HEAD", "ORIG_HEAD"] {
+        pending.extend(refs::resolve(fs, name)?);
+    }
+
+    // the index can name trees and blobs no commit has yet, but a submodule's
+    // commit is in another repository
+    let index = index::read(fs)?;
+    pending.extend(
+        index
+            .entries
+            .iter()
+            .filter(|entry| u32::from(entry.mode) != SUBMODULE_MODE)
+            .map(|entry| entry.hash.to_string()),
+    );
+    pending.extend(
+        index
+            .resolve_undo
+            .iter()
+            .flat_map(|entry| entry.stages.iter().flatten())
+            .filter(|(mode, _)| *mode != SUBMODULE_MODE)
+            .map(|(_, hash)| hash.clone()),
+    );
+    if let Some(tree) = &index.cached_tree {
+        cached_trees(tree, &mut pending);
+    }
+
+    let mut seen = HashSet::new();
+    let mut reachable = vec![];
+
+    while let Some(hash) = pending.pop() {
+        if !seen.insert(hash.clone()) {
+            continue;
+        }
+
+        let object = match packed.remove(&hash) {
+            Some(object) => object,
+            None => {
+                object::read(fs, &hash).map_err(|_| format!("fatal: unable to read {}", hash))?
+            }
+        };
+
+        match &object.object_type[..] {
+            "commit" => {
+                let commit = Commit::parse_from_object(&object.data)?;
+                pending.push(commit.tree);
+                pending.extend(commit.parents);
+            }
+            "tree" => pending.extend(
+                Tree::parse_from_object(&object.data)?
+                    .entries
+                    .into_iter()
+                    .filter(|entry| entry.mode != SUBMODULE_MODE)
+                    .map(|entry| entry.hash),
+            ),
+            "tag" => pending.extend(
+                String::from_utf8_lossy(&object.data)
+                    .lines()
+                    .next()
+                    .and_then(|line| line.strip_prefix("object "))
+                    .map(|hash| hash.to_string()),
+            ),
+            _ => {}
+        }
+
+        reachable.push(object);
+    }
+
+    Ok(reachable)
+}
+
+/// Packs every reachable object into a single pack, replacing the existing
+/// packs and the loose copies, and prunes the unreachable loose objects last
+/// modified at or before `prune_before`.
+pub fn execute(fs: &mut FileSystem, prune_before: Option<i64>) -> Result<String, String> {
+    let old_packs = pack::list_indexes(fs);
+    let mut packed = HashMap::new();
+
+    for path in &old_packs {
+        for object in pack::read_all(fs, path)? {
+            packed.insert(object.hash.clone(), object);
+        }
+    }
+
+    let reachable = reachable_objects(fs, &mut packed)?;
+    let reachable_hashes = reachable
+        .iter()
+        .map(|object| object.hash.clone())
+        .collect::<HashSet<String>>();
+
+    let new_pack = if reachable.is_empty() {
+        None
+    } else {
+        Some(pack::write(fs, reachable)?)
+    };
+
+    // unreachable objects of the old packs are kept loose, so that they are
+    // only pruned once they expire like any other loose object
+    for object in packed.into_values() {
+        hash_object::execute(fs, &object.data, object.object_type, true)?;
+    }
+
+    for path in old_packs {
+        if Some(&path) != new_pack.as_ref() {
+            fs.remove_file(&path.with_extension("pack"));
+            fs.remove_file(&path);
+        }
+    }
+
+    for (hash, path) in object::list_loose(fs) {
+        let expired = match prune_before {
+            Some(prune_before) => fs.metadata(&path)?.mtime() <= prune_before,
+            None => false,
+        };
+
+        if reachable_hashes.contains(&hash) || expired {
+            fs.remove_file(&path);
+
+            let directory = path.parent().unwrap().to_path_buf();
+
+            if fs.get_directory_files_recursively(&directory).is_empty() {
+                fs.remove_directory(&directory);
+            }
+        }
+    }
+
+    Ok("".to_string())
+}
+
+#[test]
+fn test_parse_expiry() {
+    assert_eq!(parse_expiry("now", 1000000), Ok(Some(1000000)));
+    assert_eq!(parse_expiry("never", 1000000), Ok(None));
+    assert_eq!(
+        parse_expiry("2.weeks.ago", 2000000),
+        Ok(Some(2000000 - 14 * 24 * 60 * 60))
+    );
+    assert_eq!(parse_expiry("1.hour.ago", 1000000), Ok(Some(996400)));
+    assert_eq!(
+        parse_expiry("yesterday", 1000000),
+        Err("fatal: malformed expiration date 'yesterday'".to_string())
+    );
+}
+
+#[test]
+fn test_execute() {
+    use crate::object::commit::test_commit;
+    let mut fs = FileSystem::access();
+
+    let contents = (0..100)
+        .map(|i| format!("line {}\n", i))
+        .collect::<String>();
+    let first = test_commit(&mut fs, &[("file.txt", &contents, 0o100644)], vec![]);
+    let second = test_commit(
+        &mut fs,
+        &[("file.txt", &format!("{}one more\n", contents), 0o100644)],
+        vec![first.clone()],
+    );
+    refs::update(&mut fs, "refs/heads/master", &second, false).unwrap();
+
+    let unreachable =
+        hash_object::execute(&mut fs, b"not referenced", "blob".into(), true).unwrap();
+
+    assert_eq!(execute(&mut fs, None), Ok("".to_string()));
+
+    // only the unreachable object is left loose
+    assert_eq!(
+        object::list_loose(&fs)
+            .into_iter()
+            .map(|(hash, _)| hash)
+            .collect::<Vec<String>>(),
+        vec![unreachable.clone()]
+    );
+
+    let indexes = pack::list_indexes(&fs);
+    assert_eq!(indexes.len(), 1);
+    assert_eq!(pack::read_all(&fs, &indexes[0]).unwrap().len(), 6);
+
+    let commit = Commit::read(&fs, &second).unwrap();
+    assert_eq!(commit.parents, vec![first]);
+
+    // repacking what is already packed gives the same pack back
+    assert_eq!(execute(&mut fs, Some(1591510158 - 1)), Ok("".to_string()));
+    assert_eq!(pack::list_indexes(&fs), indexes);
+    assert!(object::read(&fs, &unreachable).is_ok());
+
+    assert_eq!(execute(&mut fs, Some(1591510158)), Ok("".to_string()));
+    assert!(object::list_loose(&fs).is_empty());
+    assert!(object::read(&fs, &unreachable).is_err());
+}
+
+#[test]
+fn test_execute_during_merge() {
+    use crate::index::{FileMode, Index, IndexEntry};
+    use crate::object::commit::test_commit;
+    use crate::object::tree::TreeEntry;
+    use crate::object::ObjectId;
+    use crate::sub_commands::write_tree;
+    let mut fs = FileSystem::access();
+
+    let ours = test_commit(&mut fs, &[("file.txt", "ours\n", 0o100644)], vec![]);
+    refs::update(&mut fs, "refs/heads/master", &ours, false).unwrap();
+
+    // a commit merged by its hash is only named by MERGE_HEAD
+    let theirs = test_commit(&mut fs, &[("file.txt", "theirs\n", 0o100644)], vec![]);
+    refs::update(&mut fs, "MERGE_HEAD", &theirs, true).unwrap();
+
+    let blob = hash_object::execute(&mut fs, b"cached\n", "blob".into(), true).unwrap();
+    let tree = write_tree::write_entries(
+        &mut fs,
+        vec![TreeEntry {
+            mode: 0o100644,
+            name: "cached.txt".to_string(),
+            hash: blob.clone(),
+        }],
+    )
+    .unwrap();
+
+    index::write(
+        &mut fs,
+        Index {
+            entries: vec![IndexEntry {
+                mode: FileMode::from(SUBMODULE_MODE),
+                hash: ObjectId::from_hex(&"1".repeat(40)).unwrap(),
+                path: b"vendor".to_vec(),
+                ..Default::default()
+            }],
+            cached_tree: Some(CachedTree {
+                entry_count: 1,
+                hash: Some(tree.clone()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(execute(&mut fs, Some(1591510158)), Ok("".to_string()));
+
+    for hash in [theirs, tree, blob] {
+        assert!(object::read(&fs, &hash).is_ok());
+    }
+}
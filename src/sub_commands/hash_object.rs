@@ -1,14 +1,14 @@
 use crate::fs::{FileSystem, Fs};
+use crate::repository;
 use crypto::digest::Digest;
 use crypto::sha1::Sha1;
 use flate2::write::ZlibEncoder;
 use flate2::Compression;
 use std::io::Write;
-use std::path::PathBuf;
 
 fn create_sha1(input: &[u8]) -> String {
     let mut hasher = Sha1::new();
-    hasher.input(&input[..]);
+    hasher.input(input);
     hasher.result_str()
 }
 
@@ -20,7 +20,7 @@ fn test_create_sha1() {
     );
 }
 
-fn zlib_compress(input: &[u8]) -> Vec<u8> {
+pub fn zlib_compress(input: &[u8]) -> Vec<u8> {
     let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
     encoder.write_all(input).unwrap();
     encoder.finish().unwrap()
@@ -37,26 +37,35 @@ fn test_zlib_compress() {
     );
 }
 
-pub fn execute(
-    fs: &mut FileSystem,
-    contents: &[u8],
-    object_type: String,
-    write: bool,
-) -> Result<String, String> {
+fn add_header(contents: &[u8], object_type: &str) -> Vec<u8> {
     let mut contents_len = String::new();
 
     for i in contents.len().to_string().chars() {
         contents_len.push(i);
     }
 
-    let object_contents = [
+    [
         object_type.as_bytes(),
         b" ",
-        &contents_len.as_bytes(),
+        contents_len.as_bytes(),
         b"\x00",
-        &contents,
+        contents,
     ]
-    .concat();
+    .concat()
+}
+
+/// Computes the object name without touching the object store.
+pub fn hash(contents: &[u8], object_type: &str) -> String {
+    create_sha1(&add_header(contents, object_type))
+}
+
+pub fn execute(
+    fs: &mut FileSystem,
+    contents: &[u8],
+    object_type: String,
+    write: bool,
+) -> Result<String, String> {
+    let object_contents = add_header(contents, &object_type);
 
     let sha1 = create_sha1(&object_contents);
 
@@ -64,8 +73,7 @@ pub fn execute(
         let object_folder = &sha1[..2];
         let object_file = &sha1[2..];
 
-        let mut absolute_folder_path = PathBuf::from(fs.current_directory());
-        absolute_folder_path.push(".papyrus");
+        let mut absolute_folder_path = repository::path(fs);
         absolute_folder_path.push("objects");
         absolute_folder_path.push(object_folder);
 
@@ -1,30 +1,105 @@
 use crate::fs::{FileSystem, Fs};
+use crate::refs;
+use crate::repository;
+use std::path::PathBuf;
 
-pub fn execute(fs: &mut FileSystem) -> Result<String, String> {
-    let current_directory = fs.current_directory();
-    let papyrus_path = format!("{}/.papyrus/", current_directory);
+/// Creates a repository in `directory`, or the current directory, or fills in
+/// whatever is missing from an existing one, leaving its objects, refs and
+/// index alone. A bare repository has no worktree, the directory itself
+/// holding what would be in `.papyrus`.
+pub fn execute(
+    fs: &mut FileSystem,
+    directory: Option<PathBuf>,
+    bare: bool,
+    initial_branch: Option<String>,
+) -> Result<String, String> {
+    if let Some(directory) = directory {
+        let directory = PathBuf::from(fs.current_directory()).join(directory);
 
-    let message = if !fs.path_exists(&papyrus_path) {
-        fs.create_directory(&papyrus_path);
-        fs.create_directory(&format!("{}objects", &papyrus_path));
-        format!("Initialized empty Papyrus repository in {}", papyrus_path)
+        if !fs.path_exists(&directory) {
+            fs.create_directory(&directory);
+        }
+
+        fs.set_current_directory(&directory);
+    }
+
+    let repository_path = if bare {
+        PathBuf::from(fs.current_directory())
     } else {
-        fs.remove_directory(&papyrus_path);
-        fs.create_directory(&papyrus_path);
-        format!(
-            "Reinitialized existing Papyrus repository in {}",
-            papyrus_path
-        )
+        repository::path(fs)
     };
 
-    Ok(message)
+    if let Some(branch) = &initial_branch {
+        if !refs::is_valid_name(&format!("refs/heads/{}", branch)) {
+            return Err(format!("fatal: invalid initial branch name: '{}'", branch));
+        }
+    }
+
+    let reinitialized = fs.path_exists(&repository_path.join("HEAD"));
+
+    for directory in &[
+        "",
+        "objects",
+        "objects/info",
+        "objects/pack",
+        "refs",
+        "refs/heads",
+        "refs/tags",
+    ] {
+        let directory = repository_path.join(directory);
+
+        if !fs.path_exists(&directory) {
+            fs.create_directory(&directory);
+        }
+    }
+
+    let config_path = repository_path.join("config");
+
+    if !fs.path_exists(&config_path) {
+        fs.create_file(&config_path);
+        fs.write_file(
+            &config_path,
+            format!(
+                "[core]\n\trepositoryformatversion = 0\n\tfilemode = true\n\tbare = {}\n",
+                bare
+            )
+            .as_bytes(),
+        );
+    }
+
+    let papyrus_path = format!("{}/", repository_path.display());
+
+    if reinitialized {
+        let warning = match initial_branch {
+            Some(branch) => format!("warning: re-init: ignored --initial-branch={}\n", branch),
+            None => "".to_string(),
+        };
+
+        return Ok(format!(
+            "{}Reinitialized existing Papyrus repository in {}",
+            warning, papyrus_path
+        ));
+    }
+
+    let head = format!(
+        "ref: refs/heads/{}\n",
+        initial_branch.as_deref().unwrap_or("master")
+    );
+    let head_path = repository_path.join("HEAD");
+    fs.create_file(&head_path);
+    fs.write_file(&head_path, head.as_bytes());
+
+    Ok(format!(
+        "Initialized empty Papyrus repository in {}",
+        papyrus_path
+    ))
 }
 
 #[test]
 fn test_execute_non_initialized() {
     let mut fs = FileSystem::access();
 
-    let message = execute(&mut fs).unwrap();
+    let message = execute(&mut fs, None, false, None).unwrap();
 
     assert_eq!(
         message,
@@ -33,20 +108,73 @@ fn test_execute_non_initialized() {
 
     assert!(fs.path_exists(&format!("{}/.papyrus/", fs.current_directory())));
     assert!(fs.path_exists(&format!("{}/.papyrus/objects", fs.current_directory())));
+    assert!(fs.path_exists(&format!("{}/.papyrus/refs/heads", fs.current_directory())));
+    assert!(fs.path_exists(&format!("{}/.papyrus/refs/tags", fs.current_directory())));
+    assert_eq!(
+        fs.get_file_contents(&format!("{}/.papyrus/HEAD", fs.current_directory()).into())
+            .unwrap(),
+        "ref: refs/heads/master\n"
+    );
 }
 
 #[test]
 fn test_execute_already_initialized() {
+    use crate::object;
+    use crate::sub_commands::hash_object;
     let mut fs = FileSystem::access();
 
-    fs.create_directory(&format!("{}/.papyrus/", fs.current_directory()));
+    execute(&mut fs, None, false, None).unwrap();
+
+    let hash = hash_object::execute(&mut fs, b"kept", "blob".into(), true).unwrap();
+    refs::update(&mut fs, "refs/heads/master", &hash, false).unwrap();
 
-    let message = execute(&mut fs).unwrap();
+    let message = execute(&mut fs, None, false, Some("main".to_string())).unwrap();
 
     assert_eq!(
         message,
-        "Reinitialized existing Papyrus repository in /Users/jack/cool_project/.papyrus/"
+        "warning: re-init: ignored --initial-branch=main\n\
+         Reinitialized existing Papyrus repository in /Users/jack/cool_project/.papyrus/"
     );
 
     assert!(fs.path_exists(&format!("{}/.papyrus/", fs.current_directory())));
+    assert_eq!(object::read(&fs, &hash).unwrap().data, b"kept");
+    assert_eq!(
+        refs::resolve_name(&fs, "HEAD").unwrap(),
+        ("refs/heads/master".to_string(), Some(hash))
+    );
+}
+
+#[test]
+fn test_execute_bare_in_directory() {
+    let mut fs = FileSystem::access();
+
+    let message = execute(
+        &mut fs,
+        Some("repo.git".into()),
+        true,
+        Some("main".to_string()),
+    )
+    .unwrap();
+
+    assert_eq!(
+        message,
+        "Initialized empty Papyrus repository in /Users/jack/cool_project/repo.git/"
+    );
+    assert!(fs.path_exists("/Users/jack/cool_project/repo.git/objects/pack"));
+    assert!(fs.path_exists("/Users/jack/cool_project/repo.git/refs/heads"));
+    assert!(!fs.path_exists("/Users/jack/cool_project/repo.git/.papyrus"));
+    assert_eq!(
+        fs.get_file_contents(&"/Users/jack/cool_project/repo.git/HEAD".into())
+            .unwrap(),
+        "ref: refs/heads/main\n"
+    );
+    assert!(fs
+        .get_file_contents(&"/Users/jack/cool_project/repo.git/config".into())
+        .unwrap()
+        .contains("bare = true"));
+
+    assert_eq!(
+        execute(&mut fs, None, false, Some("bad..name".to_string())).unwrap_err(),
+        "fatal: invalid initial branch name: 'bad..name'"
+    );
 }
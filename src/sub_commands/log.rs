@@ -0,0 +1,306 @@
+use crate::fs::FileSystem;
+use crate::object::commit::Commit;
+use crate::refs;
+use crate::revision;
+use std::collections::{BinaryHeap, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// Newest committer date first, as commits are discovered.
+    Default,
+    /// Newest committer date first, but never a parent before its children.
+    Date,
+    /// Never a parent before its children, showing one line of history at a
+    /// time instead of interleaving them.
+    Topo,
+}
+
+pub fn execute(
+    fs: &FileSystem,
+    revision: Option<String>,
+    oneline: bool,
+    max_count: Option<usize>,
+    order: Order,
+) -> Result<String, String> {
+    let start = match revision {
+        Some(name) => revision::resolve(fs, &name)
+            .and_then(|hash| revision::peel(fs, &hash, "commit"))
+            .map_err(|_| {
+                format!(
+                "fatal: ambiguous argument '{}': unknown revision or path not in the working tree.",
+                name
+            )
+            })?,
+        None => match refs::resolve_name(fs, "HEAD")? {
+            (_, Some(hash)) => hash,
+            (branch, None) => {
+                return Err(format!(
+                    "fatal: your current branch '{}' does not have any commits yet",
+                    refs::shorten(&branch)
+                ))
+            }
+        },
+    };
+
+    let commits = walk(fs, &start, order)?;
+
+    let output = commits
+        .iter()
+        .take(max_count.unwrap_or(usize::MAX))
+        .map(|(hash, commit)| {
+            if oneline {
+                format_oneline(hash, commit)
+            } else {
+                format_medium(hash, commit)
+            }
+        })
+        .collect::<Vec<String>>();
+
+    Ok(output.join(if oneline { "\n" } else { "\n\n" }))
+}
+
+/// Lists every commit reachable from `start`, in the requested order.
+pub fn walk(fs: &FileSystem, start: &str, order: Order) -> Result<Vec<(String, Commit)>, String> {
+    let start = Commit::read(fs, start).map(|commit| (start.to_string(), commit))?;
+
+    if order == Order::Default {
+        return walk_by_date(fs, start);
+    }
+
+    let mut commits: HashMap<String, Commit> = HashMap::new();
+    let mut children_count: HashMap<String, usize> = HashMap::new();
+    let mut pending = vec![start.0.clone()];
+    commits.insert(start.0.clone(), start.1);
+
+    while let Some(hash) = pending.pop() {
+        for parent in commits[&hash].parents.clone() {
+            *children_count.entry(parent.clone()).or_insert(0) += 1;
+
+            if !commits.contains_key(&parent) {
+                commits.insert(parent.clone(), Commit::read(fs, &parent)?);
+                pending.push(parent);
+            }
+        }
+    }
+
+    let mut output = vec![];
+    let mut ready = vec![start.0];
+
+    while !ready.is_empty() {
+        let index = match order {
+            Order::Topo => ready.len() - 1,
+            _ => {
+                let (index, _) = ready
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|(_, hash)| commits[*hash].committer.timestamp)
+                    .unwrap();
+                index
+            }
+        };
+        let hash = ready.remove(index);
+        let commit = commits.remove(&hash).unwrap();
+
+        for parent in &commit.parents {
+            let count = children_count.get_mut(parent).unwrap();
+            *count -= 1;
+
+            // the same parent listed twice only becomes ready once
+            if *count == 0 && !ready.contains(parent) {
+                ready.push(parent.clone());
+            }
+        }
+
+        output.push((hash, commit));
+    }
+
+    Ok(output)
+}
+
+fn walk_by_date(fs: &FileSystem, start: (String, Commit)) -> Result<Vec<(String, Commit)>, String> {
+    let mut seen: HashMap<String, Commit> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    let mut output = vec![];
+    let mut sequence = 0;
+
+    // ties in date keep discovery order, so the sequence is negated to make
+    // earlier discoveries come out of the max-heap first
+    queue.push((start.1.committer.timestamp, sequence, start.0.clone()));
+    seen.insert(start.0, start.1);
+
+    while let Some((_, _, hash)) = queue.pop() {
+        let commit = seen[&hash].clone();
+
+        for parent in &commit.parents {
+            if !seen.contains_key(parent) {
+                let parent_commit = Commit::read(fs, parent)?;
+                sequence -= 1;
+                queue.push((parent_commit.committer.timestamp, sequence, parent.clone()));
+                seen.insert(parent.clone(), parent_commit);
+            }
+        }
+
+        output.push((hash, commit));
+    }
+
+    Ok(output)
+}
+
+fn format_oneline(hash: &str, commit: &Commit) -> String {
+    format!(
+        "{} {}",
+        &hash[..7],
+        commit.message.lines().next().unwrap_or("")
+    )
+}
+
+fn format_medium(hash: &str, commit: &Commit) -> String {
+    let mut output = format!("commit {}\n", hash);
+
+    if commit.parents.len() > 1 {
+        let parents = commit
+            .parents
+            .iter()
+            .map(|parent| &parent[..7])
+            .collect::<Vec<&str>>();
+        output.push_str(&format!("Merge: {}\n", parents.join(" ")));
+    }
+
+    output.push_str(&format!(
+        "Author: {} <{}>\n",
+        commit.author.name, commit.author.email
+    ));
+    output.push_str(&format!("Date:   {}\n\n", commit.author.format_date()));
+
+    let message = commit
+        .message
+        .trim_end()
+        .lines()
+        .map(|line| format!("    {}", line))
+        .collect::<Vec<String>>();
+    output.push_str(&message.join("\n"));
+
+    output
+}
+
+#[cfg(test)]
+fn commit_at(fs: &mut FileSystem, parents: Vec<String>, message: &str, timestamp: i64) -> String {
+    use crate::object::commit::test_signature;
+    use crate::sub_commands::{commit_tree, hash_object};
+
+    let tree = hash_object::execute(fs, b"", "tree".into(), true).unwrap();
+    let mut committer = test_signature();
+    committer.timestamp = timestamp;
+
+    commit_tree::execute(
+        fs,
+        tree,
+        parents,
+        vec![message.to_string()],
+        test_signature(),
+        committer,
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_execute_medium() {
+    use crate::fs::Fs;
+    use crate::sub_commands::init;
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    let first = commit_at(&mut fs, vec![], "first\n\nbody line", 1591510158);
+    let second = commit_at(&mut fs, vec![first.clone()], "second", 1591510200);
+    refs::update(&mut fs, "HEAD", &second, false).unwrap();
+
+    assert_eq!(
+        execute(&fs, None, false, None, Order::Default).unwrap(),
+        format!(
+            "commit {}\n\
+             Author: Jack <jack@example.com>\n\
+             Date:   Sun Jun 7 03:09:18 2020 -0300\n\
+             \n    second\n\
+             \n\
+             commit {}\n\
+             Author: Jack <jack@example.com>\n\
+             Date:   Sun Jun 7 03:09:18 2020 -0300\n\
+             \n    first\n    \n    body line",
+            second, first
+        )
+    );
+    assert_eq!(
+        execute(
+            &fs,
+            Some(first[..8].to_string()),
+            true,
+            None,
+            Order::Default
+        )
+        .unwrap(),
+        format!("{} first", &first[..7])
+    );
+    assert_eq!(
+        execute(
+            &fs,
+            Some("master".to_string()),
+            true,
+            Some(1),
+            Order::Default
+        )
+        .unwrap(),
+        format!("{} second", &second[..7])
+    );
+}
+
+#[test]
+fn test_execute_merge_ordering() {
+    use crate::fs::Fs;
+    use crate::sub_commands::init;
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    let first = commit_at(&mut fs, vec![], "first", 100);
+    let side1 = commit_at(&mut fs, vec![first.clone()], "side1", 200);
+    let main1 = commit_at(&mut fs, vec![first.clone()], "main1", 300);
+    let side2 = commit_at(&mut fs, vec![side1.clone()], "side2", 400);
+    let merge = commit_at(&mut fs, vec![main1.clone(), side2.clone()], "merge", 500);
+    refs::update(&mut fs, "HEAD", &merge, false).unwrap();
+
+    let messages = |order| {
+        execute(&fs, None, true, None, order)
+            .unwrap()
+            .lines()
+            .map(|line| line[8..].to_string())
+            .collect::<Vec<String>>()
+    };
+
+    assert_eq!(
+        messages(Order::Default),
+        vec!["merge", "side2", "main1", "side1", "first"]
+    );
+    assert_eq!(
+        messages(Order::Date),
+        vec!["merge", "side2", "main1", "side1", "first"]
+    );
+    assert_eq!(
+        messages(Order::Topo),
+        vec!["merge", "side2", "side1", "main1", "first"]
+    );
+}
+
+#[test]
+fn test_execute_no_commits_yet() {
+    use crate::fs::Fs;
+    use crate::sub_commands::init;
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    assert_eq!(
+        execute(&fs, None, false, None, Order::Default).unwrap_err(),
+        "fatal: your current branch 'master' does not have any commits yet"
+    );
+}
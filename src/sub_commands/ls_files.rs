@@ -1,22 +1,21 @@
-use crate::fs::{FileSystem, Fs};
-use crate::index::IndexEntry;
+use crate::fs::FileSystem;
+use crate::index::{self, IndexEntry};
 
-pub fn execute(fs: &FileSystem, stage: bool) -> Result<String, String> {
-    let index_path = format!("{}/.papyrus/index", fs.current_directory());
+/// Lists the paths in the index, with their mode, hash and stage when
+/// `stage` is set, `unmerged` listing only the stages of conflicted paths.
+pub fn execute(fs: &FileSystem, stage: bool, unmerged: bool) -> Result<String, String> {
+    let mut entries = index::read_entries(fs)?;
 
-    if !fs.path_exists(&index_path) {
-        return Ok("".to_string());
+    if unmerged {
+        entries.retain(|entry| entry.stage > 0);
     }
 
-    let index_content = fs.get_file_contents_as_bytes(&index_path.into()).unwrap();
-
-    let entries = IndexEntry::parse_from_file(&index_content)?;
-
-    Ok(IndexEntry::format_index_entries(entries, stage))
+    Ok(IndexEntry::format_index_entries(entries, stage || unmerged))
 }
 
 #[test]
 fn test_execute_bad_checksum_sha1_signature() {
+    use crate::fs::Fs;
     let mut fs = FileSystem::access();
 
     fs.create_file(&format!("{}/.papyrus/index", fs.current_directory()));
@@ -97,13 +96,14 @@ fn test_execute_bad_checksum_sha1_signature() {
     );
 
     assert_eq!(
-        execute(&fs, false).unwrap_err(),
+        execute(&fs, false, false).unwrap_err(),
         "error: bad index file sha1 signature\nfatal: index file corrupt"
     );
 }
 
 #[test]
 fn test_execute_bad_signature() {
+    use crate::fs::Fs;
     let mut fs = FileSystem::access();
 
     fs.create_file(&format!("{}/.papyrus/index", fs.current_directory()));
@@ -184,13 +184,14 @@ fn test_execute_bad_signature() {
     );
 
     assert_eq!(
-        execute(&fs, false).unwrap_err(),
+        execute(&fs, false, false).unwrap_err(),
         "error: bad signature\nfatal: index file corrupt"
     );
 }
 
 #[test]
 fn test_execute_bad_version() {
+    use crate::fs::Fs;
     let mut fs = FileSystem::access();
 
     fs.create_file(&format!("{}/.papyrus/index", fs.current_directory()));
@@ -271,13 +272,14 @@ fn test_execute_bad_version() {
     );
 
     assert_eq!(
-        execute(&fs, false).unwrap_err(),
+        execute(&fs, false, false).unwrap_err(),
         "error: bad version\nfatal: index file corrupt"
     );
 }
 
 #[test]
 fn test_execute_good1_no_stage() {
+    use crate::fs::Fs;
     let mut fs = FileSystem::access();
 
     fs.create_file(&format!("{}/.papyrus/index", fs.current_directory()));
@@ -360,6 +362,7 @@ fn test_execute_good1_no_stage() {
     assert_eq!(
         execute(
             &fs,
+            false,
             false
         )
         .unwrap(),
@@ -369,6 +372,7 @@ fn test_execute_good1_no_stage() {
 
 #[test]
 fn test_execute_good2_no_stage() {
+    use crate::fs::Fs;
     let mut fs = FileSystem::access();
 
     fs.create_file(&format!("{}/.papyrus/index", fs.current_directory()));
@@ -393,13 +397,14 @@ fn test_execute_good2_no_stage() {
     );
 
     assert_eq!(
-        execute(&fs, false).unwrap(),
+        execute(&fs, false, false).unwrap(),
         "LICENSE.txt\nREADME.md\npygit.py"
     );
 }
 
 #[test]
 fn test_execute_good1_with_stage() {
+    use crate::fs::Fs;
     let mut fs = FileSystem::access();
 
     fs.create_file(&format!("{}/.papyrus/index", fs.current_directory()));
@@ -482,7 +487,8 @@ fn test_execute_good1_with_stage() {
     assert_eq!(
         execute(
             &fs,
-            true
+            true,
+            false
         )
         .unwrap(),
         "100644 34dc40c04f6e96e8a54d4a2b9bc05b66ff92d494 0\t.gitignore\n100644 1720dcce030ba63691caefce94cf8e72bdc9a25f 0\tCargo.lock\n100644 16a80e3983ecbd703a3f4358588cfe08d5219a2c 0\tCargo.toml\n100644 fca188c1ffc43cf8bc9060341a37853a77f96d2c 0\tREADME.md\n100644 acb0772c4e0fb6fd735baf608a52854a4971d2a8 0\tnotes/terms.md\n100644 0df3a3c2d45ac3bbdd93ef5adc4543bbd0869c0a 0\tsrc/fs/fake.rs\n100644 d0b22a4c07924a017816554d2c2cb692d3458043 0\tsrc/fs/mod.rs\n100644 5afd1218b6d84e5f975051dc1912c182adefb64e 0\tsrc/fs/real.rs\n100644 a5853b1da2da6c35b4269e12d13722d14c228ed4 0\tsrc/lib.rs\n100644 2bb8c805d05b6b52d2168b402c6b49c3b799caeb 0\tsrc/main.rs\n100644 b15864fe9c0d7221b292030e25d1738ed504cc55 0\tsrc/sub_commands/cat_file.rs\n100644 d22d97452da88ef58b15bb0c0013c189e00f4bec 0\tsrc/sub_commands/hash_object.rs\n100644 0b5726037cf338214b10eb59067209c75ba3d68c 0\tsrc/sub_commands/init.rs\n100644 6aeb7cd20180f8e23a1f53f3dbc863af37bdc80e 0\tsrc/sub_commands/ls_files.rs\n100644 9a1d9f69047e991c0519b6752919b07fa6307412 0\tsrc/sub_commands/mod.rs"
@@ -491,6 +497,7 @@ fn test_execute_good1_with_stage() {
 
 #[test]
 fn test_execute_good2_with_stage() {
+    use crate::fs::Fs;
     let mut fs = FileSystem::access();
 
     fs.create_file(&format!("{}/.papyrus/index", fs.current_directory()));
@@ -517,7 +524,8 @@ fn test_execute_good2_with_stage() {
     assert_eq!(
         execute(
             &fs,
-            true
+            true,
+            false
         )
         .unwrap(),
         "100644 4aab5f560862b45d7a9f1370b1c163b74484a24d 0\tLICENSE.txt\n100644 43ab992ed09fa756c56ff162d5fe303003b5ae0f 0\tREADME.md\n100644 c10cb8bc2c114aba5a1cb20dea4c1597e5a3c193 0\tpygit.py"
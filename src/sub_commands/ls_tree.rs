@@ -0,0 +1,125 @@
+use crate::fs::FileSystem;
+use crate::object;
+use crate::object::tree::Tree;
+
+pub fn execute(
+    fs: &FileSystem,
+    tree_ish: String,
+    recursive: bool,
+    show_trees: bool,
+    name_only: bool,
+) -> Result<String, String> {
+    let tree = object::peel_to_tree(fs, &tree_ish)?;
+
+    let mut output = vec![];
+
+    list_tree(fs, &tree, "", recursive, show_trees, name_only, &mut output)?;
+
+    Ok(output.join("\n"))
+}
+
+fn list_tree(
+    fs: &FileSystem,
+    tree: &str,
+    prefix: &str,
+    recursive: bool,
+    show_trees: bool,
+    name_only: bool,
+    output: &mut Vec<String>,
+) -> Result<(), String> {
+    for entry in Tree::read(fs, tree)?.entries {
+        let path = format!("{}{}", prefix, entry.name);
+        let descend = recursive && entry.is_tree();
+
+        if !descend || show_trees {
+            if name_only {
+                output.push(path.clone());
+            } else {
+                output.push(format!(
+                    "{:06o} {} {}\t{}",
+                    entry.mode,
+                    entry.object_type(),
+                    entry.hash,
+                    path
+                ));
+            }
+        }
+
+        if descend {
+            list_tree(
+                fs,
+                &entry.hash,
+                &format!("{}/", path),
+                recursive,
+                show_trees,
+                name_only,
+                output,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_execute() {
+    use crate::fs::Fs;
+    let mut fs = FileSystem::access();
+
+    let tree = crate::object::tree::write_test_tree(&mut fs);
+
+    assert_eq!(
+        execute(&fs, tree[..7].to_string(), false, false, false).unwrap(),
+        "100755 blob 8178c76d627cade75005b40711b92f4177bc6cfc\tREADME\n\
+         040000 tree 5d90422423db5ef6b431e8b9e60e0baf04b8742a\tsrc"
+    );
+    assert_eq!(
+        execute(&fs, tree.clone(), true, false, false).unwrap(),
+        "100755 blob 8178c76d627cade75005b40711b92f4177bc6cfc\tREADME\n\
+         100644 blob f328e4d9d04c31d0d70d16d21a07d1613be9d577\tsrc/main.rs"
+    );
+    assert_eq!(
+        execute(&fs, tree, true, true, true).unwrap(),
+        "README\nsrc\nsrc/main.rs"
+    );
+}
+
+#[test]
+fn test_execute_from_commit() {
+    use crate::fs::Fs;
+    use crate::object::commit::test_signature;
+    use crate::refs;
+    use crate::sub_commands::commit_tree;
+    let mut fs = FileSystem::access();
+
+    let tree = crate::object::tree::write_test_tree(&mut fs);
+    let commit = commit_tree::execute(
+        &mut fs,
+        tree,
+        vec![],
+        vec!["first".to_string()],
+        test_signature(),
+        test_signature(),
+    )
+    .unwrap();
+    refs::update(&mut fs, "refs/heads/master", &commit, false).unwrap();
+
+    assert_eq!(
+        execute(&fs, "master".to_string(), false, false, true).unwrap(),
+        "README\nsrc"
+    );
+}
+
+#[test]
+fn test_execute_not_a_tree() {
+    use crate::fs::Fs;
+    use crate::sub_commands::hash_object;
+    let mut fs = FileSystem::access();
+
+    let blob = hash_object::execute(&mut fs, b"readme\n", "blob".into(), true).unwrap();
+
+    assert_eq!(
+        execute(&fs, blob.clone(), false, false, false).unwrap_err(),
+        format!("fatal: not a tree object: {}", blob)
+    );
+}
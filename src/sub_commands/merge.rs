@@ -0,0 +1,916 @@
+use crate::diff::merge::merge_files;
+use crate::fs::{FileSystem, Fs};
+use crate::index::{self, FileMode, IndexEntry};
+use crate::object::{self, commit::Commit, commit::Signature, ObjectId};
+use crate::refs;
+use crate::repository;
+use crate::revision;
+use crate::sub_commands::checkout::{self, check_overwritten, write_worktree_file, Entries};
+use crate::sub_commands::{add, commit_tree, hash_object, write_tree};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, VecDeque};
+use std::path::PathBuf;
+
+/// A path both sides changed in ways that couldn't be merged.
+struct Conflict {
+    /// The base, our and their version, which go into stages 1 to 3.
+    stages: [Option<(u32, String)>; 3],
+    /// What to leave in the working tree, if not our version.
+    contents: Option<(u32, Vec<u8>)>,
+    /// Where to leave it instead of the path, when a directory is in the way.
+    moved_to: Option<String>,
+}
+
+pub fn execute(
+    fs: &mut FileSystem,
+    target: String,
+    no_ff: bool,
+    messages: Vec<String>,
+    author: Signature,
+    committer: Signature,
+) -> Result<String, String> {
+    if refs::resolve(fs, "MERGE_HEAD")?.is_some() {
+        return Err(
+            "fatal: You have not concluded your merge (MERGE_HEAD exists).\n\
+                    Please, commit your changes before you merge."
+                .to_string(),
+        );
+    }
+
+    checkout::check_resolved(&index::read_entries(fs)?)?;
+
+    let (head_ref, head) = refs::resolve_name(fs, "HEAD")?;
+    let theirs = revision::resolve(fs, &target)
+        .and_then(|hash| revision::peel(fs, &hash, "commit"))
+        .map_err(|_| format!("merge: {} - not something we can merge", target))?;
+
+    let base = match &head {
+        Some(head) => merge_base(fs, head, &theirs)?,
+        None => None,
+    };
+
+    if base.as_ref() == Some(&theirs) {
+        return Ok("Already up to date.".to_string());
+    }
+
+    let our_entries = checkout::flatten_tree(fs, head.as_deref())?;
+    let their_entries = checkout::flatten_tree(fs, Some(&theirs))?;
+
+    // a branch yet to be born has nothing to merge with
+    if head.is_none() || (base == head && !no_ff) {
+        update_worktree(fs, &our_entries, &their_entries, BTreeMap::new())?;
+        refs::update(fs, "HEAD", &theirs, false)?;
+
+        return Ok(match head {
+            Some(head) => format!("Updating {}..{}\nFast-forward", &head[..7], &theirs[..7]),
+            None => "Fast-forward".to_string(),
+        });
+    }
+
+    let (head, base) = match (head, base) {
+        (Some(head), Some(base)) => (head, base),
+        _ => return Err("fatal: refusing to merge unrelated histories".to_string()),
+    };
+
+    check_index_matches(fs, &our_entries)?;
+
+    let base_entries = checkout::flatten_tree(fs, Some(&base))?;
+
+    let paths = base_entries
+        .keys()
+        .chain(our_entries.keys())
+        .chain(their_entries.keys())
+        .cloned()
+        .collect::<BTreeSet<String>>();
+
+    let mut output = vec![];
+    let mut merged = BTreeMap::new();
+    let mut conflicts = BTreeMap::new();
+
+    for path in paths {
+        let (base_entry, our_entry, their_entry) = (
+            base_entries.get(&path),
+            our_entries.get(&path),
+            their_entries.get(&path),
+        );
+
+        // a side that didn't touch the path takes the other's version
+        let (our_entry, their_entry) = if our_entry == their_entry || their_entry == base_entry {
+            if let Some(our_entry) = our_entry {
+                merged.insert(path, our_entry.clone());
+            }
+            continue;
+        } else if our_entry == base_entry {
+            if let Some(their_entry) = their_entry {
+                merged.insert(path, their_entry.clone());
+            }
+            continue;
+        } else {
+            (our_entry, their_entry)
+        };
+
+        let stages = [
+            base_entry.cloned(),
+            our_entry.cloned(),
+            their_entry.cloned(),
+        ];
+
+        let (our_entry, their_entry) = match (our_entry, their_entry) {
+            (Some(our_entry), Some(their_entry)) => (our_entry, their_entry),
+            (Some(_), None) => {
+                output.push(format!(
+                    "CONFLICT (modify/delete): {} deleted in {} and modified in HEAD.  \
+                     Version HEAD of {} left in tree.",
+                    path, target, path
+                ));
+                conflicts.insert(
+                    path,
+                    Conflict {
+                        stages,
+                        contents: None,
+                        moved_to: None,
+                    },
+                );
+                continue;
+            }
+            (None, Some((mode, hash))) => {
+                output.push(format!(
+                    "CONFLICT (modify/delete): {} deleted in HEAD and modified in {}.  \
+                     Version {} of {} left in tree.",
+                    path, target, target, path
+                ));
+                let contents = Some((*mode, object::read(fs, hash)?.data));
+                conflicts.insert(
+                    path,
+                    Conflict {
+                        stages,
+                        contents,
+                        moved_to: None,
+                    },
+                );
+                continue;
+            }
+            (None, None) => unreachable!(),
+        };
+
+        output.push(format!("Auto-merging {}", path));
+
+        let base_data = match base_entry {
+            Some((_, hash)) => object::read(fs, hash)?.data,
+            None => vec![],
+        };
+        let (data, conflicted) = merge_files(
+            &base_data,
+            &object::read(fs, &our_entry.1)?.data,
+            &object::read(fs, &their_entry.1)?.data,
+            ("HEAD", &target),
+        );
+
+        let mode = match base_entry {
+            Some((mode, _)) if *mode == our_entry.0 => their_entry.0,
+            _ => our_entry.0,
+        };
+
+        if conflicted {
+            let kind = if base_entry.is_some() {
+                "content"
+            } else {
+                "add/add"
+            };
+            output.push(format!("CONFLICT ({}): Merge conflict in {}", kind, path));
+
+            let contents = Some((mode, data));
+            conflicts.insert(
+                path,
+                Conflict {
+                    stages,
+                    contents,
+                    moved_to: None,
+                },
+            );
+        } else {
+            let hash = hash_object::execute(fs, &data, "blob".into(), true)?;
+            merged.insert(path, (mode, hash));
+        }
+    }
+
+    // one side's file can't share the working tree with the other side's
+    // directory, so it's left there under another name
+    let paths = merged
+        .keys()
+        .chain(conflicts.keys())
+        .cloned()
+        .collect::<BTreeSet<String>>();
+
+    for path in &paths {
+        let prefix = format!("{}/", path);
+        let in_directory = paths
+            .range(prefix.clone()..)
+            .take_while(|other| other.starts_with(&prefix))
+            .cloned()
+            .collect::<Vec<String>>();
+
+        if in_directory.is_empty() {
+            continue;
+        }
+
+        let (side, (mode, hash)) = match our_entries.get(path) {
+            Some(entry) => ("HEAD", entry),
+            None => (target.as_str(), &their_entries[path]),
+        };
+
+        let mut moved_to = format!("{}~{}", path, side);
+        for suffix in 0.. {
+            let file = PathBuf::from(format!("{}/{}", fs.current_directory(), moved_to));
+            if !paths.contains(&moved_to) && !fs.path_exists(&file) {
+                break;
+            }
+            moved_to = format!("{}~{}_{}", path, side, suffix);
+        }
+
+        output.push(format!(
+            "CONFLICT (file/directory): directory in the way of {} from {}; \
+             moving it to {} instead.",
+            path, side, moved_to
+        ));
+
+        merged.remove(path);
+        conflicts.insert(
+            path.clone(),
+            Conflict {
+                stages: [
+                    base_entries.get(path).cloned(),
+                    our_entries.get(path).cloned(),
+                    their_entries.get(path).cloned(),
+                ],
+                contents: Some((*mode, object::read(fs, hash)?.data)),
+                moved_to: Some(moved_to),
+            },
+        );
+
+        // what the other side has in the directory is staged as its own
+        for other in in_directory {
+            if let Some((mode, hash)) = merged.remove(&other) {
+                let stages = [
+                    base_entries.get(&other).cloned(),
+                    our_entries.get(&other).cloned(),
+                    their_entries.get(&other).cloned(),
+                ];
+                let contents = Some((mode, object::read(fs, &hash)?.data));
+                conflicts.insert(
+                    other,
+                    Conflict {
+                        stages,
+                        contents,
+                        moved_to: None,
+                    },
+                );
+            }
+        }
+    }
+
+    let message = if messages.is_empty() {
+        merge_message(fs, &target, &head_ref)?
+    } else {
+        messages.join("\n\n")
+    };
+
+    if !conflicts.is_empty() {
+        let conflicted_paths = conflicts
+            .keys()
+            .map(|path| format!("#\t{}\n", path))
+            .collect::<String>();
+
+        update_worktree(fs, &our_entries, &merged, conflicts)?;
+        refs::update(fs, "MERGE_HEAD", &theirs, true)?;
+
+        let merge_message_path = repository::path(fs).join("MERGE_MSG");
+        if !fs.path_exists(&merge_message_path) {
+            fs.create_file(&merge_message_path);
+        }
+        fs.write_file(
+            &merge_message_path,
+            format!("{}\n\n# Conflicts:\n{}", message, conflicted_paths).as_bytes(),
+        );
+
+        output.push("Automatic merge failed; fix conflicts and then commit the result.".into());
+
+        return Err(output.join("\n"));
+    }
+
+    update_worktree(fs, &our_entries, &merged, BTreeMap::new())?;
+
+    let tree = write_tree::execute(fs)?;
+    let hash = commit_tree::execute(
+        fs,
+        tree,
+        vec![head, theirs],
+        vec![message],
+        author,
+        committer,
+    )?;
+
+    refs::update(fs, "HEAD", &hash, false)?;
+
+    output.push("Merge made by the 'resolve' strategy.".to_string());
+
+    Ok(output.join("\n"))
+}
+
+/// Fails if the index has changes from `HEAD`, which a merge that isn't a
+/// fast-forward would have to make its commit from.
+fn check_index_matches(fs: &FileSystem, ours: &Entries) -> Result<(), String> {
+    let staged = index::read_entries(fs)?
+        .into_iter()
+        .map(|entry| {
+            (
+                String::from_utf8_lossy(&entry.path).into_owned(),
+                (u32::from(entry.mode), entry.hash.to_string()),
+            )
+        })
+        .collect::<Entries>();
+
+    let changed_paths = staged
+        .keys()
+        .chain(ours.keys())
+        .filter(|path| staged.get(*path) != ours.get(*path))
+        .map(|path| format!("\t{}", path))
+        .collect::<BTreeSet<String>>();
+
+    if changed_paths.is_empty() {
+        return Ok(());
+    }
+
+    Err(format!(
+        "error: Your local changes to the following files would be overwritten by merge:\n\
+         {}\n\
+         Please commit your changes or stash them before you merge.\n\
+         Aborting",
+        changed_paths
+            .into_iter()
+            .collect::<Vec<String>>()
+            .join("\n")
+    ))
+}
+
+/// The message git gives a merge commit, naming what was merged and, unless
+/// it's the main branch, what it was merged into.
+fn merge_message(fs: &FileSystem, target: &str, head_ref: &str) -> Result<String, String> {
+    let kind = match refs::dwim(fs, target)? {
+        Some((name, _)) if name.starts_with("refs/heads/") => "branch",
+        Some((name, _)) if name.starts_with("refs/tags/") => "tag",
+        Some((name, _)) if name.starts_with("refs/remotes/") => "remote-tracking branch",
+        _ => "commit",
+    };
+
+    let mut message = format!("Merge {} '{}'", kind, target);
+
+    if head_ref.starts_with("refs/heads/")
+        && head_ref != "refs/heads/master"
+        && head_ref != "refs/heads/main"
+    {
+        message.push_str(&format!(" into {}", refs::shorten(head_ref)));
+    }
+
+    Ok(message)
+}
+
+/// Lists `commit` and every commit it descends from.
+fn ancestors(fs: &FileSystem, commit: &str) -> Result<BTreeSet<String>, String> {
+    let mut ancestors = BTreeSet::new();
+    let mut queue = VecDeque::from([commit.to_string()]);
+
+    while let Some(commit) = queue.pop_front() {
+        if ancestors.insert(commit.clone()) {
+            queue.extend(Commit::read(fs, &commit)?.parents);
+        }
+    }
+
+    Ok(ancestors)
+}
+
+/// Finds the best common ancestor of two commits: one that isn't an ancestor
+/// of another common one. As in git, the histories are walked newest commit
+/// first, painting each commit with the sides it's reachable from and the
+/// ancestors of a common one as stale, until only stale commits are left.
+/// When there are several, as after criss-cross merges, the newest is used.
+pub fn merge_base(fs: &FileSystem, ours: &str, theirs: &str) -> Result<Option<String>, String> {
+    const OURS: u8 = 1;
+    const THEIRS: u8 = 2;
+    const STALE: u8 = 4;
+
+    if ours == theirs {
+        return Ok(Some(ours.to_string()));
+    }
+
+    let mut paint = HashMap::new();
+    let mut queue = BinaryHeap::new();
+
+    for (commit, side) in [(ours, OURS), (theirs, THEIRS)] {
+        let parsed = Commit::read(fs, commit)?;
+        paint.insert(commit.to_string(), side);
+        queue.push((
+            parsed.committer.timestamp,
+            commit.to_string(),
+            parsed.parents,
+        ));
+    }
+
+    let mut common = vec![];
+
+    while queue
+        .iter()
+        .any(|(_, commit, _)| paint[commit] & STALE == 0)
+    {
+        let (_, commit, parents) = queue.pop().unwrap();
+        let mut flags = paint[&commit];
+
+        if flags & (OURS | THEIRS) == OURS | THEIRS {
+            if flags & STALE == 0 && !common.contains(&commit) {
+                common.push(commit);
+            }
+            flags |= STALE;
+        }
+
+        for parent in parents {
+            let parent_flags = paint.entry(parent.clone()).or_insert(0);
+            if *parent_flags & flags == flags {
+                continue;
+            }
+            *parent_flags |= flags;
+
+            let parsed = Commit::read(fs, &parent)?;
+            queue.push((parsed.committer.timestamp, parent, parsed.parents));
+        }
+    }
+
+    // with clocks out of step, a common commit can be reached before one of
+    // its descendants
+    for candidate in &common {
+        let mut redundant = false;
+
+        for other in common.iter().filter(|other| *other != candidate) {
+            redundant |= ancestors(fs, other)?.contains(candidate);
+        }
+
+        if !redundant {
+            return Ok(Some(candidate.clone()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Takes the working tree and index from `ours` to the `merged` entries,
+/// leaving each conflict's stages in the index and its contents in the
+/// working tree, but nothing is done if a local change would be lost.
+fn update_worktree(
+    fs: &mut FileSystem,
+    ours: &Entries,
+    merged: &Entries,
+    conflicts: BTreeMap<String, Conflict>,
+) -> Result<(), String> {
+    let current_directory = fs.current_directory();
+    let worktree_path = |path: &str| PathBuf::from(format!("{}/{}", current_directory, path));
+
+    let mut index_entries = checkout::by_path_and_stage(index::read_entries(fs)?);
+
+    // their file moved out of the way of our directory leaves the path alone
+    let changed_paths = ours
+        .keys()
+        .chain(merged.keys())
+        .filter(|path| ours.get(*path) != merged.get(*path))
+        .chain(conflicts.iter().filter_map(|(path, conflict)| {
+            match (&conflict.moved_to, ours.contains_key(path)) {
+                (Some(_), false) => None,
+                _ => Some(path),
+            }
+        }))
+        .cloned()
+        .collect::<BTreeSet<String>>();
+
+    check_overwritten(
+        fs,
+        &changed_paths,
+        (ours, merged),
+        &index_entries,
+        ("merge", "merge"),
+    )?;
+
+    for path in changed_paths
+        .iter()
+        .filter(|path| !merged.contains_key(*path) && !conflicts.contains_key(*path))
+    {
+        let file = worktree_path(path);
+
+        if fs.path_exists(&file) {
+            fs.remove_file(&file);
+        }
+
+        checkout::remove_empty_parents(fs, &file, &current_directory);
+        index_entries.remove(&(path.as_bytes().to_vec(), 0));
+    }
+
+    for path in changed_paths
+        .iter()
+        .filter(|path| merged.contains_key(*path))
+    {
+        let (mode, hash) = &merged[path];
+        let file = worktree_path(path);
+
+        let blob = object::read(fs, hash)?;
+        write_worktree_file(fs, &file, &blob.data, *mode);
+
+        let entry = add::create_index_entry(fs, &file, path.as_bytes(), hash)?;
+        index_entries.insert((path.as_bytes().to_vec(), 0), entry);
+    }
+
+    for (path, conflict) in conflicts {
+        if let Some((mode, contents)) = &conflict.contents {
+            let file = match &conflict.moved_to {
+                Some(moved_to) => {
+                    // our file is where their directory goes
+                    let file = worktree_path(&path);
+                    if ours.contains_key(&path) && fs.path_exists(&file) {
+                        fs.remove_file(&file);
+                    }
+                    worktree_path(moved_to)
+                }
+                None => worktree_path(&path),
+            };
+            write_worktree_file(fs, &file, contents, *mode);
+        }
+
+        index_entries.remove(&(path.as_bytes().to_vec(), 0));
+
+        for (stage, version) in (1..).zip(conflict.stages) {
+            if let Some((mode, hash)) = version {
+                let entry = IndexEntry {
+                    mode: FileMode::from(mode),
+                    hash: ObjectId::from_hex(&hash)?,
+                    stage,
+                    path: path.as_bytes().to_vec(),
+                    ..Default::default()
+                };
+                index_entries.insert((path.as_bytes().to_vec(), stage), entry);
+            }
+        }
+    }
+
+    index::write_entries(fs, index_entries.into_values().collect());
+
+    Ok(())
+}
+
+#[cfg(test)]
+fn set_up_branches(
+    fs: &mut FileSystem,
+    ours: &[(&str, &str, u32)],
+    theirs: &[(&str, &str, u32)],
+) -> (String, String) {
+    use crate::object::commit::test_commit;
+    use crate::sub_commands::init;
+
+    init::execute(fs, None, false, None).unwrap();
+
+    let base = test_commit(
+        fs,
+        &[
+            ("README", "readme\n", 0o100644),
+            ("notes.txt", "one\ntwo\nthree\n", 0o100644),
+        ],
+        vec![],
+    );
+    let ours = if ours.is_empty() {
+        base.clone()
+    } else {
+        test_commit(fs, ours, vec![base.clone()])
+    };
+    let theirs = test_commit(fs, theirs, vec![base]);
+
+    let master = checkout::Head::Branch("refs/heads/master".to_string());
+    checkout::switch_to(fs, &ours, master).unwrap();
+    refs::update(fs, "refs/heads/master", &ours, false).unwrap();
+    refs::update(fs, "refs/heads/topic", &theirs, false).unwrap();
+
+    (ours, theirs)
+}
+
+#[cfg(test)]
+fn read_worktree_file(fs: &FileSystem, path: &str) -> String {
+    fs.get_file_contents(&PathBuf::from(format!(
+        "{}/{}",
+        fs.current_directory(),
+        path
+    )))
+    .unwrap()
+}
+
+#[test]
+fn test_execute_fast_forward() {
+    use crate::object::commit::test_signature;
+    let mut fs = FileSystem::access();
+
+    let (ours, theirs) = set_up_branches(
+        &mut fs,
+        &[],
+        &[
+            ("README", "readme\n", 0o100644),
+            ("notes.txt", "one\n2\nthree\n", 0o100644),
+        ],
+    );
+
+    let merge = |fs: &mut FileSystem| {
+        execute(
+            fs,
+            "topic".to_string(),
+            false,
+            vec![],
+            test_signature(),
+            test_signature(),
+        )
+    };
+
+    assert_eq!(
+        merge(&mut fs).unwrap(),
+        format!("Updating {}..{}\nFast-forward", &ours[..7], &theirs[..7])
+    );
+    assert_eq!(
+        refs::resolve(&fs, "refs/heads/master").unwrap(),
+        Some(theirs)
+    );
+    assert_eq!(read_worktree_file(&fs, "notes.txt"), "one\n2\nthree\n");
+
+    assert_eq!(merge(&mut fs).unwrap(), "Already up to date.");
+}
+
+#[test]
+fn test_execute_three_way() {
+    use crate::object::commit::test_signature;
+    let mut fs = FileSystem::access();
+
+    let (ours, theirs) = set_up_branches(
+        &mut fs,
+        &[
+            ("README", "readme\n", 0o100644),
+            ("notes.txt", "ONE\ntwo\nthree\n", 0o100644),
+        ],
+        &[
+            ("README", "readme\n", 0o100644),
+            ("notes.txt", "one\ntwo\nTHREE\n", 0o100644),
+            ("src/main.rs", "fn main() {}\n", 0o100644),
+        ],
+    );
+
+    assert_eq!(
+        execute(
+            &mut fs,
+            "topic".to_string(),
+            false,
+            vec![],
+            test_signature(),
+            test_signature(),
+        )
+        .unwrap(),
+        "Auto-merging notes.txt\nMerge made by the 'resolve' strategy."
+    );
+
+    let head = refs::resolve(&fs, "HEAD").unwrap().unwrap();
+    let commit = Commit::read(&fs, &head).unwrap();
+
+    assert_eq!(commit.parents, vec![ours, theirs]);
+    assert_eq!(commit.message, "Merge branch 'topic'\n");
+    assert_eq!(read_worktree_file(&fs, "notes.txt"), "ONE\ntwo\nTHREE\n");
+    assert_eq!(read_worktree_file(&fs, "src/main.rs"), "fn main() {}\n");
+}
+
+#[test]
+fn test_execute_with_conflicts() {
+    use crate::object::commit::test_signature;
+    use crate::sub_commands::{commit, ls_files};
+    let mut fs = FileSystem::access();
+
+    let (ours, theirs) = set_up_branches(
+        &mut fs,
+        &[
+            ("README", "readme\n", 0o100644),
+            ("notes.txt", "one\nours\nthree\n", 0o100644),
+        ],
+        &[
+            ("README", "readme\n", 0o100644),
+            ("notes.txt", "one\ntheirs\nthree\n", 0o100644),
+        ],
+    );
+
+    let merge = |fs: &mut FileSystem| {
+        execute(
+            fs,
+            "topic".to_string(),
+            false,
+            vec![],
+            test_signature(),
+            test_signature(),
+        )
+    };
+
+    assert_eq!(
+        merge(&mut fs).unwrap_err(),
+        "Auto-merging notes.txt\n\
+         CONFLICT (content): Merge conflict in notes.txt\n\
+         Automatic merge failed; fix conflicts and then commit the result."
+    );
+    assert_eq!(
+        read_worktree_file(&fs, "notes.txt"),
+        "one\n<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> topic\nthree\n"
+    );
+    assert_eq!(
+        ls_files::execute(&fs, false, true)
+            .unwrap()
+            .lines()
+            .map(|line| line.split_once('\t').unwrap().0.ends_with(" 2"))
+            .collect::<Vec<bool>>(),
+        vec![false, true, false]
+    );
+    assert_eq!(
+        refs::resolve(&fs, "MERGE_HEAD").unwrap(),
+        Some(theirs.clone())
+    );
+    assert!(merge(&mut fs).unwrap_err().contains("MERGE_HEAD exists"));
+
+    // resolving the conflict and committing concludes the merge
+    let file = PathBuf::from(format!("{}/notes.txt", fs.current_directory()));
+    fs.write_file(&file, b"one\nboth\nthree\n");
+    add::execute(&mut fs, vec![PathBuf::from("notes.txt")]).unwrap();
+    commit::execute(&mut fs, vec![], test_signature(), test_signature()).unwrap();
+
+    let head = refs::resolve(&fs, "HEAD").unwrap().unwrap();
+    let commit = Commit::read(&fs, &head).unwrap();
+
+    assert_eq!(commit.parents, vec![ours, theirs]);
+    assert_eq!(commit.message, "Merge branch 'topic'\n");
+    assert_eq!(refs::resolve(&fs, "MERGE_HEAD").unwrap(), None);
+}
+
+#[test]
+fn test_execute_with_unresolved_index() {
+    use crate::object::commit::test_signature;
+    let mut fs = FileSystem::access();
+
+    let (ours, _) = set_up_branches(
+        &mut fs,
+        &[],
+        &[
+            ("README", "readme\n", 0o100644),
+            ("notes.txt", "one\n2\nthree\n", 0o100644),
+        ],
+    );
+
+    let mut entries = index::read_entries(&fs).unwrap();
+    let stages = entries
+        .iter()
+        .filter(|entry| entry.path == b"notes.txt")
+        .flat_map(|entry| {
+            (1..=3).map(move |stage| IndexEntry {
+                stage,
+                ..entry.clone()
+            })
+        })
+        .collect::<Vec<IndexEntry>>();
+    entries.retain(|entry| entry.path != b"notes.txt");
+    entries.extend(stages);
+    index::write_entries(&mut fs, entries.clone());
+
+    assert_eq!(
+        execute(
+            &mut fs,
+            "topic".to_string(),
+            false,
+            vec![],
+            test_signature(),
+            test_signature(),
+        )
+        .unwrap_err(),
+        "notes.txt: needs merge\n\
+         error: you need to resolve your current index first"
+    );
+    assert_eq!(refs::resolve(&fs, "HEAD").unwrap(), Some(ours));
+    assert_eq!(index::read_entries(&fs).unwrap(), entries);
+}
+
+#[test]
+fn test_execute_with_staged_changes() {
+    use crate::object::commit::test_signature;
+    let mut fs = FileSystem::access();
+
+    let (ours, _) = set_up_branches(
+        &mut fs,
+        &[
+            ("README", "readme\n", 0o100644),
+            ("notes.txt", "ONE\ntwo\nthree\n", 0o100644),
+        ],
+        &[
+            ("README", "readme\n", 0o100644),
+            ("notes.txt", "one\ntwo\nTHREE\n", 0o100644),
+        ],
+    );
+
+    // a change to a file the merge doesn't touch is still in the way
+    let file = PathBuf::from(format!("{}/README", fs.current_directory()));
+    fs.write_file(&file, b"staged\n");
+    add::execute(&mut fs, vec![PathBuf::from("README")]).unwrap();
+    let entries = index::read_entries(&fs).unwrap();
+
+    assert_eq!(
+        execute(
+            &mut fs,
+            "topic".to_string(),
+            false,
+            vec![],
+            test_signature(),
+            test_signature(),
+        )
+        .unwrap_err(),
+        "error: Your local changes to the following files would be overwritten by merge:\n\
+         \tREADME\n\
+         Please commit your changes or stash them before you merge.\n\
+         Aborting"
+    );
+    assert_eq!(refs::resolve(&fs, "HEAD").unwrap(), Some(ours));
+    assert_eq!(index::read_entries(&fs).unwrap(), entries);
+    assert_eq!(read_worktree_file(&fs, "notes.txt"), "ONE\ntwo\nthree\n");
+}
+
+#[test]
+fn test_execute_with_file_directory_conflict() {
+    use crate::object::commit::test_signature;
+    use crate::sub_commands::ls_files;
+    let mut fs = FileSystem::access();
+
+    set_up_branches(
+        &mut fs,
+        &[
+            ("README", "readme\n", 0o100644),
+            ("notes.txt", "one\ntwo\nthree\n", 0o100644),
+            ("lib", "a file\n", 0o100644),
+        ],
+        &[
+            ("README", "readme\n", 0o100644),
+            ("notes.txt", "one\ntwo\nthree\n", 0o100644),
+            ("lib/mod.rs", "in a directory\n", 0o100644),
+        ],
+    );
+
+    assert_eq!(
+        execute(
+            &mut fs,
+            "topic".to_string(),
+            false,
+            vec![],
+            test_signature(),
+            test_signature(),
+        )
+        .unwrap_err(),
+        "CONFLICT (file/directory): directory in the way of lib from HEAD; \
+         moving it to lib~HEAD instead.\n\
+         Automatic merge failed; fix conflicts and then commit the result."
+    );
+    assert_eq!(read_worktree_file(&fs, "lib~HEAD"), "a file\n");
+    assert_eq!(read_worktree_file(&fs, "lib/mod.rs"), "in a directory\n");
+    assert_eq!(
+        ls_files::execute(&fs, false, true)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let (info, path) = line.split_once('\t').unwrap();
+                format!("{} {}", &info[info.len() - 1..], path)
+            })
+            .collect::<Vec<String>>(),
+        vec!["2 lib", "3 lib/mod.rs"]
+    );
+}
+
+#[test]
+fn test_merge_base() {
+    use crate::object::commit::test_commit;
+    use crate::sub_commands::init;
+    let mut fs = FileSystem::access();
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    let mut commit =
+        |name: &str, parents: Vec<String>| test_commit(&mut fs, &[(name, name, 0o100644)], parents);
+
+    let base = commit("base", vec![]);
+    let ours = commit("ours", vec![base.clone()]);
+    let theirs = commit("theirs", vec![base.clone()]);
+    let merged = commit("merged", vec![ours.clone(), theirs.clone()]);
+    let later = commit("later", vec![theirs.clone()]);
+    let unrelated = commit("unrelated", vec![]);
+
+    let merge_base = |ours: &str, theirs: &str| merge_base(&fs, ours, theirs).unwrap();
+
+    assert_eq!(merge_base(&ours, &theirs), Some(base.clone()));
+    assert_eq!(merge_base(&base, &merged), Some(base));
+    assert_eq!(merge_base(&merged, &theirs), Some(theirs.clone()));
+    // what was merged before is the better base, not the fork point
+    assert_eq!(merge_base(&merged, &later), Some(theirs));
+    assert_eq!(merge_base(&merged, &unrelated), None);
+}
@@ -1,17 +1,43 @@
 use crate::fs::{FileSystem, Fs};
+use crate::object::commit::Signature;
+use crate::repository::{Repository, DIRECTORY_VARIABLE};
+use std::io::Write;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
 pub mod add;
 pub mod cat_file;
+pub mod checkout;
+pub mod commit;
+pub mod commit_tree;
+pub mod diff;
+pub mod fsck;
+pub mod gc;
 pub mod hash_object;
 pub mod init;
+pub mod log;
 pub mod ls_files;
+pub mod ls_tree;
+pub mod merge;
+pub mod read_tree;
+pub mod rev_parse;
+pub mod show_ref;
+pub mod status;
+pub mod switch;
+pub mod symbolic_ref;
+pub mod update_index;
+pub mod update_ref;
 pub mod write_tree;
 
 #[derive(StructOpt, Debug)]
 pub enum SubCommand {
-    Init,
+    Init {
+        #[structopt(long)]
+        bare: bool,
+        #[structopt(short = "b", long)]
+        initial_branch: Option<String>,
+        directory: Option<PathBuf>,
+    },
     HashObject {
         file_name: PathBuf,
         #[structopt(short, long)]
@@ -23,46 +49,338 @@ pub enum SubCommand {
     LsFiles {
         #[structopt(short, long)]
         stage: bool,
+        #[structopt(short, long)]
+        unmerged: bool,
     },
     Add {
+        #[structopt(parse(from_os_str))]
         files: Vec<PathBuf>,
     },
     WriteTree,
+    ReadTree {
+        #[structopt(long)]
+        prefix: Option<String>,
+        tree_ish: String,
+    },
+    LsTree {
+        #[structopt(short = "r")]
+        recursive: bool,
+        #[structopt(short = "t")]
+        show_trees: bool,
+        #[structopt(long)]
+        name_only: bool,
+        tree_ish: String,
+    },
+    CommitTree {
+        tree: String,
+        #[structopt(short = "p", number_of_values = 1)]
+        parents: Vec<String>,
+        #[structopt(short = "m", required = true, number_of_values = 1)]
+        messages: Vec<String>,
+    },
+    Commit {
+        #[structopt(short = "m", long = "message", number_of_values = 1)]
+        messages: Vec<String>,
+    },
+    Merge {
+        #[structopt(long)]
+        no_ff: bool,
+        #[structopt(short = "m", number_of_values = 1)]
+        messages: Vec<String>,
+        branch: String,
+    },
+    Checkout {
+        #[structopt(short = "b")]
+        new_branch: Option<String>,
+        target: Option<String>,
+    },
+    Switch {
+        #[structopt(short = "c", long = "create")]
+        new_branch: Option<String>,
+        #[structopt(long)]
+        detach: bool,
+        target: Option<String>,
+    },
+    Status {
+        #[structopt(long)]
+        porcelain: bool,
+    },
+    Diff {
+        #[structopt(long, alias = "staged")]
+        cached: bool,
+        #[structopt(short = "U", long = "unified", default_value = "3")]
+        context: usize,
+        #[structopt(long = "diff-algorithm", default_value = "myers")]
+        algorithm: crate::diff::Algorithm,
+        commits: Vec<String>,
+    },
+    Fsck,
+    Gc {
+        #[structopt(long, default_value = "2.weeks.ago")]
+        prune: String,
+        #[structopt(long)]
+        no_prune: bool,
+    },
+    Log {
+        revision: Option<String>,
+        #[structopt(long)]
+        oneline: bool,
+        #[structopt(short = "n", long = "max-count")]
+        max_count: Option<usize>,
+        #[structopt(long)]
+        topo_order: bool,
+        #[structopt(long, conflicts_with = "topo-order")]
+        date_order: bool,
+    },
+    RevParse {
+        #[structopt(long)]
+        verify: bool,
+        revisions: Vec<String>,
+    },
+    UpdateIndex {
+        #[structopt(long)]
+        index_version: Option<u32>,
+    },
+    UpdateRef {
+        #[structopt(short = "d")]
+        delete: bool,
+        #[structopt(long)]
+        no_deref: bool,
+        name: String,
+        new_value: Option<String>,
+        old_value: Option<String>,
+    },
+    SymbolicRef {
+        #[structopt(short = "d", long)]
+        delete: bool,
+        #[structopt(long)]
+        short: bool,
+        name: String,
+        target: Option<String>,
+    },
+    ShowRef {
+        #[structopt(long)]
+        head: bool,
+        #[structopt(long)]
+        heads: bool,
+        #[structopt(long)]
+        tags: bool,
+        #[structopt(short = "s", long = "hash")]
+        hash_only: bool,
+        patterns: Vec<String>,
+    },
 }
 
 #[derive(StructOpt, Debug)]
 pub enum CatFile {
-    Blob { file_name: String },
-    Tree { file_name: String },
-    Type { file_name: String },
+    Blob {
+        file_name: String,
+    },
+    Tree {
+        file_name: String,
+    },
+    Commit {
+        file_name: String,
+    },
+    Tag {
+        file_name: String,
+    },
+    Type {
+        file_name: String,
+    },
+    Size {
+        file_name: String,
+    },
+    Pretty {
+        file_name: String,
+    },
+    /// Reads object names from stdin, printing each object
+    Batch,
+    /// Reads object names from stdin, printing only their type and size
+    BatchCheck,
+}
+
+/// Writes object contents straight to stdout, as they may not be valid UTF-8.
+fn write_raw(contents: &[u8]) -> Result<String, String> {
+    std::io::stdout()
+        .write_all(contents)
+        .map_err(|err| format!("fatal: unable to write to stdout: {}", err))?;
+
+    Ok("".to_string())
 }
 
 impl SubCommand {
     pub fn execute(self) -> Result<String, String> {
         let mut fs = FileSystem::access();
 
+        // commands run from the root of the worktree, except for `init`,
+        // which creates a repository right where it is run
+        let repository = match &self {
+            Self::Init { .. } => None,
+            Self::HashObject { write: false, .. } => Repository::discover(&fs).ok(),
+            _ => Some(Repository::discover(&fs)?),
+        }
+        .unwrap_or_else(|| Repository {
+            worktree: fs.current_directory().into(),
+            prefix: PathBuf::new(),
+            git_dir: None,
+        });
+
+        // a bare repository is handed down the way `--git-dir` is
+        if let Some(git_dir) = &repository.git_dir {
+            std::env::set_var(DIRECTORY_VARIABLE, git_dir);
+        }
+
+        fs.set_current_directory(&repository.worktree);
+
         match self {
-            Self::Init => init::execute(&mut fs),
+            Self::Init {
+                bare,
+                initial_branch,
+                directory,
+            } => init::execute(&mut fs, directory, bare, initial_branch),
             Self::HashObject {
                 file_name,
                 object_type,
                 write,
             } => {
-                let contents = fs.get_file_contents_as_bytes(&file_name)?;
+                let contents = fs.get_file_contents_as_bytes(&repository.prefix.join(file_name))?;
                 hash_object::execute(&mut fs, &contents, object_type, write)
             }
-            Self::CatFile(CatFile::Blob { file_name }) => {
-                cat_file::execute(&fs, "blob".to_string(), file_name)
+            Self::CatFile(CatFile::Batch) => {
+                let stdin = std::io::stdin();
+                let stdout = std::io::stdout();
+
+                cat_file::execute_batch(&fs, stdin.lock(), &mut stdout.lock(), false)?;
+
+                Ok("".to_string())
+            }
+            Self::CatFile(CatFile::BatchCheck) => {
+                let stdin = std::io::stdin();
+                let stdout = std::io::stdout();
+
+                cat_file::execute_batch(&fs, stdin.lock(), &mut stdout.lock(), true)?;
+
+                Ok("".to_string())
             }
-            Self::CatFile(CatFile::Tree { file_name }) => {
-                cat_file::execute(&fs, "tree".to_string(), file_name)
+            Self::CatFile(cat_file) => {
+                let (flag, file_name) = match cat_file {
+                    CatFile::Blob { file_name } => ("blob", file_name),
+                    CatFile::Tree { file_name } => ("tree", file_name),
+                    CatFile::Commit { file_name } => ("commit", file_name),
+                    CatFile::Tag { file_name } => ("tag", file_name),
+                    CatFile::Type { file_name } => ("-t", file_name),
+                    CatFile::Size { file_name } => ("-s", file_name),
+                    CatFile::Pretty { file_name } => ("-p", file_name),
+                    CatFile::Batch | CatFile::BatchCheck => unreachable!(),
+                };
+
+                let contents = cat_file::execute(&fs, flag.to_string(), file_name)?;
+
+                match flag {
+                    "-t" | "-s" => Ok(String::from_utf8(contents).unwrap()),
+                    _ => write_raw(&contents),
+                }
             }
-            Self::CatFile(CatFile::Type { file_name }) => {
-                cat_file::execute(&fs, "-t".to_string(), file_name)
+            Self::LsFiles { stage, unmerged } => ls_files::execute(&fs, stage, unmerged),
+            Self::Add { files } => {
+                let files = files
+                    .iter()
+                    .map(|file| repository.worktree_path(file))
+                    .collect::<Result<Vec<PathBuf>, String>>()?;
+                add::execute(&mut fs, files)
             }
-            Self::LsFiles { stage } => ls_files::execute(&fs, stage),
-            Self::Add { files } => add::execute(&mut fs, files),
             Self::WriteTree => write_tree::execute(&mut fs),
+            Self::ReadTree { prefix, tree_ish } => read_tree::execute(&mut fs, tree_ish, prefix),
+            Self::LsTree {
+                recursive,
+                show_trees,
+                name_only,
+                tree_ish,
+            } => ls_tree::execute(&fs, tree_ish, recursive, show_trees, name_only),
+            Self::CommitTree {
+                tree,
+                parents,
+                messages,
+            } => {
+                let author = Signature::from_env("AUTHOR")?;
+                let committer = Signature::from_env("COMMITTER")?;
+                commit_tree::execute(&mut fs, tree, parents, messages, author, committer)
+            }
+            Self::Commit { messages } => {
+                let author = Signature::from_env("AUTHOR")?;
+                let committer = Signature::from_env("COMMITTER")?;
+                commit::execute(&mut fs, messages, author, committer)
+            }
+            Self::Merge {
+                no_ff,
+                messages,
+                branch,
+            } => {
+                let author = Signature::from_env("AUTHOR")?;
+                let committer = Signature::from_env("COMMITTER")?;
+                merge::execute(&mut fs, branch, no_ff, messages, author, committer)
+            }
+            Self::Checkout { new_branch, target } => checkout::execute(&mut fs, target, new_branch),
+            Self::Switch {
+                new_branch,
+                detach,
+                target,
+            } => switch::execute(&mut fs, target, new_branch, detach),
+            Self::Status { porcelain } => status::execute(&fs, porcelain),
+            Self::Diff {
+                cached,
+                context,
+                algorithm,
+                commits,
+            } => diff::execute(&fs, commits, cached, context, algorithm),
+            Self::Fsck => fsck::execute(&fs),
+            Self::Gc { prune, no_prune } => {
+                let prune_before = if no_prune {
+                    None
+                } else {
+                    gc::parse_expiry(&prune, chrono::Utc::now().timestamp())?
+                };
+                gc::execute(&mut fs, prune_before)
+            }
+            Self::Log {
+                revision,
+                oneline,
+                max_count,
+                topo_order,
+                date_order,
+            } => {
+                let order = if topo_order {
+                    log::Order::Topo
+                } else if date_order {
+                    log::Order::Date
+                } else {
+                    log::Order::Default
+                };
+                log::execute(&fs, revision, oneline, max_count, order)
+            }
+            Self::RevParse { verify, revisions } => rev_parse::execute(&fs, revisions, verify),
+            Self::UpdateIndex { index_version } => update_index::execute(&mut fs, index_version),
+            Self::UpdateRef {
+                delete,
+                no_deref,
+                name,
+                new_value,
+                old_value,
+            } => update_ref::execute(&mut fs, name, new_value, old_value, delete, no_deref),
+            Self::SymbolicRef {
+                delete,
+                short,
+                name,
+                target,
+            } => symbolic_ref::execute(&mut fs, name, target, short, delete),
+            Self::ShowRef {
+                head,
+                heads,
+                tags,
+                hash_only,
+                patterns,
+            } => show_ref::execute(&fs, patterns, head, heads, tags, hash_only),
         }
     }
 }
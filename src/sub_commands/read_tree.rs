@@ -0,0 +1,117 @@
+use crate::fs::FileSystem;
+use crate::index::{self, FileMode, IndexEntry};
+use crate::object::tree::Tree;
+use crate::object::{self, ObjectId};
+
+pub fn execute(
+    fs: &mut FileSystem,
+    tree_ish: String,
+    prefix: Option<String>,
+) -> Result<String, String> {
+    let tree = object::peel_to_tree(fs, &tree_ish)?;
+
+    let prefix = match prefix {
+        Some(prefix) if !prefix.is_empty() && !prefix.ends_with('/') => format!("{}/", prefix),
+        Some(prefix) => prefix,
+        None => "".to_string(),
+    };
+
+    // without a prefix the tree replaces the whole index, with one it is
+    // grafted next to what is already there
+    let mut entries = if !prefix.is_empty() {
+        index::read_entries(fs)?
+    } else {
+        vec![]
+    };
+
+    let tree_entries = Tree::read_recursive(fs, &tree, &prefix)?;
+
+    if !prefix.is_empty() {
+        // a file can't stay where the prefix or one of its parents would be
+        // a directory
+        if let Some(existing) = entries.iter().find(|entry| {
+            prefix
+                .match_indices('/')
+                .any(|(end, _)| entry.path == prefix.as_bytes()[..end])
+        }) {
+            return Err(format!(
+                "error: Entry '{}' would be overwritten by merge. Cannot merge.",
+                String::from_utf8_lossy(&existing.path)
+            ));
+        }
+
+        if let Some(existing) = entries
+            .iter()
+            .find(|entry| entry.path.starts_with(prefix.as_bytes()))
+        {
+            return Err(format!(
+                "error: Entry '{}' overlaps with '{}'.  Cannot bind.",
+                String::from_utf8_lossy(&existing.path),
+                prefix
+            ));
+        }
+    }
+
+    for tree_entry in tree_entries {
+        entries.push(IndexEntry {
+            mode: FileMode::from(tree_entry.mode),
+            hash: ObjectId::from_hex(&tree_entry.hash)?,
+            path: tree_entry.name.into_bytes(),
+            ..Default::default()
+        });
+    }
+
+    index::write_entries(fs, entries);
+
+    Ok("".to_string())
+}
+
+#[test]
+fn test_execute_replaces_index() {
+    use crate::fs::Fs;
+    use crate::sub_commands::{ls_files, write_tree};
+    let mut fs = FileSystem::access();
+
+    let tree = crate::object::tree::write_test_tree(&mut fs);
+
+    execute(&mut fs, tree.clone(), None).unwrap();
+
+    assert_eq!(
+        ls_files::execute(&fs, true, false).unwrap(),
+        "100755 8178c76d627cade75005b40711b92f4177bc6cfc 0\tREADME\n\
+         100644 f328e4d9d04c31d0d70d16d21a07d1613be9d577 0\tsrc/main.rs"
+    );
+
+    // reading the tree back and writing it again gives the same tree
+    assert_eq!(write_tree::execute(&mut fs).unwrap(), tree);
+}
+
+#[test]
+fn test_execute_with_prefix() {
+    use crate::fs::Fs;
+    use crate::sub_commands::ls_files;
+    let mut fs = FileSystem::access();
+
+    let tree = crate::object::tree::write_test_tree(&mut fs);
+
+    execute(&mut fs, tree.clone(), None).unwrap();
+    execute(&mut fs, tree.clone(), Some("vendor/lib".to_string())).unwrap();
+
+    assert_eq!(
+        ls_files::execute(&fs, false, false).unwrap(),
+        "README\nsrc/main.rs\nvendor/lib/README\nvendor/lib/src/main.rs"
+    );
+
+    assert_eq!(
+        execute(&mut fs, tree.clone(), Some("vendor/lib/".to_string())).unwrap_err(),
+        "error: Entry 'vendor/lib/README' overlaps with 'vendor/lib/'.  Cannot bind."
+    );
+
+    // a tracked file is in the way of the prefix, or of a directory above it
+    for prefix in ["README", "README/nested/"] {
+        assert_eq!(
+            execute(&mut fs, tree.clone(), Some(prefix.to_string())).unwrap_err(),
+            "error: Entry 'README' would be overwritten by merge. Cannot merge."
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use crate::fs::FileSystem;
+use crate::revision;
+
+pub fn execute(fs: &FileSystem, revisions: Vec<String>, verify: bool) -> Result<String, String> {
+    if verify {
+        // anything but exactly one valid revision is reported the same way
+        let needed = || "fatal: Needed a single revision".to_string();
+
+        return match &revisions[..] {
+            [name] => revision::resolve(fs, name).map_err(|_| needed()),
+            _ => Err(needed()),
+        };
+    }
+
+    let hashes = revisions
+        .iter()
+        .map(|name| revision::resolve(fs, name))
+        .collect::<Result<Vec<String>, String>>()?;
+
+    Ok(hashes.join("\n"))
+}
+
+#[test]
+fn test_execute() {
+    use crate::fs::Fs;
+    use crate::object::commit::test_commit;
+    use crate::refs;
+    let mut fs = FileSystem::access();
+
+    let first = test_commit(&mut fs, &[("file.txt", "first\n", 0o100644)], vec![]);
+    let second = test_commit(
+        &mut fs,
+        &[("file.txt", "second\n", 0o100644)],
+        vec![first.clone()],
+    );
+    refs::update(&mut fs, "refs/heads/master", &second, false).unwrap();
+
+    assert_eq!(
+        execute(&fs, vec!["master".into(), "master~".into()], false),
+        Ok(format!("{}\n{}", second, first))
+    );
+    assert_eq!(
+        execute(&fs, vec!["master~2".into()], false),
+        Err(
+            "fatal: ambiguous argument 'master~2': unknown revision or path not in the working tree."
+                .to_string()
+        )
+    );
+    assert_eq!(
+        execute(&fs, vec!["master~2".into()], true),
+        Err("fatal: Needed a single revision".to_string())
+    );
+    assert_eq!(
+        execute(&fs, vec!["master".into(), "master~".into()], true),
+        Err("fatal: Needed a single revision".to_string())
+    );
+}
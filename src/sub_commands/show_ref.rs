@@ -0,0 +1,82 @@
+use crate::fs::FileSystem;
+use crate::refs;
+
+pub fn execute(
+    fs: &FileSystem,
+    patterns: Vec<String>,
+    head: bool,
+    heads: bool,
+    tags: bool,
+    hash_only: bool,
+) -> Result<String, String> {
+    let mut refs = refs::list(fs)?;
+
+    if head {
+        if let Some(hash) = refs::resolve(fs, "HEAD")? {
+            refs.insert(0, ("HEAD".to_string(), hash));
+        }
+    }
+
+    let output = refs
+        .into_iter()
+        .filter(|(name, _)| {
+            (!heads && !tags)
+                || name == "HEAD"
+                || (heads && name.starts_with("refs/heads/"))
+                || (tags && name.starts_with("refs/tags/"))
+        })
+        .filter(|(name, _)| {
+            // a pattern matches whole trailing components, so `master`
+            // matches `refs/heads/master` but not `refs/heads/not-master`
+            patterns.is_empty()
+                || name == "HEAD"
+                || patterns
+                    .iter()
+                    .any(|pattern| name == pattern || name.ends_with(&format!("/{}", pattern)))
+        })
+        .map(|(name, hash)| {
+            if hash_only {
+                hash
+            } else {
+                format!("{} {}", hash, name)
+            }
+        })
+        .collect::<Vec<String>>();
+
+    Ok(output.join("\n"))
+}
+
+#[test]
+fn test_execute() {
+    use crate::fs::Fs;
+    use crate::sub_commands::init;
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    let hash1 = "3a57a4f328d9448ce1e3ee4913712c63782dcaf2";
+    let hash2 = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+    refs::update(&mut fs, "refs/heads/master", hash1, false).unwrap();
+    refs::update(&mut fs, "refs/heads/feature/master", hash2, false).unwrap();
+    refs::update(&mut fs, "refs/tags/v1.0", hash2, false).unwrap();
+
+    assert_eq!(
+        execute(&fs, vec![], false, false, false, false).unwrap(),
+        format!(
+            "{0} refs/heads/feature/master\n{1} refs/heads/master\n{0} refs/tags/v1.0",
+            hash2, hash1
+        )
+    );
+    assert_eq!(
+        execute(&fs, vec!["master".to_string()], true, false, false, false).unwrap(),
+        format!(
+            "{1} HEAD\n{0} refs/heads/feature/master\n{1} refs/heads/master",
+            hash2, hash1
+        )
+    );
+    assert_eq!(
+        execute(&fs, vec![], false, false, true, true).unwrap(),
+        hash2
+    );
+}
@@ -0,0 +1,547 @@
+use crate::fs::{FileSystem, Fs};
+use crate::index::{self, IndexEntry};
+use crate::refs;
+use crate::repository;
+use crate::sub_commands::checkout;
+use std::collections::{BTreeMap, BTreeSet};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl Change {
+    fn short(self) -> char {
+        match self {
+            Self::Added => 'A',
+            Self::Modified => 'M',
+            Self::Deleted => 'D',
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Added => "new file:",
+            Self::Modified => "modified:",
+            Self::Deleted => "deleted:",
+        }
+    }
+}
+
+/// The short and long names git gives a conflict, by which of the base, our
+/// and their version made it into the index.
+fn conflict_names(stages: [bool; 3]) -> (&'static str, &'static str) {
+    match stages {
+        [true, false, false] => ("DD", "both deleted:"),
+        [false, true, false] => ("AU", "added by us:"),
+        [true, true, false] => ("UD", "deleted by them:"),
+        [false, false, true] => ("UA", "added by them:"),
+        [true, false, true] => ("DU", "deleted by us:"),
+        [false, true, true] => ("AA", "both added:"),
+        _ => ("UU", "both modified:"),
+    }
+}
+
+struct Status {
+    staged: BTreeMap<String, Change>,
+    unstaged: BTreeMap<String, Change>,
+    /// The paths with conflicts, and which stages they have.
+    unmerged: BTreeMap<String, [bool; 3]>,
+    untracked: BTreeSet<String>,
+}
+
+pub fn execute(fs: &FileSystem, porcelain: bool) -> Result<String, String> {
+    let (head_ref, head) = refs::resolve_name(fs, "HEAD")?;
+    let (entries, conflicts): (Vec<IndexEntry>, Vec<IndexEntry>) = index::read_entries(fs)?
+        .into_iter()
+        .partition(|entry| entry.stage == 0);
+
+    let mut unmerged = BTreeMap::new();
+
+    for entry in &conflicts {
+        let path = String::from_utf8_lossy(&entry.path).into_owned();
+        let stages: &mut [bool; 3] = unmerged.entry(path).or_default();
+        stages[entry.stage as usize - 1] = true;
+    }
+
+    let status = Status {
+        staged: staged_changes(fs, head.as_deref(), &entries, &unmerged)?,
+        unstaged: unstaged_changes(fs, &entries)?,
+        untracked: untracked_files(fs, &[entries, conflicts].concat()),
+        unmerged,
+    };
+
+    if porcelain {
+        return Ok(format_porcelain(&status));
+    }
+
+    let mut output = vec![];
+
+    match &head {
+        _ if head_ref != "HEAD" => output.push(format!("On branch {}", refs::shorten(&head_ref))),
+        Some(head) => output.push(format!("HEAD detached at {}", &head[..7])),
+        None => output.push("Not currently on any branch.".to_string()),
+    }
+
+    if head.is_none() {
+        output.push("\nNo commits yet".to_string());
+    }
+
+    let sections = [
+        ("Changes to be committed:", &status.staged),
+        ("Changes not staged for commit:", &status.unstaged),
+    ];
+
+    for (i, (title, changes)) in sections.iter().enumerate() {
+        // conflicts go between what is staged and what isn't
+        if i == 1 && !status.unmerged.is_empty() {
+            output.push("\nUnmerged paths:".to_string());
+
+            for (path, stages) in &status.unmerged {
+                output.push(format!("\t{:<17}{}", conflict_names(*stages).1, path));
+            }
+        }
+
+        if changes.is_empty() {
+            continue;
+        }
+
+        output.push(format!("\n{}", title));
+
+        for (path, change) in changes.iter() {
+            output.push(format!("\t{:<12}{}", change.label(), path));
+        }
+    }
+
+    if !status.untracked.is_empty() {
+        output.push("\nUntracked files:".to_string());
+
+        for path in &status.untracked {
+            output.push(format!("\t{}", path));
+        }
+    }
+
+    let summary = if !status.staged.is_empty() {
+        None
+    } else if !status.unstaged.is_empty() || !status.unmerged.is_empty() {
+        Some("no changes added to commit")
+    } else if !status.untracked.is_empty() {
+        Some("nothing added to commit but untracked files present")
+    } else if head.is_none() {
+        Some("nothing to commit (create/copy files and use \"papyrus add\" to track)")
+    } else {
+        Some("nothing to commit, working tree clean")
+    };
+
+    if let Some(summary) = summary {
+        output.push(format!("\n{}", summary));
+    }
+
+    Ok(output.join("\n"))
+}
+
+fn format_porcelain(status: &Status) -> String {
+    let paths = status
+        .staged
+        .keys()
+        .chain(status.unstaged.keys())
+        .chain(status.unmerged.keys())
+        .collect::<BTreeSet<&String>>();
+
+    let short = |change: Option<&Change>| change.map(|change| change.short()).unwrap_or(' ');
+
+    paths
+        .into_iter()
+        .map(|path| match status.unmerged.get(path) {
+            Some(stages) => format!("{} {}", conflict_names(*stages).0, path),
+            None => format!(
+                "{}{} {}",
+                short(status.staged.get(path)),
+                short(status.unstaged.get(path)),
+                path
+            ),
+        })
+        .chain(status.untracked.iter().map(|path| format!("?? {}", path)))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Compares the tree of `HEAD` with the index, leaving out the paths with
+/// conflicts.
+fn staged_changes(
+    fs: &FileSystem,
+    head: Option<&str>,
+    entries: &[IndexEntry],
+    unmerged: &BTreeMap<String, [bool; 3]>,
+) -> Result<BTreeMap<String, Change>, String> {
+    let mut tree = checkout::flatten_tree(fs, head)?;
+    tree.retain(|path, _| !unmerged.contains_key(path));
+    let mut changes = BTreeMap::new();
+
+    for entry in entries {
+        let path = String::from_utf8_lossy(&entry.path).into_owned();
+        let mode = u32::from(entry.mode);
+        let hash = entry.hash.to_string();
+
+        match tree.remove(&path) {
+            None => {
+                changes.insert(path, Change::Added);
+            }
+            Some(tree_entry) if tree_entry != (mode, hash) => {
+                changes.insert(path, Change::Modified);
+            }
+            Some(_) => {}
+        }
+    }
+
+    for path in tree.into_keys() {
+        changes.insert(path, Change::Deleted);
+    }
+
+    Ok(changes)
+}
+
+/// Compares the index with the working tree. Files whose stat data still
+/// matches their entry are not read, unless they were modified in the same
+/// second the index was written, as then a later change could go unnoticed.
+fn unstaged_changes(
+    fs: &FileSystem,
+    entries: &[IndexEntry],
+) -> Result<BTreeMap<String, Change>, String> {
+    let index_path = index::path(fs);
+
+    let index_mtime = match fs.metadata(&index_path) {
+        Ok(metadata) => metadata.mtime() as u32,
+        Err(_) => 0,
+    };
+
+    let mut changes = BTreeMap::new();
+
+    for entry in entries {
+        let path = String::from_utf8_lossy(&entry.path).into_owned();
+        let file = checkout::worktree_file(fs, &entry.path);
+
+        if !fs.path_exists(&file) {
+            changes.insert(path, Change::Deleted);
+            continue;
+        }
+
+        let metadata = fs.metadata(&file)?;
+        let racy = entry.mtime.seconds >= index_mtime;
+
+        if entry.matches_stat(&metadata) && !racy {
+            continue;
+        }
+
+        let mode = index::normalize_mode(metadata.permissions().mode());
+        let hash = checkout::working_tree_hash(fs, &file)?;
+
+        if mode != u32::from(entry.mode) || hash != Some(entry.hash.to_string()) {
+            changes.insert(path, Change::Modified);
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Lists the files that aren't in the index, a directory without any tracked
+/// file being listed once instead of file by file.
+fn untracked_files(fs: &FileSystem, entries: &[IndexEntry]) -> BTreeSet<String> {
+    let current_directory = fs.current_directory();
+
+    let tracked = entries
+        .iter()
+        .map(|entry| &entry.path[..])
+        .collect::<BTreeSet<&[u8]>>();
+    let has_tracked_files =
+        |directory: &[u8]| tracked.iter().any(|path| path.starts_with(directory));
+
+    // the repository itself is never listed, wherever it is
+    let repository = repository::path(fs);
+    let repository = repository
+        .strip_prefix(&current_directory)
+        .ok()
+        .map(|path| [path.as_os_str().as_bytes(), b"/"].concat());
+
+    let mut untracked = BTreeSet::new();
+
+    for file in fs.get_directory_files_recursively(&PathBuf::from(&current_directory)) {
+        let path = match file.strip_prefix(&current_directory) {
+            Ok(path) => path.as_os_str().as_bytes(),
+            Err(_) => continue,
+        };
+
+        let in_repository = repository
+            .as_ref()
+            .is_some_and(|repository| path.starts_with(repository));
+
+        if in_repository || tracked.contains(path) {
+            continue;
+        }
+
+        let directory = (0..path.len())
+            .filter(|i| path[*i] == b'/')
+            .map(|i| &path[..=i])
+            .find(|directory| !has_tracked_files(directory));
+
+        untracked.insert(String::from_utf8_lossy(directory.unwrap_or(path)).into_owned());
+    }
+
+    untracked
+}
+
+#[cfg(test)]
+fn write_worktree_file(fs: &mut FileSystem, path: &str, contents: &str) {
+    let file = PathBuf::from(format!("{}/{}", fs.current_directory(), path));
+
+    if !fs.path_exists(&file) {
+        fs.create_file(&file);
+    }
+
+    fs.write_file(&file, contents.as_bytes());
+}
+
+#[test]
+fn test_execute_clean() {
+    use crate::object::commit::test_commit;
+    use crate::sub_commands::init;
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    assert_eq!(
+        execute(&fs, false).unwrap(),
+        "On branch master\n\
+         \n\
+         No commits yet\n\
+         \n\
+         nothing to commit (create/copy files and use \"papyrus add\" to track)"
+    );
+
+    let commit = test_commit(&mut fs, &[("README", "readme\n", 0o100644)], vec![]);
+    refs::update(&mut fs, "refs/heads/base", &commit, false).unwrap();
+    checkout::execute(&mut fs, Some("base".to_string()), None).unwrap();
+
+    assert_eq!(
+        execute(&fs, false).unwrap(),
+        "On branch base\n\
+         \n\
+         nothing to commit, working tree clean"
+    );
+    assert_eq!(execute(&fs, true).unwrap(), "");
+}
+
+#[test]
+fn test_execute_with_changes() {
+    use crate::object::commit::test_commit;
+    use crate::sub_commands::{add, init};
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    let commit = test_commit(
+        &mut fs,
+        &[
+            ("README", "readme\n", 0o100644),
+            ("notes.txt", "notes\n", 0o100644),
+            ("src/lib.rs", "\n", 0o100644),
+            ("src/main.rs", "fn main() {}\n", 0o100644),
+        ],
+        vec![],
+    );
+    refs::update(&mut fs, "refs/heads/base", &commit, false).unwrap();
+    checkout::execute(&mut fs, Some("base".to_string()), None).unwrap();
+
+    // staged modification, then modified again in the working tree
+    write_worktree_file(&mut fs, "README", "staged readme\n");
+    let readme = PathBuf::from(format!("{}/README", fs.current_directory()));
+    let sha1 =
+        crate::sub_commands::hash_object::execute(&mut fs, b"staged readme\n", "blob".into(), true)
+            .unwrap();
+    let mut entries = index::read_entries(&fs).unwrap();
+    entries.retain(|entry| entry.path != b"README");
+    entries.push(add::create_index_entry(&fs, &readme, b"README", &sha1).unwrap());
+    index::write_entries(&mut fs, entries);
+    write_worktree_file(&mut fs, "README", "unstaged readme\n");
+
+    // deleted from the working tree only
+    fs.remove_file(&PathBuf::from(format!(
+        "{}/notes.txt",
+        fs.current_directory()
+    )));
+
+    // unstaged modification
+    write_worktree_file(&mut fs, "src/main.rs", "fn main() { todo!() }\n");
+
+    write_worktree_file(&mut fs, "src/util.rs", "\n");
+    write_worktree_file(&mut fs, "docs/guide.md", "guide\n");
+    write_worktree_file(&mut fs, "docs/api/index.md", "api\n");
+
+    assert_eq!(
+        execute(&fs, false).unwrap(),
+        "On branch base\n\
+         \n\
+         Changes to be committed:\n\
+         \tmodified:   README\n\
+         \n\
+         Changes not staged for commit:\n\
+         \tmodified:   README\n\
+         \tdeleted:    notes.txt\n\
+         \tmodified:   src/main.rs\n\
+         \n\
+         Untracked files:\n\
+         \tdocs/\n\
+         \tsrc/util.rs"
+    );
+    assert_eq!(
+        execute(&fs, true).unwrap(),
+        [
+            "MM README",
+            " D notes.txt",
+            " M src/main.rs",
+            "?? docs/",
+            "?? src/util.rs"
+        ]
+        .join("\n")
+    );
+}
+
+#[test]
+fn test_execute_trusts_matching_stat_data() {
+    use crate::object::commit::test_commit;
+    use crate::sub_commands::init;
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    let commit = test_commit(&mut fs, &[("README", "readme\n", 0o100644)], vec![]);
+    refs::update(&mut fs, "refs/heads/base", &commit, false).unwrap();
+    checkout::execute(&mut fs, Some("base".to_string()), None).unwrap();
+
+    let readme = PathBuf::from(format!("{}/README", fs.current_directory()));
+    let mtime = fs.metadata(&readme).unwrap().mtime();
+
+    // the change keeps the stat data, so only reading the file would see it
+    write_worktree_file(&mut fs, "README", "edited\n");
+
+    fs.set_mtime(&index::path(&fs), mtime + 1);
+    assert_eq!(execute(&fs, true).unwrap(), "");
+
+    // written in the same second as the index, the file could have changed
+    // after it without its stat data telling
+    fs.set_mtime(&index::path(&fs), mtime);
+    assert_eq!(execute(&fs, true).unwrap(), " M README");
+}
+
+#[test]
+fn test_execute_new_files_without_commits() {
+    use crate::sub_commands::{add, init};
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    write_worktree_file(&mut fs, "README", "readme\n");
+    write_worktree_file(&mut fs, "LICENSE", "license\n");
+    add::execute(&mut fs, vec![PathBuf::from("README")]).unwrap();
+
+    assert_eq!(
+        execute(&fs, true).unwrap(),
+        "A  README\n\
+         ?? LICENSE"
+    );
+}
+
+#[test]
+fn test_execute_with_conflicts() {
+    use crate::object::ObjectId;
+    use crate::sub_commands::{hash_object, init};
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    write_worktree_file(&mut fs, "added.txt", "ours\n");
+    write_worktree_file(&mut fs, "notes.txt", "<<<<<<< HEAD\n");
+
+    let hash = hash_object::execute(&mut fs, b"ours\n", "blob".into(), true).unwrap();
+    let stage = |path: &str, stage: u8| IndexEntry {
+        hash: ObjectId::from_hex(&hash).unwrap(),
+        stage,
+        path: path.as_bytes().to_vec(),
+        ..Default::default()
+    };
+
+    index::write_entries(
+        &mut fs,
+        vec![
+            stage("added.txt", 2),
+            stage("added.txt", 3),
+            stage("notes.txt", 1),
+            stage("notes.txt", 2),
+            stage("notes.txt", 3),
+        ],
+    );
+
+    assert_eq!(
+        execute(&fs, false).unwrap(),
+        "On branch master\n\
+         \n\
+         No commits yet\n\
+         \n\
+         Unmerged paths:\n\
+         \tboth added:      added.txt\n\
+         \tboth modified:   notes.txt\n\
+         \n\
+         no changes added to commit"
+    );
+    assert_eq!(execute(&fs, true).unwrap(), "AA added.txt\nUU notes.txt");
+}
+
+#[test]
+fn test_execute_with_a_path_that_isnt_utf8() {
+    use crate::diff::Algorithm;
+    use crate::object::ObjectId;
+    use crate::sub_commands::{diff, hash_object, init, ls_files, write_tree};
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    let hash = hash_object::execute(&mut fs, b"latin-1\n", "blob".into(), true).unwrap();
+    index::write_entries(
+        &mut fs,
+        vec![IndexEntry {
+            hash: ObjectId::from_hex(&hash).unwrap(),
+            path: b"caf\xe9.txt".to_vec(),
+            ..Default::default()
+        }],
+    );
+
+    let files: [(&[u8], &[u8]); 2] = [(b"caf\xe9.txt", b"changed\n"), (b"na\xefve.txt", b"new\n")];
+
+    for (path, contents) in files {
+        let file = checkout::worktree_file(&fs, path);
+        fs.create_file(&file);
+        fs.write_file(&file, contents);
+    }
+
+    assert_eq!(
+        execute(&fs, true).unwrap(),
+        "AM caf\u{fffd}.txt\n?? na\u{fffd}ve.txt"
+    );
+    assert_eq!(
+        ls_files::execute(&fs, false, false).unwrap(),
+        "caf\u{fffd}.txt"
+    );
+    assert!(diff::execute(&fs, vec![], false, 3, Algorithm::Myers)
+        .unwrap()
+        .ends_with("-latin-1\n+changed"));
+    assert_eq!(
+        write_tree::execute(&mut fs).unwrap_err(),
+        "error: invalid path 'caf\u{fffd}.txt'\n\
+         fatal: write-tree: error building trees"
+    );
+}
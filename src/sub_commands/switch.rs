@@ -0,0 +1,66 @@
+use crate::fs::FileSystem;
+use crate::refs;
+use crate::sub_commands::checkout;
+
+pub fn execute(
+    fs: &mut FileSystem,
+    target: Option<String>,
+    new_branch: Option<String>,
+    detach: bool,
+) -> Result<String, String> {
+    let target = target
+        .map(|target| checkout::expand_previous(fs, target))
+        .transpose()?;
+
+    match (target, new_branch) {
+        (target, Some(new_branch)) => checkout::create_branch(fs, &new_branch, target),
+        (Some(target), None) if detach => checkout::detach(fs, &target),
+        (Some(target), None) => {
+            if refs::resolve(fs, &format!("refs/heads/{}", target))?.is_none() {
+                return Err(format!(
+                    "fatal: a branch is expected, got '{}'\n\
+                     hint: If you want to detach HEAD at the commit, try again with the --detach option.",
+                    target
+                ));
+            }
+
+            checkout::switch_branch(fs, &target)
+        }
+        (None, None) => Err("fatal: missing branch or commit argument".to_string()),
+    }
+}
+
+#[test]
+fn test_execute_requires_branch() {
+    use crate::fs::Fs;
+    use crate::object::commit::test_commit;
+    use crate::sub_commands::init;
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    let commit = test_commit(&mut fs, &[("README", "readme\n", 0o100644)], vec![]);
+
+    assert_eq!(
+        execute(&mut fs, Some(commit.clone()), None, false).unwrap_err(),
+        format!(
+            "fatal: a branch is expected, got '{}'\n\
+             hint: If you want to detach HEAD at the commit, try again with the --detach option.",
+            commit
+        )
+    );
+
+    assert_eq!(
+        execute(&mut fs, Some(commit.clone()), None, true).unwrap(),
+        format!("HEAD is now at {} test commit", &commit[..7])
+    );
+
+    assert_eq!(
+        execute(&mut fs, None, Some("topic".to_string()), false).unwrap(),
+        "Switched to a new branch 'topic'"
+    );
+    assert_eq!(
+        refs::resolve_name(&fs, "HEAD").unwrap(),
+        ("refs/heads/topic".to_string(), Some(commit))
+    );
+}
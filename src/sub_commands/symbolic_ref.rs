@@ -0,0 +1,85 @@
+use crate::fs::FileSystem;
+use crate::refs;
+
+pub fn execute(
+    fs: &mut FileSystem,
+    name: String,
+    target: Option<String>,
+    short: bool,
+    delete: bool,
+) -> Result<String, String> {
+    if delete {
+        return match refs::read(fs, &name)? {
+            Some(refs::Ref::Symbolic(_)) => {
+                refs::delete(fs, &name)?;
+                Ok("".to_string())
+            }
+            _ => Err(format!("fatal: Cannot delete {}, not a symbolic ref", name)),
+        };
+    }
+
+    if let Some(target) = target {
+        refs::update_symbolic(fs, &name, &target)?;
+        return Ok("".to_string());
+    }
+
+    match refs::read(fs, &name)? {
+        Some(refs::Ref::Symbolic(target)) if short => Ok(refs::shorten(&target).to_string()),
+        Some(refs::Ref::Symbolic(target)) => Ok(target),
+        Some(refs::Ref::Direct(_)) => Err(format!("fatal: ref {} is not a symbolic ref", name)),
+        None => Err(format!("fatal: No such ref: {}", name)),
+    }
+}
+
+#[test]
+fn test_execute_read_head() {
+    use crate::fs::Fs;
+    use crate::sub_commands::init;
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    assert_eq!(
+        execute(&mut fs, "HEAD".to_string(), None, false, false).unwrap(),
+        "refs/heads/master"
+    );
+    assert_eq!(
+        execute(&mut fs, "HEAD".to_string(), None, true, false).unwrap(),
+        "master"
+    );
+}
+
+#[test]
+fn test_execute_write_head() {
+    use crate::fs::Fs;
+    use crate::sub_commands::init;
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+
+    execute(
+        &mut fs,
+        "HEAD".to_string(),
+        Some("refs/heads/main".to_string()),
+        false,
+        false,
+    )
+    .unwrap();
+
+    assert_eq!(
+        execute(&mut fs, "HEAD".to_string(), None, false, false).unwrap(),
+        "refs/heads/main"
+    );
+
+    assert_eq!(
+        execute(
+            &mut fs,
+            "HEAD".to_string(),
+            Some("main".to_string()),
+            false,
+            false
+        )
+        .unwrap_err(),
+        "fatal: Refusing to point HEAD outside of refs/"
+    );
+}
@@ -0,0 +1,65 @@
+use crate::fs::FileSystem;
+use crate::index;
+
+/// Rewrites the index in another on-disk format version, keeping its
+/// entries as they are.
+pub fn execute(fs: &mut FileSystem, index_version: Option<u32>) -> Result<String, String> {
+    if let Some(version) = index_version {
+        if !index::VERSIONS.contains(&version) {
+            return Err(format!(
+                "fatal: index-version {} not in range: {}..{}",
+                version,
+                index::VERSIONS.start(),
+                index::VERSIONS.end()
+            ));
+        }
+
+        let mut index = index::read(fs)?;
+        index.version = version;
+
+        index::write(fs, index);
+    }
+
+    Ok("".to_string())
+}
+
+#[test]
+fn test_execute_index_version() {
+    use crate::fs::Fs;
+    use crate::index::IndexEntry;
+    let mut fs = FileSystem::access();
+
+    let entries = ["src/lib.rs", "src/main.rs", "README.md"]
+        .iter()
+        .map(|path| IndexEntry {
+            path: path.as_bytes().to_vec(),
+            ..Default::default()
+        })
+        .collect::<Vec<IndexEntry>>();
+    index::write_entries(&mut fs, entries);
+
+    assert_eq!(index::read(&fs).unwrap().version, 2);
+
+    execute(&mut fs, Some(4)).unwrap();
+
+    assert_eq!(index::read(&fs).unwrap().version, 4);
+    assert_eq!(
+        index::read_entries(&fs)
+            .unwrap()
+            .iter()
+            .map(|entry| String::from_utf8(entry.path.clone()).unwrap())
+            .collect::<Vec<String>>(),
+        vec!["README.md", "src/lib.rs", "src/main.rs"]
+    );
+
+    // rewriting the index keeps its version
+    let entries = index::read_entries(&fs).unwrap();
+    index::write_entries(&mut fs, entries);
+
+    assert_eq!(index::read(&fs).unwrap().version, 4);
+
+    assert_eq!(
+        execute(&mut fs, Some(5)).unwrap_err(),
+        "fatal: index-version 5 not in range: 2..4"
+    );
+}
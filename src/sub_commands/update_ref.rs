@@ -0,0 +1,171 @@
+use crate::fs::FileSystem;
+use crate::refs;
+use crate::revision;
+
+const NULL_HASH: &str = "0000000000000000000000000000000000000000";
+
+pub fn execute(
+    fs: &mut FileSystem,
+    name: String,
+    new_value: Option<String>,
+    old_value: Option<String>,
+    delete: bool,
+    no_deref: bool,
+) -> Result<String, String> {
+    // `update-ref -d <ref> [<oldvalue>]` takes the old value in the position
+    // where the new one would be
+    let (new_value, old_value) = if delete {
+        if old_value.is_some() {
+            return Err("usage: papyrus update-ref -d <refname> [<old-val>]".to_string());
+        }
+        (None, new_value)
+    } else {
+        match new_value {
+            Some(new_value) => (Some(new_value), old_value),
+            None => {
+                return Err("usage: papyrus update-ref <refname> <new-val> [<old-val>]".to_string())
+            }
+        }
+    };
+
+    let (target, current) = if no_deref {
+        let current = match refs::read(fs, &name)? {
+            Some(refs::Ref::Direct(hash)) => Some(hash),
+            Some(refs::Ref::Symbolic(_)) => refs::resolve(fs, &name)?,
+            None => None,
+        };
+        (name.clone(), current)
+    } else {
+        refs::resolve_name(fs, &name)?
+    };
+
+    if let Some(old_value) = old_value {
+        let expected = if old_value == NULL_HASH {
+            None
+        } else {
+            Some(
+                revision::resolve(fs, &old_value)
+                    .map_err(|_| format!("fatal: {}: not a valid old SHA1", old_value))?,
+            )
+        };
+
+        if expected != current {
+            return Err(match (current, expected) {
+                (Some(current), Some(expected)) => format!(
+                    "fatal: cannot lock ref '{}': is at {} but expected {}",
+                    target, current, expected
+                ),
+                (Some(_), None) => {
+                    format!(
+                        "fatal: cannot lock ref '{}': reference already exists",
+                        target
+                    )
+                }
+                (None, _) => format!("fatal: cannot lock ref '{}': reference is missing", target),
+            });
+        }
+    }
+
+    match new_value {
+        Some(new_value) => {
+            let hash = revision::resolve(fs, &new_value)
+                .map_err(|_| format!("fatal: {}: not a valid SHA1", new_value))?;
+
+            refs::update(fs, &name, &hash, no_deref)?;
+        }
+        None => refs::delete(fs, &target)?,
+    }
+
+    Ok("".to_string())
+}
+
+#[test]
+fn test_execute_create_and_delete() {
+    use crate::fs::Fs;
+    use crate::sub_commands::{hash_object, init};
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+    let hash = hash_object::execute(&mut fs, b"", "tree".into(), true).unwrap();
+
+    execute(
+        &mut fs,
+        "refs/heads/topic".to_string(),
+        Some(hash[..6].to_string()),
+        Some(NULL_HASH.to_string()),
+        false,
+        false,
+    )
+    .unwrap();
+
+    assert_eq!(
+        refs::resolve(&fs, "refs/heads/topic").unwrap(),
+        Some(hash.clone())
+    );
+
+    assert_eq!(
+        execute(
+            &mut fs,
+            "refs/heads/topic".to_string(),
+            Some(hash.clone()),
+            Some(NULL_HASH.to_string()),
+            false,
+            false,
+        )
+        .unwrap_err(),
+        "fatal: cannot lock ref 'refs/heads/topic': reference already exists"
+    );
+
+    execute(
+        &mut fs,
+        "refs/heads/topic".to_string(),
+        Some(hash),
+        None,
+        true,
+        false,
+    )
+    .unwrap();
+
+    assert_eq!(refs::resolve(&fs, "refs/heads/topic").unwrap(), None);
+}
+
+#[test]
+fn test_execute_head_updates_branch() {
+    use crate::fs::Fs;
+    use crate::sub_commands::{hash_object, init};
+    let mut fs = FileSystem::access();
+
+    init::execute(&mut fs, None, false, None).unwrap();
+    let hash = hash_object::execute(&mut fs, b"", "tree".into(), true).unwrap();
+
+    execute(
+        &mut fs,
+        "HEAD".to_string(),
+        Some(hash.clone()),
+        None,
+        false,
+        false,
+    )
+    .unwrap();
+
+    assert_eq!(refs::resolve(&fs, "refs/heads/master").unwrap(), Some(hash));
+}
+
+#[test]
+fn test_execute_invalid_new_value() {
+    use crate::fs::Fs;
+    let mut fs = FileSystem::access();
+
+    assert_eq!(
+        execute(
+            &mut fs,
+            "refs/heads/master".to_string(),
+            Some("deadbeef".to_string()),
+            None,
+            false,
+            false,
+        )
+        .unwrap_err(),
+        "fatal: deadbeef: not a valid SHA1"
+    );
+}
@@ -1,39 +1,148 @@
-use crate::fs::{FileSystem, Fs};
-use crate::index::IndexEntry;
+use crate::fs::FileSystem;
+use crate::index::{self, cached_tree::CachedTree};
+use crate::object;
+use crate::object::tree::{Tree, TreeEntry, TREE_MODE};
 use crate::sub_commands::hash_object;
-use std::convert::TryInto;
+use std::collections::BTreeMap;
 use std::str::from_utf8;
 
+/// Writes the tree of the index, reusing the trees cached in it for the
+/// directories that haven't changed, and caching the ones it writes.
 pub fn execute(fs: &mut FileSystem) -> Result<String, String> {
-    let index_path = format!("{}/.papyrus/index", fs.current_directory());
+    let mut index = index::read(fs)?;
+
+    let unmerged = index
+        .entries
+        .iter()
+        .filter(|index_entry| index_entry.stage > 0)
+        .map(|index_entry| {
+            format!(
+                "{}: unmerged ({})",
+                String::from_utf8_lossy(&index_entry.path),
+                index_entry.hash
+            )
+        })
+        .collect::<Vec<String>>();
 
-    let index_content = fs.get_file_contents_as_bytes(&index_path.into()).unwrap();
-    let index_entries = IndexEntry::parse_from_file(&index_content)?;
+    if !unmerged.is_empty() {
+        return Err(format!(
+            "{}\nfatal: write-tree: error building trees",
+            unmerged.join("\n")
+        ));
+    }
 
-    let mut tree_entries = vec![];
+    // paths added with `add -N` have no contents to write yet, and tree
+    // entries can only be named in UTF-8
+    let entries = index
+        .entries
+        .iter()
+        .filter(|index_entry| !index_entry.intent_to_add)
+        .map(|index_entry| {
+            let name = from_utf8(&index_entry.path).map_err(|_| {
+                format!(
+                    "error: invalid path '{}'\nfatal: write-tree: error building trees",
+                    String::from_utf8_lossy(&index_entry.path)
+                )
+            })?;
 
-    for index_entry in index_entries {
-        let mode = u32::from_be_bytes(index_entry.mode.try_into().unwrap());
-        let path = from_utf8(&index_entry.path).unwrap();
+            Ok(TreeEntry {
+                mode: u32::from(index_entry.mode),
+                name: name.to_string(),
+                hash: index_entry.hash.to_string(),
+            })
+        })
+        .collect::<Result<Vec<TreeEntry>, String>>()?;
 
-        let mode_path = format!("{:o} {}", mode, path);
+    let mut cached_tree = index.cached_tree.take().unwrap_or_default();
+    let hash = write_cached_entries(fs, entries, &mut cached_tree)?;
 
-        let mut tree_entry = vec![];
+    index.cached_tree = Some(cached_tree);
+    index::write(fs, index);
+
+    Ok(hash)
+}
 
-        let mut mode_path_bytes = mode_path.as_bytes().to_vec();
+/// Splits entries whose names are paths into the ones directly in the tree
+/// and the ones in each directory, named relative to it.
+fn split_directories(entries: Vec<TreeEntry>) -> (Tree, BTreeMap<String, Vec<TreeEntry>>) {
+    let mut tree = Tree::default();
+    let mut directories: BTreeMap<String, Vec<TreeEntry>> = BTreeMap::new();
 
-        tree_entry.append(&mut mode_path_bytes);
-        tree_entry.append(&mut b"\x00".to_vec());
-        tree_entry.append(&mut index_entry.sha1.to_vec());
+    for entry in entries {
+        match entry.name.find('/') {
+            Some(index) => {
+                let directory = entry.name[..index].to_string();
+                let name = entry.name[index + 1..].to_string();
 
-        tree_entries.push(tree_entry);
+                directories
+                    .entry(directory)
+                    .or_default()
+                    .push(TreeEntry { name, ..entry });
+            }
+            None => tree.entries.push(entry),
+        }
     }
 
-    hash_object::execute(fs, &tree_entries.concat(), "tree".into(), true)
+    (tree, directories)
+}
+
+fn write_cached_entries(
+    fs: &mut FileSystem,
+    entries: Vec<TreeEntry>,
+    cached_tree: &mut CachedTree,
+) -> Result<String, String> {
+    if let Some(hash) = &cached_tree.hash {
+        if cached_tree.entry_count == entries.len() && object::find(fs, hash).is_ok() {
+            return Ok(hash.clone());
+        }
+    }
+
+    let entry_count = entries.len();
+    let (mut tree, directories) = split_directories(entries);
+
+    cached_tree
+        .children
+        .retain(|child| directories.contains_key(&child.name));
+
+    for (directory, entries) in directories {
+        let hash = write_cached_entries(fs, entries, cached_tree.child_mut(&directory))?;
+
+        tree.entries.push(TreeEntry {
+            mode: TREE_MODE,
+            name: directory,
+            hash,
+        });
+    }
+
+    let hash = hash_object::execute(fs, &tree.parse_into_object(), "tree".into(), true)?;
+
+    cached_tree.entry_count = entry_count;
+    cached_tree.hash = Some(hash.clone());
+
+    Ok(hash)
+}
+
+/// Writes one tree for the given entries, whose names are paths relative to
+/// it, recursing into a subtree for every directory.
+pub fn write_entries(fs: &mut FileSystem, entries: Vec<TreeEntry>) -> Result<String, String> {
+    let (mut tree, directories) = split_directories(entries);
+
+    for (directory, entries) in directories {
+        let hash = write_entries(fs, entries)?;
+
+        tree.entries.push(TreeEntry {
+            mode: TREE_MODE,
+            name: directory,
+            hash,
+        });
+    }
+
+    hash_object::execute(fs, &tree.parse_into_object(), "tree".into(), true)
 }
 
 #[test]
 fn execute_successfully() {
+    use crate::fs::Fs;
     let mut fs = FileSystem::access();
 
     let index_path = format!("{}/.papyrus/index", fs.current_directory());
@@ -75,3 +184,137 @@ fn execute_successfully() {
         ]
     );
 }
+
+#[test]
+fn execute_with_subdirectories() {
+    use crate::fs::Fs;
+    use crate::index::IndexEntry;
+    use crate::object::tree::Tree;
+    use crate::object::ObjectId;
+    let mut fs = FileSystem::access();
+
+    let files: [(&str, &[u8]); 4] = [
+        ("README", b"readme\n"),
+        ("src.rs", b"x\n"),
+        ("src/fs/mod.rs", b"mod fs;\n"),
+        ("src/main.rs", b"fn main() {}\n"),
+    ];
+
+    let mut entries = vec![];
+
+    for (path, contents) in files.iter() {
+        let sha1 = hash_object::execute(&mut fs, contents, "blob".into(), true).unwrap();
+
+        entries.push(IndexEntry {
+            hash: ObjectId::from_hex(&sha1).unwrap(),
+            path: path.as_bytes().to_vec(),
+            ..Default::default()
+        });
+    }
+
+    index::write_entries(&mut fs, entries);
+
+    assert_eq!(
+        execute(&mut fs).unwrap(),
+        "bee4a103cd500299b3277749ce3b29fd31f439a0"
+    );
+
+    let root = Tree::read(&fs, "bee4a103cd500299b3277749ce3b29fd31f439a0").unwrap();
+    let names = root
+        .entries
+        .iter()
+        .map(|entry| format!("{:o} {}", entry.mode, entry.name))
+        .collect::<Vec<String>>();
+
+    assert_eq!(names, vec!["100644 README", "100644 src.rs", "40000 src"]);
+}
+
+#[test]
+fn execute_with_cached_trees() {
+    use crate::fs::Fs;
+    use crate::index::IndexEntry;
+    use crate::object::tree::write_test_tree;
+    let mut fs = FileSystem::access();
+
+    let entry = |fs: &mut FileSystem, path: &str, contents: &[u8]| {
+        let sha1 = hash_object::execute(fs, contents, "blob".into(), true).unwrap();
+
+        IndexEntry {
+            hash: object::ObjectId::from_hex(&sha1).unwrap(),
+            path: path.as_bytes().to_vec(),
+            ..Default::default()
+        }
+    };
+
+    let entries = vec![
+        entry(&mut fs, "README", b"readme\n"),
+        entry(&mut fs, "src/fs/mod.rs", b"mod fs;\n"),
+        entry(&mut fs, "src/main.rs", b"fn main() {}\n"),
+    ];
+    index::write_entries(&mut fs, entries);
+
+    let root = execute(&mut fs).unwrap();
+    let cached_tree = index::read(&fs).unwrap().cached_tree.unwrap();
+
+    assert_eq!(cached_tree.hash, Some(root));
+    assert_eq!(cached_tree.entry_count, 3);
+    assert_eq!(cached_tree.child("src").unwrap().entry_count, 2);
+
+    // changing a file only invalidates the directories holding it
+    let mut entries = index::read_entries(&fs).unwrap();
+    entries[2] = entry(&mut fs, "src/main.rs", b"fn main() { run() }\n");
+    index::write_entries(&mut fs, entries);
+
+    let mut index = index::read(&fs).unwrap();
+    let cached_tree = index.cached_tree.as_mut().unwrap();
+
+    assert_eq!(cached_tree.hash, None);
+    assert_eq!(cached_tree.child("src").unwrap().hash, None);
+    assert!(cached_tree
+        .child("src")
+        .unwrap()
+        .child("fs")
+        .unwrap()
+        .hash
+        .is_some());
+
+    // a valid cached tree is used as is, without looking at its entries
+    let other_tree = write_test_tree(&mut fs);
+    cached_tree.child_mut("src").child_mut("fs").hash = Some(other_tree.clone());
+    index::write(&mut fs, index);
+
+    let root = execute(&mut fs).unwrap();
+
+    assert_eq!(
+        crate::revision::resolve(&fs, &format!("{}:src/fs", root)).unwrap(),
+        other_tree
+    );
+}
+
+#[test]
+fn execute_with_unmerged_entries() {
+    use crate::fs::Fs;
+    use crate::index::IndexEntry;
+    use crate::object::ObjectId;
+    let mut fs = FileSystem::access();
+
+    let hash = hash_object::hash(b"ours\n", "blob");
+
+    index::write_entries(
+        &mut fs,
+        vec![IndexEntry {
+            hash: ObjectId::from_hex(&hash).unwrap(),
+            stage: 2,
+            path: b"file.txt".to_vec(),
+            ..Default::default()
+        }],
+    );
+
+    assert_eq!(
+        execute(&mut fs).unwrap_err(),
+        format!(
+            "file.txt: unmerged ({})\nfatal: write-tree: error building trees",
+            hash
+        )
+    );
+}